@@ -5,20 +5,20 @@ use std::hint::black_box;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use rusty_rolodex::prelude::{Contact, ContactManager, contact, manager::IndexUpdateType};
-use std::fs;
-use std::path::PathBuf;
-use uuid::Uuid as BenchUuid;
+use rusty_rolodex::storage::file::{JsonStorage, TxtStorage};
+use rusty_rolodex::storage::memory::MemStore;
+use tempfile::TempDir;
 
 fn make_store_with_n(n: usize) -> ContactManager {
     let mut rng = StdRng::seed_from_u64(42); // Seeded for reproducibility in benchmarks
 
     // Randomized and more realistic data pools
-    let first_names = vec![
+    let first_names = [
         "Alice", "Bob", "Charlie", "Diana", "Eve", "Frank", "Grace", "Henry", "Ivy", "Jack",
         "Kate", "Liam", "Mia", "Noah", "Olivia", "Peter", "Quinn", "Ryan", "Sophia", "Tyler",
         "Uma", "Victor", "Wendy", "Xander", "Yara", "Zoe",
     ];
-    let last_names = vec![
+    let last_names = [
         "Smith",
         "Johnson",
         "Williams",
@@ -40,7 +40,7 @@ fn make_store_with_n(n: usize) -> ContactManager {
         "Jackson",
         "Martin",
     ];
-    let domains = vec![
+    let domains = [
         "gmail.com",
         "yahoo.com",
         "hotmail.com",
@@ -49,7 +49,7 @@ fn make_store_with_n(n: usize) -> ContactManager {
         "test.org",
         "company.net",
     ];
-    let tags = vec![
+    let tags = [
         "friends",
         "work",
         "family",
@@ -59,7 +59,9 @@ fn make_store_with_n(n: usize) -> ContactManager {
         "",
     ];
 
-    let mut storage = ContactManager::new().expect("Store not created");
+    let mut storage = ContactManager::builder(Box::new(MemStore::new()))
+        .build()
+        .expect("Store not created");
     storage.mem = (0..n)
         .map(|_| {
             let first = first_names[rng.gen_range(0..first_names.len())];
@@ -108,16 +110,10 @@ fn bench_list(c: &mut Criterion) {
         b.iter(|| {
             let mut filtered_contacts: Vec<&Contact> = storage
                 .mem
-                .iter()
-                .filter_map(|(_, cont)| {
-                    if cont.tag.to_lowercase() == "friends" {
-                        Some(cont)
-                    } else {
-                        None
-                    }
-                })
+                .values()
+                .filter(|cont| cont.tag.to_lowercase() == "friends")
                 .collect();
-            filtered_contacts.sort_by(|a, b| a.email.to_lowercase().cmp(&b.email.to_lowercase()));
+            filtered_contacts.sort_by_key(|c| c.email.to_lowercase());
             filtered_contacts.reverse();
 
             black_box(filtered_contacts);
@@ -258,32 +254,18 @@ fn bench_save_store_json(c: &mut Criterion) {
     c.bench_function("save_20k_json_contacts", |b| {
         b.iter_batched(
             || {
-                // Setup: create temp dir and enter it
-                let base = std::env::temp_dir()
-                    .join(format!("rusty-rolodex-bench-json-{}", BenchUuid::new_v4()));
-                fs::create_dir_all(&base).expect("create temp dir");
-
-                // chdir into temp dir so relative storage path is isolated
-                std::env::set_current_dir(&base).expect("chdir into tempdir");
-
-                unsafe {
-                    std::env::set_var("STORAGE_CHOICE", "json");
-                }
-
-                // Build store in setup (excluded from measured timing)
-                let storage = make_store_with_n(20_000);
+                // Setup: a store backed by a json file in a temp dir
+                let dir = tempfile::tempdir().expect("create temp dir");
+                let mut storage = make_store_with_n(20_000);
+                storage.storage = Box::new(JsonStorage::at(&temp_file(&dir, "contacts.json")));
 
-                (storage, base)
+                (storage, dir)
             },
-            |(mut storage, base)| {
+            |(mut storage, dir)| {
                 // Measured: call Store::save (timed)
                 let _ = storage.save();
 
-                // Restore original cwd (robustly) BEFORE removing temp dir
-                restore_to_manifest();
-
-                // Now remove the temporary directory (best-effort)
-                let _ = fs::remove_dir_all(&base);
+                drop(dir);
                 black_box(&storage.mem);
             },
             BatchSize::SmallInput,
@@ -295,41 +277,23 @@ fn bench_read_store_json(c: &mut Criterion) {
     c.bench_function("read_20k_json_contacts", |b| {
         b.iter_batched(
             || {
-                // Setup: create temp dir and enter it
-                let base = std::env::temp_dir()
-                    .join(format!("rusty-rolodex-bench-json-{}", BenchUuid::new_v4()));
-                fs::create_dir_all(&base).expect("create temp dir");
-                let original_cwd = std::env::current_dir().expect("cwd");
-
-                std::env::set_current_dir(&base).expect("chdir into tempdir");
-                unsafe {
-                    std::env::set_var("STORAGE_CHOICE", "json");
-                }
-
                 // Build and save the store so there's something to load
+                let dir = tempfile::tempdir().expect("create temp dir");
+                let path = temp_file(&dir, "contacts.json");
                 let mut storage = make_store_with_n(20_000);
+                storage.storage = Box::new(JsonStorage::at(&path));
                 storage.save().expect("setup save failed");
 
-                // restore cwd so setup leaves global state clean; measured closure will chdir into base
-                std::env::set_current_dir(&original_cwd).expect("restore cwd after setup");
-
-                base
+                (path, dir)
             },
-            |base| {
-                // Measured: chdir into temp dir and call load()
-                if let Err(e) = std::env::set_current_dir(&base) {
-                    eprintln!("warning: failed to chdir into bench base: {}", e);
-                    return;
-                }
-
-                // Create a Store instance that picks up JSON path via env var/current dir
-                let mut manager = ContactManager::new().expect("failed to create store for load");
-                let _ = manager.load();
-
-                // Restore cwd to manifest dir BEFORE removing tempdir
-                restore_to_manifest();
-
-                let _ = std::fs::remove_dir_all(&base);
+            |(path, dir)| {
+                // Measured: load and index the saved contacts
+                let manager = ContactManager::builder(Box::new(JsonStorage::at(&path)))
+                    .build()
+                    .expect("failed to load store");
+
+                drop(dir);
+                black_box(&manager.mem);
             },
             BatchSize::SmallInput,
         );
@@ -340,26 +304,16 @@ fn bench_save_store_txt(c: &mut Criterion) {
     c.bench_function("save_20k_txt_contacts", |b| {
         b.iter_batched(
             || {
-                let base = std::env::temp_dir()
-                    .join(format!("rusty-rolodex-bench-txt-{}", BenchUuid::new_v4()));
-                fs::create_dir_all(&base).expect("create temp dir");
-
-                std::env::set_current_dir(&base).expect("chdir into tempdir");
-                unsafe {
-                    std::env::set_var("STORAGE_CHOICE", "txt");
-                }
-
-                let storage = make_store_with_n(20_000);
+                let dir = tempfile::tempdir().expect("create temp dir");
+                let mut storage = make_store_with_n(20_000);
+                storage.storage = Box::new(TxtStorage::at(&temp_file(&dir, "contacts.txt")));
 
-                (storage, base)
+                (storage, dir)
             },
-            |(mut storage, base)| {
+            |(mut storage, dir)| {
                 let _ = storage.save();
 
-                // Restore original cwd (robustly) BEFORE removing temp dir
-                restore_to_manifest();
-
-                let _ = fs::remove_dir_all(&base);
+                drop(dir);
                 black_box(&storage.mem);
             },
             BatchSize::SmallInput,
@@ -371,49 +325,29 @@ fn bench_read_store_txt(c: &mut Criterion) {
     c.bench_function("read_20k_txt_contacts", |b| {
         b.iter_batched(
             || {
-                let base = std::env::temp_dir()
-                    .join(format!("rusty-rolodex-bench-txt-{}", BenchUuid::new_v4()));
-                fs::create_dir_all(&base).expect("create temp dir");
-                let original_cwd = std::env::current_dir().expect("cwd");
-
-                std::env::set_current_dir(&base).expect("chdir into tempdir");
-                unsafe {
-                    std::env::set_var("STORAGE_CHOICE", "txt");
-                }
-
+                let dir = tempfile::tempdir().expect("create temp dir");
+                let path = temp_file(&dir, "contacts.txt");
                 let mut storage = make_store_with_n(20_000);
+                storage.storage = Box::new(TxtStorage::at(&path));
                 storage.save().expect("setup save failed");
 
-                std::env::set_current_dir(&original_cwd).expect("restore cwd after setup");
-
-                base
+                (path, dir)
             },
-            |base| {
-                std::env::set_current_dir(&base).expect("chdir into tempdir for read");
-
-                let mut manager = ContactManager::new().expect("failed to create store for load");
-                let _ = manager.load();
-
-                // Restore original to manifest dir BEFORE removing temp dir
-                restore_to_manifest();
+            |(path, dir)| {
+                let manager = ContactManager::builder(Box::new(TxtStorage::at(&path)))
+                    .build()
+                    .expect("failed to load store");
 
-                let _ = fs::remove_dir_all(&base);
+                drop(dir);
+                black_box(&manager.mem);
             },
             BatchSize::SmallInput,
         );
     });
 }
 
-fn restore_to_manifest() {
-    let manifest_dir: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    if std::env::set_current_dir(&manifest_dir).is_ok() {
-        return;
-    }
-    eprintln!(
-        "warning: fallback restore to manifest_dir {:?} failed. trying '/' fallback.",
-        manifest_dir
-    );
-    let _ = std::env::set_current_dir("/");
+fn temp_file(dir: &TempDir, name: &str) -> String {
+    dir.path().join(name).to_string_lossy().to_string()
 }
 
 fn configure() -> Criterion {
@@ -3,9 +3,13 @@ use clap::{Parser, Subcommand, ValueEnum};
 #[derive(Parser, Debug)]
 #[command(name = "rolodex", version, about = "Simple Contact Book")]
 pub struct Cli {
-    /// Storage choice (mem, txt, json) are available
-    #[arg(long, env = "STORAGE_CHOICE", default_value_t = String::from("json"))]
-    pub storage_choice: String,
+    /// Storage choice (json, txt, bin, jsonl, events, dir, remote, mem) for the default book, overriding the config
+    #[arg(long)]
+    pub storage_choice: Option<String>,
+
+    /// Address book to work on (default: the one set with `book default`)
+    #[arg(long, env = "ROLODEX_BOOK", global = true)]
+    pub book: Option<String>,
 
     #[command(subcommand)]
     pub command: Commands,
@@ -115,6 +119,38 @@ pub enum Commands {
         /// **Only** Optional if **remote url** has been configured in envionment
         #[arg(short, long)]
         src: Option<String>,
+
+        /// How contacts existing on both sides are merged
+        #[arg(short, long, value_enum, default_value_t = SyncPolicyOption::LastWriteWins)]
+        policy: SyncPolicyOption,
+
+        /// What happens when a contact deleted on one side was edited later on the other
+        #[arg(long, value_enum, default_value_t = DeletionRuleOption::FollowPolicy)]
+        on_delete: DeletionRuleOption,
+
+        /// Show what the import would change without saving anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Format of the dry-run report
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+
+        /// Column layout of a CSV source; detected from its header if not given
+        #[arg(long, value_enum)]
+        csv_dialect: Option<CsvDialectOption>,
+
+        /// TOML file naming the columns of a CSV source, for layouts no dialect knows
+        #[arg(long, conflicts_with = "csv_dialect")]
+        csv_mapping: Option<String>,
+
+        /// Import nothing if any record is invalid, instead of setting those records aside
+        #[arg(long)]
+        strict: bool,
+
+        /// File the invalid records are written to (default: <SRC>.rejects.<ext> next to the source)
+        #[arg(long, conflicts_with = "strict")]
+        rejects: Option<String>,
     },
 
     /// Export contacts to a storage destination
@@ -129,6 +165,226 @@ pub enum Commands {
         /// **Only** Optional if **remote url** has been confiqured in envionment
         #[arg(short, long)]
         des: Option<String>,
+
+        /// Column layout of a CSV destination
+        #[arg(long, value_enum, default_value_t = CsvDialectOption::Native)]
+        csv_dialect: CsvDialectOption,
+    },
+
+    /// Pull from a storage, merge, and push the merged book back to it
+    Sync {
+        /// Storage to sync with is:
+        /// f = A file
+        /// r = Remote storage
+        /// g = A bare git repository
+        #[arg(short, long)]
+        with: ImportExportOption,
+
+        /// File path or remote URL of the storage to sync with
+        /// **Only** Optional if **remote url** has been configured in envionment
+        #[arg(short, long)]
+        src: Option<String>,
+
+        /// How contacts existing on both sides are merged
+        #[arg(short, long, value_enum, default_value_t = SyncPolicyOption::LastWriteWins)]
+        policy: SyncPolicyOption,
+
+        /// What happens when a contact deleted on one side was edited later on the other
+        #[arg(long, value_enum, default_value_t = DeletionRuleOption::FollowPolicy)]
+        on_delete: DeletionRuleOption,
+
+        /// Show what would be pulled and pushed without saving anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Format of the report
+        #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+    },
+
+    /// List and resolve sync conflicts waiting in the queue
+    Conflicts {
+        #[command(subcommand)]
+        action: Option<ConflictAction>,
+    },
+
+    /// Create, list, rename and delete address books
+    Book {
+        #[command(subcommand)]
+        action: Option<BookAction>,
+    },
+
+    /// Read and change settings
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+
+    /// Move a contact to another address book
+    Move {
+        /// Name of contact to move
+        #[arg(long)]
+        name: String,
+
+        /// Contact number, needed when the name matches multiple contacts
+        #[arg(long)]
+        phone: Option<String>,
+
+        /// Book to move the contact to
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Copy a contact to another address book
+    Copy {
+        /// Name of contact to copy
+        #[arg(long)]
+        name: String,
+
+        /// Contact number, needed when the name matches multiple contacts
+        #[arg(long)]
+        phone: Option<String>,
+
+        /// Book to copy the contact to
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Rewrite the active book in the current storage schema, optionally in another medium
+    Migrate {
+        /// Medium to convert the book to (default: keep its medium)
+        #[arg(long, value_enum)]
+        to: Option<BookMediumOption>,
+    },
+
+    /// Encrypt the active book at rest, change its passphrase, or decrypt it
+    Encryption {
+        #[command(subcommand)]
+        action: EncryptionAction,
+    },
+
+    /// Fold the event log of the active book into a checkpoint
+    Compact,
+
+    /// Show the git history of the active book (see `git.enabled`)
+    Log {
+        /// Number of commits to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EncryptionAction {
+    /// Encrypt the book's file with a passphrase from the configured key source
+    Enable,
+
+    /// Re-encrypt the book under a new passphrase (read from ROLODEX_NEW_PASSPHRASE or a prompt)
+    ChangePassphrase,
+
+    /// Decrypt the book's file for good, or write a plaintext copy with --out
+    Decrypt {
+        /// File to write the plaintext copy to, leaving the book encrypted
+        #[arg(long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BookAction {
+    /// List address books (default)
+    List,
+
+    /// Create an address book
+    Create {
+        /// Book name (letters, digits, '-' and '_')
+        name: String,
+
+        /// Storage medium of the book
+        #[arg(long, value_enum, default_value_t = BookMediumOption::Json)]
+        medium: BookMediumOption,
+
+        /// File path or remote URL of the book
+        /// (default: ./.instance/books/<name>.<medium>, or the remote configured in environment)
+        #[arg(long)]
+        path: Option<String>,
+    },
+
+    /// Rename an address book
+    Rename { name: String, new_name: String },
+
+    /// Delete an address book
+    Delete {
+        name: String,
+
+        /// Also delete the book's contacts file
+        #[arg(long)]
+        purge: bool,
+    },
+
+    /// Set the book used when --book isn't given
+    Default { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the value in effect for a setting
+    Get { key: String },
+
+    /// Write a setting to the user config file
+    Set {
+        key: String,
+        value: String,
+
+        /// Write to the project config file (./rolodex.toml) instead
+        #[arg(long)]
+        project: bool,
+    },
+
+    /// Store a secret (such as remote.api_key) in the credential store
+    SetSecret {
+        key: String,
+
+        /// The secret (read from stdin if not given, to keep it out of shell history)
+        value: Option<String>,
+    },
+
+    /// Print every setting in effect (default)
+    List,
+
+    /// Print where the config files are looked for
+    Path,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConflictAction {
+    /// List queued conflicts (default)
+    List,
+
+    /// Resolve queued conflicts, prompting for each one unless --take is given
+    Resolve {
+        /// Number of the conflict as shown by `conflicts list` (default: all, in order)
+        index: Option<usize>,
+
+        /// Side to keep without prompting
+        #[arg(long)]
+        take: Option<ConflictSide>,
+
+        /// Merged name (with --take merge)
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Merged phone number (with --take merge)
+        #[arg(long)]
+        phone: Option<String>,
+
+        /// Merged email address (with --take merge)
+        #[arg(long)]
+        email: Option<String>,
+
+        /// Merged tag (with --take merge)
+        #[arg(long)]
+        tag: Option<String>,
     },
 }
 
@@ -157,4 +413,85 @@ pub enum ImportExportOption {
 
     /// Remote Storage
     R,
+
+    /// Bare git repository, e.g. on a shared filesystem (see `git.remote`)
+    G,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum SyncPolicyOption {
+    /// Keep local contacts, only pull in new ones
+    LocalAlwaysWins,
+
+    /// Overwrite local contacts with the remote version
+    RemoteAlwaysWins,
+
+    /// Whichever contact was updated last wins all fields
+    LastWriteWins,
+
+    /// Merge field by field against the last sync with this source
+    FieldLevelMerge,
+
+    /// Queue every disagreement for `conflicts resolve`
+    Manual,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum DeletionRuleOption {
+    /// The sync policy settles it like any other change
+    FollowPolicy,
+
+    /// The edit brings the contact back
+    EditResurrects,
+
+    /// The deletion is kept and the edit is dropped
+    DeleteWins,
+
+    /// Queue a conflict for `conflicts resolve`
+    Ask,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum BookMediumOption {
+    Json,
+    Txt,
+    /// Compact binary file, quickest to load for large books
+    Bin,
+    /// One contact per line, appended to on add
+    Jsonl,
+    /// Log of changes, replayed on load
+    Events,
+    /// Directory with one file per contact
+    Dir,
+    Remote,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum CsvDialectOption {
+    /// rolodex's own columns, as written by export
+    Native,
+    /// Google Contacts
+    Google,
+    /// Outlook and Outlook.com
+    Outlook,
+    /// Common column names such as Name, Phone, E-mail and Group
+    Generic,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    Table,
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum ConflictSide {
+    /// Keep the local version
+    Local,
+
+    /// Take the remote version
+    Remote,
+
+    /// Start from local and apply the --name/--phone/--email/--tag given
+    Merge,
 }
@@ -1,56 +1,142 @@
 use crate::{
-    domain::contact,
+    config::{CONFIG_KEYS, Config, project_config_path, set_in_file, user_config_path},
     prelude::{
         AppError, ContactStore, RemoteStorage,
-        command::{Cli, Commands, ImportExportOption, SearchKey, SortKey},
-        contact::{Contact, EMAIL_REQ_MESSAGE, NAME_REQ_MESSAGE, PHONE_REQ_MESSAGE},
-        file::{CsvStorage, JsonStorage},
+        command::{
+            BookAction, BookMediumOption, Cli, Commands, ConfigAction, ConflictAction,
+            ConflictSide, CsvDialectOption, DeletionRuleOption, EncryptionAction,
+            ImportExportOption, ReportFormat, SearchKey, SortKey, SyncPolicyOption,
+        },
+        contact::Contact,
+        file::{self, CsvStorage, JsonStorage, JsonlStorage},
         manager::{ContactManager, IndexUpdateType},
+        render::RenderFormat,
+        sync::{
+            CONFLICT_FIELDS, ConflictResolution, DeletionRule, FieldLevelMerge, LastWriteWins,
+            LocalAlwaysWins, Manual, RemoteAlwaysWins, SyncConflict, SyncOutcome, SyncPolicy,
+            SyncReport, WithDeletionRule, field_value,
+        },
+        uuid::Uuid,
+    },
+    storage::{
+        self, StorageMediums,
+        binary::BinaryStorage,
+        books::{self, BookRegistry},
+        compression, create_file_parent,
+        credentials::{self, PASSPHRASE_KEY, SECRET_KEYS},
+        csv_dialect::{ColumnMapping, CsvDialect},
+        encryption::{self, EncryptedStorage, KeySource, NEW_PASSPHRASE_ENV},
+        event_log::{self, EventLogStorage},
+        git::{GitBook, GitRemoteStorage},
+        import::ValidatedImport,
+        ldif::LdifStorage,
+        remote::{RemoteState, is_valid_url},
+        schema::CURRENT_VERSION,
+        sync_state::book_state_dir,
+        vcard::VcardStorage,
     },
-    storage::{StorageMediums, remote::is_valid_url},
 };
 use clap::Parser;
-use std::{env, path::Path, process::exit};
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::Path,
+    process::exit,
+};
 
 pub fn run_app() -> Result<(), AppError> {
     let cli = Cli::parse();
 
-    unsafe {
-        env::set_var("STORAGE_CHOICE", &cli.storage_choice);
+    let mut config = Config::load()?;
+    if let Some(book) = &cli.book {
+        config.book = Some(book.clone());
+    }
+    if let Some(choice) = &cli.storage_choice {
+        config.set("storage.choice", choice)?;
     }
 
-    let mut manager = ContactManager::new()?;
+    // Managing books and settings doesn't need any book loaded
+    let command = match cli.command {
+        Commands::Book { action } => {
+            return run_book_action(action.unwrap_or(BookAction::List), &config);
+        }
+        Commands::Config { action } => {
+            return run_config_action(action.unwrap_or(ConfigAction::List), &config);
+        }
+        command => command,
+    };
 
-    println!(
-        "Current storage choice is: {}",
-        manager.storage.get_medium()
+    let mut manager = ContactManager::from_config(&config)?;
+    for skipped in manager.storage.skipped() {
+        eprintln!("Warning: skipped {skipped}");
+    }
+
+    // Keep stdout parseable when a JSON report is requested
+    let json_output = matches!(
+        command,
+        Commands::Import {
+            dry_run: true,
+            format: ReportFormat::Json,
+            ..
+        } | Commands::Sync {
+            format: ReportFormat::Json,
+            ..
+        }
     );
 
-    match cli.command {
+    if !json_output {
+        println!(
+            "Current storage choice is: {}",
+            manager.storage.get_medium()
+        );
+    }
+
+    let change = change_message(&command);
+    let target_book = match &command {
+        Commands::Move { to, .. } | Commands::Copy { to, .. } => Some(to.clone()),
+        _ => None,
+    };
+    // A migration swaps the manager's storage, and the old file goes in the same commit
+    let source = (
+        manager.storage.get_medium().to_string(),
+        manager.storage.get_location(),
+    );
+    run_command(command, &mut manager, &config)?;
+
+    if let Some(message) = change
+        && config.git.enabled
+    {
+        commit_book(manager.storage.as_ref(), &message, Some(&source))?;
+        if let Some(to) = target_book {
+            commit_book(books::book_storage(&to, &config)?.as_ref(), &message, None)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_command(
+    command: Commands,
+    manager: &mut ContactManager,
+    config: &Config,
+) -> Result<(), AppError> {
+    let is_move = matches!(command, Commands::Move { .. });
+
+    match command {
         Commands::Add {
             name,
             phone,
             email,
             tag,
         } => {
-            let new_contact = Contact::new(
+            let mut new_contact = Contact::new(
                 name,
                 phone,
                 email.unwrap_or_default(),
                 tag.unwrap_or_default(),
             );
+            new_contact.touch_after(&mut manager.clock, &manager.sync_state.device);
 
-            if !new_contact.validate_name()? {
-                return Err(AppError::Validation(NAME_REQ_MESSAGE.to_string()));
-            }
-
-            if !new_contact.validate_number()? {
-                return Err(AppError::Validation(PHONE_REQ_MESSAGE.to_string()));
-            }
-
-            if !new_contact.validate_email()? {
-                return Err(AppError::Validation(EMAIL_REQ_MESSAGE.to_string()));
-            }
+            new_contact.validate()?;
 
             if new_contact.already_exist(&manager.contact_list()[0..]) {
                 return Err(AppError::Validation(
@@ -58,9 +144,10 @@ pub fn run_app() -> Result<(), AppError> {
                 ));
             }
 
+            let id = new_contact.id;
             manager.add_contact(new_contact);
 
-            manager.save()?;
+            manager.save_contact(&id)?;
 
             println!("Contact added successfully");
             Ok(())
@@ -73,14 +160,8 @@ pub fn run_app() -> Result<(), AppError> {
             if let Some(tag) = tag {
                 contact_list = manager
                     .mem
-                    .iter()
-                    .filter_map(|(_, cont)| {
-                        if cont.tag.to_lowercase() == tag.to_lowercase() && !cont.deleted {
-                            Some(cont)
-                        } else {
-                            None
-                        }
-                    })
+                    .values()
+                    .filter(|cont| cont.tag.to_lowercase() == tag.to_lowercase() && !cont.deleted)
                     .collect();
             } else {
                 contact_list = manager.contact_list();
@@ -125,6 +206,7 @@ pub fn run_app() -> Result<(), AppError> {
             new_email,
             new_tag,
         } => {
+            let device = manager.sync_state.device.clone();
             let desired_contact = Contact::new(name, phone, "".to_string(), "".to_string());
             let ids = manager
                 .get_ids_by_name(&desired_contact.name)
@@ -174,7 +256,7 @@ pub fn run_app() -> Result<(), AppError> {
                     contact.tag = tag;
                 }
 
-                contact.updated_at = contact::Utc::now();
+                contact.touch_after(&mut manager.clock, &device);
             } else {
                 return Err(AppError::NotFound("Contact".to_string()));
             }
@@ -210,7 +292,7 @@ pub fn run_app() -> Result<(), AppError> {
                                     manager.delete_contact(id)?;
                                     manager.save()?;
                                     println!("Contact deleted successfully");
-                                    exit(0);
+                                    return Ok(());
                                 }
                             }
 
@@ -279,26 +361,85 @@ pub fn run_app() -> Result<(), AppError> {
         }
 
         // Import contacts into storage from a storage
-        Commands::Import { from, src } => {
+        Commands::Import {
+            from,
+            src,
+            policy,
+            on_delete,
+            dry_run,
+            format,
+            csv_dialect,
+            csv_mapping,
+            strict,
+            rejects,
+        } => {
             let mut source: String = String::new();
 
             if let Some(path) = src {
                 source = path;
             }
+            let dialect = match csv_mapping {
+                Some(path) => Some(CsvDialect::Mapping(ColumnMapping::from_file(&path)?)),
+                None => csv_dialect.map(parse_csv_dialect),
+            };
+            let storage: Box<dyn ContactStore> =
+                parse_import_export_storage_type(from, &source, false, dialect, config)?;
+            let storage = ValidatedImport::check(storage)?;
+
+            if !storage.rejected.is_empty() {
+                let rejected: Vec<String> =
+                    storage.rejected.iter().map(|r| r.to_string()).collect();
+                if strict {
+                    return Err(AppError::Validation(format!(
+                        "{} invalid record(s), nothing imported:\n{}",
+                        rejected.len(),
+                        rejected.join("\n")
+                    )));
+                }
+
+                for rejection in &rejected {
+                    eprintln!("Rejected {rejection}");
+                }
+                // A dry run only reports them
+                if !dry_run {
+                    let path = rejects.unwrap_or_else(|| storage.default_reject_path());
+                    storage.write_rejects(&path)?;
+                    eprintln!("{} invalid record(s) written to {path}", rejected.len());
+                }
+            }
+            let storage: Box<dyn ContactStore> = Box::new(storage);
+
+            let policy = parse_sync_policy(policy, on_delete, storage.as_ref(), manager)?;
+
+            if dry_run {
+                let report = manager.preview_import(storage.as_ref(), policy.as_ref())?;
+                print_sync_report(&report, format)?;
+                return Ok(());
+            }
+
             println!("Importing");
 
             println!("Source: {}", source);
-            let storage: Box<dyn ContactStore> =
-                parse_import_export_storage_type(from, &source, false)?;
 
-            manager.import_contacts_from_storage(storage)?;
+            let conflicts = manager.import_contacts_from_storage(storage, policy.as_ref())?;
             println!("Imported");
             println!("Contacts imported successfully from {:?}.", source);
+
+            if !conflicts.is_empty() {
+                println!(
+                    "{} conflict(s) need your attention, see `rolodex conflicts`",
+                    conflicts.len()
+                );
+            }
             Ok(())
         }
 
         // contacts into storage to a storage
-        Commands::Export { to, des } => {
+        Commands::Export {
+            to,
+            des,
+            csv_dialect,
+        } => {
             let mut source: String = String::new();
 
             if let Some(path) = des {
@@ -306,13 +447,677 @@ pub fn run_app() -> Result<(), AppError> {
             }
             println!("Exporting");
 
-            let storage = parse_import_export_storage_type(to, &source, true)?;
+            // Pages for reading and printing, not storage
+            if matches!(to, ImportExportOption::F)
+                && let Some(format) = RenderFormat::from_path(&source)
+            {
+                let book = books::active_book(config)?;
+                let title = match book.as_str() {
+                    books::DEFAULT_BOOK => "Contacts".to_string(),
+                    name => format!("Contacts: {name}"),
+                };
+                let page = format.render(&title, &manager.contact_list());
+                file::write_file(&source, page.as_bytes())?;
+                println!("Exported");
+                println!("Contacts exported successfully to {:?}.", source);
+                return Ok(());
+            }
+
+            let storage = parse_import_export_storage_type(
+                to,
+                &source,
+                true,
+                Some(parse_csv_dialect(csv_dialect)),
+                config,
+            )?;
 
             manager.export_contacts_to_storage(storage)?;
             println!("Exported");
             println!("Contacts exported successfully to {:?}.", source);
             Ok(())
         }
+
+        // Pull, merge and push back in one step
+        Commands::Sync {
+            with,
+            src,
+            policy,
+            on_delete,
+            dry_run,
+            format,
+        } => {
+            let source = src.unwrap_or_default();
+
+            // A missing file is fine: everything local gets pushed to it
+            let storage = parse_import_export_storage_type(with, &source, true, None, config)?;
+            let policy = parse_sync_policy(policy, on_delete, storage.as_ref(), manager)?;
+
+            if dry_run {
+                let outcome = manager.preview_sync(storage.as_ref(), policy.as_ref())?;
+
+                if format == ReportFormat::Json {
+                    return print_sync_outcome(&outcome, format);
+                }
+                println!("Would pull:");
+                print_sync_report(&outcome.pulled, format)?;
+                println!("Would push:");
+                return print_sync_report(&outcome.pushed, format);
+            }
+
+            let outcome = manager.sync_with_storage(storage, policy.as_ref())?;
+            print_sync_outcome(&outcome, format)
+        }
+
+        // List or resolve queued sync conflicts
+        Commands::Conflicts { action } => {
+            let queue = manager.sync_state.conflicts();
+            let pending = queue.load(manager.storage.as_ref())?;
+
+            if pending.is_empty() {
+                println!("No conflicts");
+                return Ok(());
+            }
+
+            match action.unwrap_or(ConflictAction::List) {
+                ConflictAction::List => {
+                    for (mut i, c) in pending.iter().enumerate() {
+                        i += 1;
+                        println!(
+                            "{i:>3}. {:<20} {:15} fields: {:<30} from: {}",
+                            c.local.name,
+                            c.local.phone,
+                            c.fields.join(", "),
+                            c.source
+                        );
+                    }
+                }
+                ConflictAction::Resolve {
+                    index,
+                    take,
+                    name,
+                    phone,
+                    email,
+                    tag,
+                } => {
+                    // A merge or hand-typed fields only make sense for one contact
+                    let overrides =
+                        name.is_some() || phone.is_some() || email.is_some() || tag.is_some();
+                    if index.is_none() && (matches!(take, Some(ConflictSide::Merge)) || overrides) {
+                        return Err(AppError::Validation(
+                            "--take merge and --name/--phone/--email/--tag need the number of a conflict"
+                                .to_string(),
+                        ));
+                    }
+
+                    let selected: Vec<&SyncConflict> = match index {
+                        Some(i) => match i.checked_sub(1).and_then(|i| pending.get(i)) {
+                            Some(conflict) => vec![conflict],
+                            None => return Err(AppError::NotFound("Conflict".to_string())),
+                        },
+                        None => pending.iter().collect(),
+                    };
+
+                    for conflict in selected {
+                        print_conflict(conflict);
+
+                        let resolution = match take {
+                            Some(ConflictSide::Local) => ConflictResolution::KeepLocal,
+                            Some(ConflictSide::Remote) => ConflictResolution::TakeRemote,
+                            Some(ConflictSide::Merge) => {
+                                let mut merged = conflict.local.clone();
+                                if let Some(name) = &name {
+                                    merged.name = name.clone();
+                                }
+                                if let Some(phone) = &phone {
+                                    merged.phone = phone.clone();
+                                }
+                                if let Some(email) = &email {
+                                    merged.email = email.clone();
+                                }
+                                if let Some(tag) = &tag {
+                                    merged.tag = tag.clone();
+                                }
+                                ConflictResolution::Merged(Box::new(merged))
+                            }
+                            None => match prompt_resolution(conflict)? {
+                                Some(resolution) => resolution,
+                                None => {
+                                    println!("Skipped");
+                                    continue;
+                                }
+                            },
+                        };
+
+                        if let ConflictResolution::Merged(merged) = &resolution {
+                            merged.validate()?;
+                        }
+
+                        manager.resolve_conflict(conflict, resolution)?;
+                        println!("Conflict resolved");
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        // Move or copy a contact to another book
+        Commands::Move { name, phone, to } | Commands::Copy { name, phone, to } => {
+            if to == books::active_book(config)? {
+                return Err(AppError::Validation(format!(
+                    "Contact is already in book '{to}'"
+                )));
+            }
+
+            let id = find_contact_id(manager, &name, phone)?;
+            let mut target = ContactManager::builder(books::book_storage(&to, config)?)
+                .with_config(config)?
+                .sync_state_dir(book_state_dir(&to))
+                .build()?;
+
+            if is_move {
+                manager.move_contact_to(&id, &mut target)?;
+                target.save()?;
+                manager.save()?;
+                println!("Contact moved to book '{to}'");
+            } else {
+                manager.copy_contact_to(&id, &mut target)?;
+                target.save()?;
+                println!("Contact copied to book '{to}'");
+            }
+            Ok(())
+        }
+
+        Commands::Migrate { to } => {
+            let book = books::active_book(config)?;
+            let from = manager.storage.get_medium().to_string();
+            let source = manager.storage.get_location();
+            let encrypted = encryption::is_encrypted(&source);
+
+            let Some(to) = to.map(book_medium) else {
+                manager.storage.save_iter(&mut manager.mem.values())?;
+                manager.remove_replaced_files()?;
+                println!("Book '{book}' rewritten in storage schema {CURRENT_VERSION}");
+                return Ok(());
+            };
+            let to_name = to.is_which().to_string();
+
+            // Write the contacts before switching the book over, so nothing is lost on failure
+            if book == books::DEFAULT_BOOK {
+                let target = storage::parse_storage_type_env_config(Some(to), config)?;
+                let target = keep_encrypted(encrypted, target, config)?;
+                target.save_iter(&mut manager.mem.values())?;
+                set_storage_choice(target.get_medium())?;
+                finish_migration(&from, &source, target.as_ref())?;
+                manager.storage = target;
+            } else {
+                let mut registry = BookRegistry::load()?;
+                let moved = registry.set_medium(&book, to)?.clone();
+                let target = books::open_book(&book, &moved, config)?;
+                let target = keep_encrypted(encrypted, target, config)?;
+                target.save_iter(&mut manager.mem.values())?;
+                registry.save()?;
+                finish_migration(&from, &source, target.as_ref())?;
+                manager.storage = target;
+            }
+            manager.remove_replaced_files()?;
+
+            println!(
+                "Book '{book}' migrated from {from} to {to_name} (storage schema {CURRENT_VERSION})"
+            );
+            Ok(())
+        }
+
+        Commands::Encryption { action } => {
+            let book = books::active_book(config)?;
+            let medium: StorageMediums = manager.storage.get_medium().try_into()?;
+            if !medium.is_file() {
+                return Err(AppError::Validation(format!(
+                    "Only json, txt, bin, jsonl and events books can be encrypted, not {}",
+                    medium.is_which()
+                )));
+            }
+            let location = manager.storage.get_location();
+            let encrypted = encryption::is_encrypted(&location);
+
+            match action {
+                EncryptionAction::Enable => {
+                    if encrypted {
+                        return Err(AppError::Validation(format!(
+                            "Book '{book}' is already encrypted"
+                        )));
+                    }
+                    let passphrase = KeySource::from_config(config)?.new_passphrase()?;
+                    let store = storage::file_store(medium, &location, config)?;
+                    let sealed = EncryptedStorage::with_passphrase(store, passphrase);
+                    sealed.save_iter(&mut manager.mem.values())?;
+                    manager
+                        .sync_state
+                        .reseal(manager.storage.as_ref(), &sealed)?;
+                    println!("Book '{book}' is now encrypted");
+                }
+                EncryptionAction::ChangePassphrase => {
+                    if !encrypted {
+                        return Err(AppError::Validation(format!(
+                            "Book '{book}' isn't encrypted (see `rolodex encryption enable`)"
+                        )));
+                    }
+                    let key_source = KeySource::from_config(config)?;
+                    let passphrase = match env::var(NEW_PASSPHRASE_ENV) {
+                        Ok(passphrase) if !passphrase.is_empty() => passphrase,
+                        _ => KeySource::Prompt.new_passphrase()?,
+                    };
+                    let store = storage::file_store(medium, &location, config)?;
+                    let sealed = EncryptedStorage::with_passphrase(store, passphrase.clone());
+                    sealed.save_iter(&mut manager.mem.values())?;
+                    manager
+                        .sync_state
+                        .reseal(manager.storage.as_ref(), &sealed)?;
+
+                    match key_source {
+                        KeySource::Credentials(credentials_config) => {
+                            credentials::open(&credentials_config)?
+                                .set(PASSPHRASE_KEY, &passphrase)?;
+                        }
+                        KeySource::Env => println!(
+                            "Set {} to the new passphrase before opening the book again",
+                            encryption::PASSPHRASE_ENV
+                        ),
+                        KeySource::File(path) => println!(
+                            "Write the new passphrase to {path} before opening the book again"
+                        ),
+                        KeySource::Prompt => {}
+                    }
+                    println!("Passphrase of book '{book}' changed");
+                }
+                EncryptionAction::Decrypt { out } => {
+                    if !encrypted {
+                        return Err(AppError::Validation(format!(
+                            "Book '{book}' isn't encrypted"
+                        )));
+                    }
+                    match out {
+                        Some(out) => {
+                            storage::file_store(medium, &out, config)?
+                                .save_iter(&mut manager.mem.values())?;
+                            println!("Wrote a decrypted copy of book '{book}' to {out}");
+                        }
+                        None => {
+                            let plain = storage::file_store(medium, &location, config)?;
+                            plain.save_iter(&mut manager.mem.values())?;
+                            manager
+                                .sync_state
+                                .reseal(manager.storage.as_ref(), plain.as_ref())?;
+                            println!("Book '{book}' is decrypted");
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        Commands::Compact => {
+            let book = books::active_book(config)?;
+            let location = manager.storage.get_location();
+            if manager.storage.get_medium() != "events" {
+                return Err(AppError::Validation(format!(
+                    "Only events books can be compacted, book '{book}' is {}",
+                    manager.storage.get_medium()
+                )));
+            }
+            if encryption::is_encrypted(&location) {
+                return Err(AppError::Validation(format!(
+                    "Book '{book}' is encrypted, so its log is already compacted on every save"
+                )));
+            }
+
+            let folded = EventLogStorage::at(&location).compact()?;
+            println!("Folded {folded} events of book '{book}' into a checkpoint");
+            Ok(())
+        }
+
+        Commands::Log { limit } => {
+            let book = books::active_book(config)?;
+            let entries = GitBook::open(manager.storage.as_ref())
+                .map_err(|_| {
+                    AppError::NotFound(format!(
+                        "Git history of book '{book}' (set git.enabled to keep one)"
+                    ))
+                })?
+                .log(limit)?;
+
+            if entries.is_empty() {
+                println!("No commits yet");
+            }
+            for entry in entries {
+                println!(
+                    "{}  {}  {:<12} {}",
+                    entry.id,
+                    entry.time.format("%Y-%m-%d %H:%M"),
+                    entry.author,
+                    entry.message
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Book { .. } | Commands::Config { .. } => {
+            unreachable!("run before a book is loaded")
+        }
+    }
+}
+
+/// The commit message for a command that changes the book, e.g. "edit: Jane Doe phone".
+/// Commands that only read it give `None`.
+fn change_message(command: &Commands) -> Option<String> {
+    let message = match command {
+        Commands::Add { name, .. } => format!("add: {name}"),
+        Commands::Edit {
+            name,
+            new_name,
+            new_phone,
+            new_email,
+            new_tag,
+            ..
+        } => {
+            let fields: Vec<&str> = [
+                ("name", new_name),
+                ("phone", new_phone),
+                ("email", new_email),
+                ("tag", new_tag),
+            ]
+            .into_iter()
+            .filter(|(_, value)| value.is_some())
+            .map(|(field, _)| field)
+            .collect();
+            format!("edit: {name} {}", fields.join(", "))
+        }
+        Commands::Delete { name, .. } => format!("delete: {name}"),
+        Commands::Import {
+            src,
+            dry_run: false,
+            ..
+        } => format!("import: {}", src.as_deref().unwrap_or_default()),
+        Commands::Sync {
+            src,
+            dry_run: false,
+            ..
+        } => format!("sync: {}", src.as_deref().unwrap_or_default()),
+        Commands::Conflicts {
+            action: Some(ConflictAction::Resolve { .. }),
+        } => "conflicts: resolve".to_string(),
+        Commands::Move { name, to, .. } => format!("move: {name} to {to}"),
+        Commands::Copy { name, to, .. } => format!("copy: {name} to {to}"),
+        Commands::Migrate { to: Some(to) } => {
+            format!("migrate: to {}", book_medium(*to).is_which())
+        }
+        Commands::Migrate { to: None } => format!("migrate: storage schema {CURRENT_VERSION}"),
+        Commands::Encryption { action } => match action {
+            EncryptionAction::Enable => "encryption: enable".to_string(),
+            EncryptionAction::ChangePassphrase => "encryption: change passphrase".to_string(),
+            EncryptionAction::Decrypt { out: None } => "encryption: decrypt".to_string(),
+            EncryptionAction::Decrypt { out: Some(_) } => return None,
+        },
+        Commands::Compact => "compact".to_string(),
+        _ => return None,
+    };
+    Some(message.trim_end().to_string())
+}
+
+/// Commits the book's files after a change, when they are kept in git, along
+/// with those of the `(medium, location)` it was migrated from. Remote and
+/// in-memory books have no files to commit.
+fn commit_book(
+    store: &dyn ContactStore,
+    message: &str,
+    source: Option<&(String, String)>,
+) -> Result<(), AppError> {
+    if matches!(store.get_medium(), "remote" | "mem") {
+        return Ok(());
+    }
+    let mut book = GitBook::init(store)?;
+    if let Some((medium, location)) = source
+        && !matches!(medium.as_str(), "remote" | "mem")
+        && *location != store.get_location()
+    {
+        book = book.including(medium, location)?;
+    }
+    book.commit(message)?;
+    Ok(())
+}
+
+fn book_medium(option: BookMediumOption) -> StorageMediums {
+    match option {
+        BookMediumOption::Json => StorageMediums::Json,
+        BookMediumOption::Txt => StorageMediums::Txt,
+        BookMediumOption::Bin => StorageMediums::Bin,
+        BookMediumOption::Jsonl => StorageMediums::Jsonl,
+        BookMediumOption::Events => StorageMediums::Events,
+        BookMediumOption::Dir => StorageMediums::Dir,
+        BookMediumOption::Remote => StorageMediums::Remote,
+    }
+}
+
+/// Saves the default book's new medium in the config file that sets it:
+/// `./rolodex.toml` if it does, else the user config file.
+fn set_storage_choice(medium: &str) -> Result<(), AppError> {
+    let project = project_config_path();
+    let sets_choice = fs::read_to_string(&project)
+        .ok()
+        .and_then(|data| data.parse::<toml::Table>().ok())
+        .is_some_and(|table| table.get("storage").and_then(|s| s.get("choice")).is_some());
+
+    let path = if sets_choice {
+        project
+    } else {
+        user_config_path().ok_or(AppError::NotFound("User config directory".to_string()))?
+    };
+    set_in_file(&path, "storage.choice", medium)?;
+
+    if env::var("STORAGE_CHOICE").is_ok() {
+        println!(
+            "Note: STORAGE_CHOICE is set and still overrides {}",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Encrypts the migration target too when the book was encrypted.
+/// Remote books can't be encrypted, so those migrations are refused.
+fn keep_encrypted(
+    encrypted: bool,
+    target: Box<dyn ContactStore>,
+    config: &Config,
+) -> Result<Box<dyn ContactStore>, AppError> {
+    if !encrypted {
+        return Ok(target);
+    }
+
+    let medium: StorageMediums = target.get_medium().try_into()?;
+    if medium.is_remote() {
+        return Err(AppError::Validation(
+            "Remote books can't be encrypted: decrypt the book first (`rolodex encryption decrypt`)"
+                .to_string(),
+        ));
+    }
+    let store = storage::file_store(medium, &target.get_location(), config)?;
+    Ok(Box::new(EncryptedStorage::new(
+        store,
+        KeySource::from_config(config)?,
+    )))
+}
+
+/// Removes the file a book was migrated away from. Remote contacts are left in place.
+fn finish_migration(from: &str, source: &str, target: &dyn ContactStore) -> Result<(), AppError> {
+    let is_file = StorageMediums::try_from(from).is_ok_and(|medium| medium.is_file());
+    if is_file && source != target.get_location() && fs::exists(source)? {
+        fs::remove_file(source)?;
+    }
+    let checkpoint = event_log::checkpoint_path(source);
+    if from == "events" && source != target.get_location() && fs::exists(&checkpoint)? {
+        fs::remove_file(checkpoint)?;
+    }
+    if from == "remote" {
+        println!("The contacts at {source} were left in place");
+    }
+    if from == "dir" {
+        println!("The contact files in {source} were left in place");
+    }
+    Ok(())
+}
+
+fn run_book_action(action: BookAction, config: &Config) -> Result<(), AppError> {
+    let mut registry = BookRegistry::load()?;
+
+    match action {
+        BookAction::List => {
+            let active = books::active_book(config)?;
+
+            for name in registry.names() {
+                let marker = if name == active { "*" } else { " " };
+                let storage = books::book_storage(name, config)?;
+                let location = storage.get_location();
+                let encrypted = if encryption::is_encrypted(&location) {
+                    " (encrypted)"
+                } else {
+                    ""
+                };
+                println!(
+                    "{marker} {:<20} {:<8} {location}{encrypted}",
+                    name,
+                    storage.get_medium(),
+                );
+            }
+        }
+        BookAction::Create { name, medium, path } => {
+            let medium = book_medium(medium);
+            if medium.is_remote()
+                && let Some(url) = &path
+                && !is_valid_url(url)
+            {
+                return Err(AppError::Validation(format!("Invalid url: {url}")));
+            }
+
+            registry.create(&name, medium, path)?;
+            registry.save()?;
+            println!("Book '{name}' created");
+        }
+        BookAction::Rename { name, new_name } => {
+            registry.rename(&name, &new_name)?;
+            registry.save()?;
+
+            for (old_dir, new_dir) in [
+                (book_state_dir(&name), book_state_dir(&new_name)),
+                (
+                    RemoteState::book_dir(&name),
+                    RemoteState::book_dir(&new_name),
+                ),
+            ] {
+                if old_dir.exists() {
+                    create_file_parent(&new_dir.to_string_lossy())?;
+                    fs::rename(old_dir, new_dir)?;
+                }
+            }
+            println!("Book '{name}' renamed to '{new_name}'");
+        }
+        BookAction::Delete { name, purge } => {
+            let book = registry.remove(&name)?;
+            registry.save()?;
+
+            for state_dir in [book_state_dir(&name), RemoteState::book_dir(&name)] {
+                if state_dir.exists() {
+                    fs::remove_dir_all(state_dir)?;
+                }
+            }
+            if purge && book.medium != "remote" && Path::new(&book.path).exists() {
+                fs::remove_file(&book.path)?;
+            }
+            println!("Book '{name}' deleted");
+        }
+        BookAction::Default { name } => {
+            registry.set_default(&name)?;
+            registry.save()?;
+            println!("Default book is now '{name}'");
+        }
+    }
+
+    Ok(())
+}
+
+fn run_config_action(action: ConfigAction, config: &Config) -> Result<(), AppError> {
+    match action {
+        ConfigAction::Get { key } => println!("{}", config.get(&key)?),
+        ConfigAction::Set {
+            key,
+            value,
+            project,
+        } => {
+            let path = if project {
+                project_config_path()
+            } else {
+                user_config_path().ok_or(AppError::NotFound("User config directory".to_string()))?
+            };
+            set_in_file(&path, &key, &value)?;
+            println!("Set {key} in {}", path.display());
+        }
+        ConfigAction::SetSecret { key, value } => {
+            if !SECRET_KEYS.contains(&key.as_str()) {
+                return Err(AppError::NotFound(format!(
+                    "Secret '{key}' (known secrets: {})",
+                    SECRET_KEYS.join(", ")
+                )));
+            }
+
+            let secret = match value {
+                Some(value) => value,
+                None => prompt(&format!("{key}: "))?,
+            };
+            if secret.is_empty() {
+                return Err(AppError::Validation("Secret can't be empty".to_string()));
+            }
+
+            credentials::open(&config.credentials)?.set(&key, &secret)?;
+            println!("Stored {key}");
+        }
+        ConfigAction::List => {
+            for (key, _) in CONFIG_KEYS {
+                println!("{key} = {}", config.get(key)?);
+            }
+        }
+        ConfigAction::Path => {
+            if let Some(path) = user_config_path() {
+                println!("user:    {}", path.display());
+            }
+            println!("project: {}", project_config_path().display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds a contact by name, using the phone number when the name matches more than one.
+fn find_contact_id(
+    manager: &ContactManager,
+    name: &str,
+    phone: Option<String>,
+) -> Result<Uuid, AppError> {
+    let ids = manager
+        .get_ids_by_name(name)
+        .ok_or(AppError::NotFound("Contact".to_string()))?;
+
+    match phone {
+        Some(phone) => {
+            let desired_contact =
+                Contact::new(name.to_string(), phone, "".to_string(), "".to_string());
+            ids.into_iter()
+                .find(|id| manager.mem.get(id) == Some(&desired_contact))
+                .ok_or(AppError::NotFound("Contact".to_string()))
+        }
+        None if ids.len() > 1 => Err(AppError::Validation(format!(
+            "Found multiple contacts with this name: {name}, please provide number"
+        ))),
+        None => Ok(ids[0]),
     }
 }
 
@@ -325,6 +1130,8 @@ fn parse_import_export_storage_type(
     storage_option: ImportExportOption,
     source: &str,
     is_export: bool,
+    csv_dialect: Option<CsvDialect>,
+    config: &Config,
 ) -> Result<Box<dyn ContactStore>, AppError> {
     match storage_option {
         ImportExportOption::F => {
@@ -346,40 +1153,284 @@ fn parse_import_export_storage_type(
                 }
             }
 
-            let file_extension = path_obj.extension();
-            if file_extension.is_none() {
+            // `contacts.json.gz` is a json file
+            let Some(ext) = compression::inner_extension(source) else {
                 return Err(AppError::Validation("Can't decode file type".to_string()));
+            };
+            // Exports pass the native dialect whatever the file type
+            if ext != "csv"
+                && csv_dialect
+                    .as_ref()
+                    .is_some_and(|dialect| *dialect != CsvDialect::Native)
+            {
+                return Err(AppError::Validation(
+                    "CSV dialects and mappings only apply to .csv files".to_string(),
+                ));
             }
 
-            let file_extension = file_extension.unwrap();
-            let extension_str = file_extension.to_str();
-            if extension_str.is_none() {
-                return Err(AppError::Validation("Can't decode file type".to_string()));
+            // vCard and LDIF files are only imported and exported, never a book's medium
+            match ext.as_str() {
+                "vcf" => return Ok(Box::new(VcardStorage::at(source))),
+                "ldif" => {
+                    let store = LdifStorage::at(source).with_base_dn(&config.ldif.base_dn);
+                    return Ok(Box::new(store));
+                }
+                _ => {}
             }
-
-            let ext = extension_str.unwrap().to_string();
             let src_medium: StorageMediums = ext.as_str().try_into()?;
 
             match src_medium {
-                StorageMediums::Json => Ok(Box::new(JsonStorage {
-                    medium: "json".to_string(),
-                    path: source.to_string(),
-                })),
-                StorageMediums::Csv => Ok(Box::new(CsvStorage::new(source)?)),
+                StorageMediums::Json => Ok(Box::new(JsonStorage::at(source))),
+                StorageMediums::Bin => Ok(Box::new(BinaryStorage::at(source))),
+                StorageMediums::Jsonl => Ok(Box::new(JsonlStorage::at(source))),
+                StorageMediums::Csv => {
+                    let store = CsvStorage::new(source)?;
+                    Ok(Box::new(match csv_dialect {
+                        Some(dialect) => store.with_dialect(dialect),
+                        None => store,
+                    }))
+                }
 
                 _ => Err(AppError::Validation("Storage not supported".to_string())),
             }
         }
 
+        ImportExportOption::G => {
+            let url = match (source.is_empty(), &config.git.remote) {
+                (false, _) => source.to_string(),
+                (true, Some(remote)) => remote.clone(),
+                (true, None) => {
+                    return Err(AppError::NotFound(
+                        "Git repository to sync with (pass --src or set git.remote)".to_string(),
+                    ));
+                }
+            };
+            let local = books::book_storage(&books::active_book(config)?, config)?;
+            Ok(Box::new(GitRemoteStorage::new(
+                &url,
+                local.as_ref(),
+                config,
+            )?))
+        }
+
         ImportExportOption::R => {
-            let remote_storage = Box::new(RemoteStorage::new()?);
+            let remote_storage = Box::new(RemoteStorage::from_config(
+                config,
+                &books::active_book(config)?,
+            )?);
 
+            // Without an explicit url, loads and saves build their own from the config
             if is_valid_url(source) {
                 remote_storage.update_active_url_from_str(source);
-            } else {
-                remote_storage.format_get_req_from_base_url()?;
             }
             Ok(remote_storage)
         }
     }
 }
+
+fn parse_csv_dialect(option: CsvDialectOption) -> CsvDialect {
+    match option {
+        CsvDialectOption::Native => CsvDialect::Native,
+        CsvDialectOption::Google => CsvDialect::Google,
+        CsvDialectOption::Outlook => CsvDialect::Outlook,
+        CsvDialectOption::Generic => CsvDialect::Generic,
+    }
+}
+
+fn parse_sync_policy(
+    option: SyncPolicyOption,
+    on_delete: DeletionRuleOption,
+    storage: &dyn ContactStore,
+    manager: &ContactManager,
+) -> Result<Box<dyn SyncPolicy>, AppError> {
+    let policy: Box<dyn SyncPolicy> = match option {
+        SyncPolicyOption::LocalAlwaysWins => Box::new(LocalAlwaysWins),
+        SyncPolicyOption::RemoteAlwaysWins => Box::new(RemoteAlwaysWins),
+        SyncPolicyOption::LastWriteWins => Box::new(LastWriteWins),
+        SyncPolicyOption::FieldLevelMerge => Box::new(FieldLevelMerge::for_storage(
+            storage,
+            &manager.sync_state,
+            manager.storage.as_ref(),
+        )?),
+        SyncPolicyOption::Manual => Box::new(Manual),
+    };
+
+    let rule = match on_delete {
+        DeletionRuleOption::FollowPolicy => DeletionRule::FollowPolicy,
+        DeletionRuleOption::EditResurrects => DeletionRule::EditResurrects,
+        DeletionRuleOption::DeleteWins => DeletionRule::DeleteWins,
+        DeletionRuleOption::Ask => DeletionRule::Ask,
+    };
+
+    Ok(Box::new(WithDeletionRule { policy, rule }))
+}
+
+/// Prints a sync report as a table, or as JSON for scripts.
+fn print_sync_report(report: &SyncReport, format: ReportFormat) -> Result<(), AppError> {
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+
+    if report.is_empty() {
+        println!("No changes");
+        return Ok(());
+    }
+
+    println!("Added ({})", report.added.len());
+    for (mut i, c) in report.added.iter().enumerate() {
+        i += 1;
+        println!(
+            "{i:>3}. {:<20} {:15} {:^30} {:<15}",
+            c.name, c.phone, c.email, c.tag
+        );
+    }
+
+    println!("Updated ({})", report.updated.len());
+    for (mut i, update) in report.updated.iter().enumerate() {
+        i += 1;
+        println!("{i:>3}. {}", update.name);
+        for change in &update.changes {
+            println!(
+                "       {:<10} {:<30} -> {:<30}",
+                change.field, change.before, change.after
+            );
+        }
+    }
+
+    println!("Deleted ({})", report.deleted.len());
+    for (mut i, c) in report.deleted.iter().enumerate() {
+        i += 1;
+        println!("{i:>3}. {:<20} {:15}", c.name, c.phone);
+    }
+
+    println!("Skipped as duplicates ({})", report.skipped.len());
+    for (mut i, c) in report.skipped.iter().enumerate() {
+        i += 1;
+        println!("{i:>3}. {:<20} {:15}", c.name, c.phone);
+    }
+
+    println!("Conflicts ({})", report.conflicts.len());
+    for (mut i, c) in report.conflicts.iter().enumerate() {
+        i += 1;
+        println!(
+            "{i:>3}. {:<20} {:15} fields: {}",
+            c.local.name,
+            c.local.phone,
+            c.fields.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn print_sync_outcome(outcome: &SyncOutcome, format: ReportFormat) -> Result<(), AppError> {
+    if format == ReportFormat::Json {
+        println!("{}", serde_json::to_string_pretty(outcome)?);
+        return Ok(());
+    }
+
+    let counts = |report: &SyncReport| {
+        format!(
+            "{} added, {} updated, {} deleted",
+            report.added.len(),
+            report.updated.len(),
+            report.deleted.len()
+        )
+    };
+
+    println!("Pulled: {}", counts(&outcome.pulled));
+    println!("Pushed: {}", counts(&outcome.pushed));
+
+    if !outcome.pulled.skipped.is_empty() {
+        println!("{} duplicate(s) skipped", outcome.pulled.skipped.len());
+    }
+    if !outcome.pulled.conflicts.is_empty() {
+        println!(
+            "{} conflict(s) need your attention, see `rolodex conflicts`",
+            outcome.pulled.conflicts.len()
+        );
+    }
+    Ok(())
+}
+
+/// Prints both versions of a conflicted contact side by side, marking the fields that differ.
+fn print_conflict(conflict: &SyncConflict) {
+    println!();
+    println!(
+        "Conflict from {} (detected {})",
+        conflict.source,
+        conflict.detected_at.date_naive()
+    );
+    println!("  {:<10} {:<30} {:<30}", "", "Local", "Remote");
+
+    for field in CONFLICT_FIELDS {
+        let marker = if conflict.fields.iter().any(|f| f == field) {
+            '*'
+        } else {
+            ' '
+        };
+        println!(
+            "{marker} {:<10} {:<30} {:<30}",
+            field,
+            field_value(&conflict.local, field),
+            field_value(&conflict.remote, field)
+        );
+    }
+}
+
+fn prompt(message: &str) -> Result<String, AppError> {
+    print!("{message}");
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Asks the user how to settle a conflict. Returns `None` if they skip it.
+fn prompt_resolution(conflict: &SyncConflict) -> Result<Option<ConflictResolution>, AppError> {
+    loop {
+        let choice = prompt("Keep [l]ocal, take [r]emote, [m]erge or [s]kip? ")?;
+
+        match choice.to_lowercase().as_str() {
+            "l" | "local" => return Ok(Some(ConflictResolution::KeepLocal)),
+            "r" | "remote" => return Ok(Some(ConflictResolution::TakeRemote)),
+            "m" | "merge" => return Ok(Some(prompt_merge(conflict)?)),
+            "s" | "skip" | "" => return Ok(None),
+            _ => println!("Please answer l, r, m or s"),
+        }
+    }
+}
+
+/// Walks through the differing fields, taking local, remote or a typed-in value for each.
+fn prompt_merge(conflict: &SyncConflict) -> Result<ConflictResolution, AppError> {
+    let mut merged = conflict.local.clone();
+
+    for field in ["name", "phone", "email", "tag"] {
+        if !conflict.fields.iter().any(|f| f == field) {
+            continue;
+        }
+
+        let answer = prompt(&format!(
+            "{field} [l = {:?}, r = {:?}, or type a new value]: ",
+            field_value(&conflict.local, field),
+            field_value(&conflict.remote, field)
+        ))?;
+
+        let value = match answer.as_str() {
+            "l" | "" => field_value(&conflict.local, field),
+            "r" => field_value(&conflict.remote, field),
+            _ => answer,
+        };
+
+        match field {
+            "name" => merged.name = value,
+            "phone" => merged.phone = value,
+            "email" => merged.email = value,
+            _ => merged.tag = value,
+        }
+    }
+
+    Ok(ConflictResolution::Merged(Box::new(merged)))
+}
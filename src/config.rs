@@ -0,0 +1,454 @@
+use crate::prelude::AppError;
+use crate::storage::{compression::Compression, directory::RecordFormat};
+use dotenv::dotenv;
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::PathBuf, sync::OnceLock};
+
+const PROJECT_CONFIG_FILE: &str = "./rolodex.toml";
+
+/// Settings for the contact book, merged from (lowest to highest precedence):
+/// built-in defaults, the user config file, the project config file
+/// (`./rolodex.toml`), env vars (and .env, except the API key), then CLI flags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageConfig,
+    /// Soft-deleted contacts older than this are purged on save.
+    pub purge_days: i64,
+    pub remote: RemoteConfig,
+    pub credentials: CredentialsConfig,
+    pub encryption: EncryptionConfig,
+    pub git: GitConfig,
+    pub ldif: LdifConfig,
+    /// Book picked with `--book` or `ROLODEX_BOOK`; the default book setting
+    /// applies when unset. Never read from or written to config files.
+    #[serde(skip)]
+    pub book: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    /// Medium of the default book: json, txt, bin, jsonl, events, dir, remote or mem.
+    pub choice: String,
+    pub json_path: String,
+    pub txt_path: String,
+    pub bin_path: String,
+    pub jsonl_path: String,
+    pub events_path: String,
+    /// Directory of the `dir` book, one file per contact.
+    pub dir_path: String,
+    /// How the `dir` book writes contact files: json or vcf.
+    pub dir_format: String,
+    /// json or txt file the `mem` storage starts from.
+    pub mem_fixture: Option<String>,
+    /// How json, txt and jsonl books are written: none, gzip or zstd. A `.gz` or
+    /// `.zst` path extension takes precedence.
+    pub compression: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    pub url: Option<String>,
+    /// Overrides the resource id kept in the book's remote state file.
+    pub resource_id: Option<String>,
+    /// Only taken from `REMOTE_API_KEY`, never from config files; see `storage::credentials`.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CredentialsConfig {
+    /// `file` or `command`.
+    pub store: String,
+    /// Command printing a secret, with `{key}` standing for its name (for the `command` store).
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Where the passphrase of encrypted books comes from: `prompt`, `env`
+    /// (`ROLODEX_PASSPHRASE`), `file` or `credentials`.
+    pub key_source: String,
+    /// Key file read for the `file` key source.
+    pub key_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Commit the book's files to a git repository after every change.
+    pub enabled: bool,
+    /// Bare repository `sync --with g` uses when no `--src` is given.
+    pub remote: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LdifConfig {
+    /// DN the entries of LDIF exports are written under.
+    pub base_dn: String,
+}
+
+impl Default for LdifConfig {
+    fn default() -> Self {
+        Self {
+            base_dn: "ou=people,dc=example,dc=com".to_string(),
+        }
+    }
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            key_source: "prompt".to_string(),
+            key_file: None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            storage: StorageConfig::default(),
+            purge_days: 1,
+            remote: RemoteConfig::default(),
+            credentials: CredentialsConfig::default(),
+            encryption: EncryptionConfig::default(),
+            git: GitConfig::default(),
+            ldif: LdifConfig::default(),
+            book: None,
+        }
+    }
+}
+
+impl Default for CredentialsConfig {
+    fn default() -> Self {
+        Self {
+            store: "file".to_string(),
+            command: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            choice: "json".to_string(),
+            json_path: "./.instance/contacts.json".to_string(),
+            txt_path: "./.instance/contacts.txt".to_string(),
+            bin_path: "./.instance/contacts.bin".to_string(),
+            jsonl_path: "./.instance/contacts.jsonl".to_string(),
+            events_path: "./.instance/contacts.events".to_string(),
+            dir_path: "./.instance/contacts".to_string(),
+            dir_format: "json".to_string(),
+            mem_fixture: None,
+            compression: "none".to_string(),
+        }
+    }
+}
+
+/// Every setting, as used by `config get/set`, and the env var overriding it.
+pub const CONFIG_KEYS: [(&str, &str); 20] = [
+    ("storage.choice", "STORAGE_CHOICE"),
+    ("storage.json_path", "JSON_STORAGE_PATH"),
+    ("storage.txt_path", "TXT_STORAGE_PATH"),
+    ("storage.bin_path", "BIN_STORAGE_PATH"),
+    ("storage.jsonl_path", "JSONL_STORAGE_PATH"),
+    ("storage.events_path", "EVENTS_STORAGE_PATH"),
+    ("storage.dir_path", "DIR_STORAGE_PATH"),
+    ("storage.dir_format", "DIR_STORAGE_FORMAT"),
+    ("storage.mem_fixture", "MEM_FIXTURE"),
+    ("storage.compression", "STORAGE_COMPRESSION"),
+    ("purge_days", "PURGE_DAYS"),
+    ("remote.url", "REMOTE_STORAGE_URL"),
+    ("remote.resource_id", "RESOURCE_ID"),
+    ("credentials.store", "CREDENTIALS_STORE"),
+    ("credentials.command", "CREDENTIALS_COMMAND"),
+    ("encryption.key_source", "ENCRYPTION_KEY_SOURCE"),
+    ("encryption.key_file", "ENCRYPTION_KEY_FILE"),
+    ("git.enabled", "GIT_ENABLED"),
+    ("git.remote", "GIT_REMOTE"),
+    ("ldif.base_dn", "LDIF_BASE_DN"),
+];
+
+impl Config {
+    /// Loads the config files and applies env vars on top. CLI flags are applied by the caller.
+    pub fn load() -> Result<Self, AppError> {
+        let mut merged = toml::Table::try_from(Self::default())?;
+
+        for path in [user_config_path(), Some(project_config_path())]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(table) = read_table(&path)? {
+                merge_tables(&mut merged, table);
+            }
+        }
+
+        let mut config: Self = merged.try_into()?;
+
+        // The API key must come from the real environment or the credential
+        // store, so it is read before `.env` is loaded into the environment
+        static API_KEY_ENV: OnceLock<Option<String>> = OnceLock::new();
+        let api_key = API_KEY_ENV.get_or_init(|| env::var("REMOTE_API_KEY").ok());
+
+        dotenv().ok();
+        for (key, var) in CONFIG_KEYS {
+            // A bad override shouldn't stop every command, so the file's value stays
+            if let Ok(value) = env::var(var)
+                && let Err(err) = config.set(key, &value)
+            {
+                eprintln!("Warning: ignoring {var}: {err}");
+            }
+        }
+        config.remote.api_key = api_key.clone();
+        config.book = env::var("ROLODEX_BOOK")
+            .ok()
+            .filter(|book| !book.is_empty());
+
+        Ok(config)
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, AppError> {
+        let value = match key {
+            "storage.choice" => self.storage.choice.clone(),
+            "storage.json_path" => self.storage.json_path.clone(),
+            "storage.txt_path" => self.storage.txt_path.clone(),
+            "storage.bin_path" => self.storage.bin_path.clone(),
+            "storage.jsonl_path" => self.storage.jsonl_path.clone(),
+            "storage.events_path" => self.storage.events_path.clone(),
+            "storage.dir_path" => self.storage.dir_path.clone(),
+            "storage.dir_format" => self.storage.dir_format.clone(),
+            "storage.mem_fixture" => self.storage.mem_fixture.clone().unwrap_or_default(),
+            "storage.compression" => self.storage.compression.clone(),
+            "purge_days" => self.purge_days.to_string(),
+            "remote.url" => self.remote.url.clone().unwrap_or_default(),
+            "remote.resource_id" => self.remote.resource_id.clone().unwrap_or_default(),
+            "credentials.store" => self.credentials.store.clone(),
+            "credentials.command" => self.credentials.command.clone().unwrap_or_default(),
+            "encryption.key_source" => self.encryption.key_source.clone(),
+            "encryption.key_file" => self.encryption.key_file.clone().unwrap_or_default(),
+            "git.enabled" => self.git.enabled.to_string(),
+            "git.remote" => self.git.remote.clone().unwrap_or_default(),
+            "ldif.base_dn" => self.ldif.base_dn.clone(),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    /// Sets a setting from its string form, checking the value fits.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), AppError> {
+        let optional = || (!value.is_empty()).then(|| value.to_string());
+
+        match key {
+            "storage.choice" => {
+                if ![
+                    "json", "txt", "bin", "jsonl", "events", "dir", "remote", "mem",
+                ]
+                .contains(&value)
+                {
+                    return Err(AppError::Validation(format!(
+                        "storage.choice must be json, txt, bin, jsonl, events, dir, remote or mem, not '{value}'"
+                    )));
+                }
+                self.storage.choice = value.to_string();
+            }
+            "storage.json_path" => self.storage.json_path = value.to_string(),
+            "storage.txt_path" => self.storage.txt_path = value.to_string(),
+            "storage.bin_path" => self.storage.bin_path = value.to_string(),
+            "storage.jsonl_path" => self.storage.jsonl_path = value.to_string(),
+            "storage.events_path" => self.storage.events_path = value.to_string(),
+            "storage.dir_path" => self.storage.dir_path = value.to_string(),
+            "storage.dir_format" => {
+                RecordFormat::try_from(value)?;
+                self.storage.dir_format = value.to_string();
+            }
+            "storage.mem_fixture" => self.storage.mem_fixture = optional(),
+            "storage.compression" => {
+                Compression::try_from(value)?;
+                self.storage.compression = value.to_string();
+            }
+            "purge_days" => {
+                self.purge_days = value.parse().map_err(|_| {
+                    AppError::Validation(format!("purge_days must be a number, not '{value}'"))
+                })?
+            }
+            "remote.url" => self.remote.url = optional(),
+            "remote.resource_id" => self.remote.resource_id = optional(),
+            "credentials.store" => {
+                if !["file", "command"].contains(&value) {
+                    return Err(AppError::Validation(format!(
+                        "credentials.store must be file or command, not '{value}'"
+                    )));
+                }
+                self.credentials.store = value.to_string();
+            }
+            "credentials.command" => self.credentials.command = optional(),
+            "encryption.key_source" => {
+                if !["prompt", "env", "file", "credentials"].contains(&value) {
+                    return Err(AppError::Validation(format!(
+                        "encryption.key_source must be prompt, env, file or credentials, not '{value}'"
+                    )));
+                }
+                self.encryption.key_source = value.to_string();
+            }
+            "encryption.key_file" => self.encryption.key_file = optional(),
+            "git.enabled" => {
+                self.git.enabled = value.parse().map_err(|_| {
+                    AppError::Validation(format!(
+                        "git.enabled must be true or false, not '{value}'"
+                    ))
+                })?
+            }
+            "git.remote" => self.git.remote = optional(),
+            "ldif.base_dn" => {
+                if !value.contains('=') {
+                    return Err(AppError::Validation(format!(
+                        "ldif.base_dn must be a DN such as ou=people,dc=example,dc=com, not '{value}'"
+                    )));
+                }
+                self.ldif.base_dn = value.to_string();
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rolodex/config.toml` (or the platform's config dir).
+pub fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rolodex").join("config.toml"))
+}
+
+pub fn project_config_path() -> PathBuf {
+    PathBuf::from(PROJECT_CONFIG_FILE)
+}
+
+/// `$XDG_DATA_HOME/rolodex` (or the platform's data dir), for state and secrets
+/// that must not end up in the working directory.
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|dir| dir.join("rolodex"))
+        .unwrap_or(PathBuf::from("./.instance"))
+}
+
+/// Writes one setting to a config file, keeping the rest of the file as it is.
+pub fn set_in_file(path: &PathBuf, key: &str, value: &str) -> Result<(), AppError> {
+    // Validate and get the value with its proper TOML type
+    let mut config = Config::default();
+    config.set(key, value)?;
+    let typed = toml::Table::try_from(&config)?;
+
+    let mut table = read_table(path)?.unwrap_or_default();
+    let mut parent = &mut table;
+    let mut typed_parent = &typed;
+    let mut segments = key.split('.').peekable();
+
+    while let Some(segment) = segments.next() {
+        if segments.peek().is_none() {
+            match typed_parent.get(segment) {
+                Some(typed_value) => parent.insert(segment.to_string(), typed_value.clone()),
+                // Emptied optional setting
+                None => parent.remove(segment),
+            };
+            break;
+        }
+
+        typed_parent = typed_parent
+            .get(segment)
+            .and_then(|v| v.as_table())
+            .ok_or_else(|| unknown_key(key))?;
+        parent = parent
+            .entry(segment)
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| AppError::Validation(format!("'{segment}' is not a table")))?;
+    }
+
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, toml::to_string_pretty(&table)?)?;
+    Ok(())
+}
+
+fn read_table(path: &PathBuf) -> Result<Option<toml::Table>, AppError> {
+    if !fs::exists(path)? {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.parse()?))
+}
+
+/// Overlays `top` onto `base`, merging nested tables key by key.
+fn merge_tables(base: &mut toml::Table, top: toml::Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(top_table)) => {
+                merge_tables(base_table, top_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn unknown_key(key: &str) -> AppError {
+    let keys: Vec<&str> = CONFIG_KEYS.iter().map(|(key, _)| *key).collect();
+    AppError::NotFound(format!(
+        "Config key '{key}' (known keys: {})",
+        keys.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_file_overrides_user_file_key_by_key() -> Result<(), AppError> {
+        let mut merged = toml::Table::try_from(Config::default())?;
+        merge_tables(
+            &mut merged,
+            "purge_days = 7\n[storage]\nchoice = \"txt\"\n".parse()?,
+        );
+        merge_tables(
+            &mut merged,
+            "[storage]\ntxt_path = \"./book.txt\"\n".parse()?,
+        );
+
+        let config: Config = merged.try_into()?;
+        assert_eq!(config.purge_days, 7);
+        assert_eq!(config.storage.choice, "txt");
+        assert_eq!(config.storage.txt_path, "./book.txt");
+        assert_eq!(
+            config.storage.json_path,
+            Config::default().storage.json_path
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn set_in_file_keeps_other_settings() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "# mine\n[remote]\nurl = \"https://example.com\"\n")?;
+
+        set_in_file(&path, "purge_days", "30")?;
+        set_in_file(&path, "credentials.store", "command")?;
+        assert!(set_in_file(&path, "purge_days", "soon").is_err());
+        assert!(set_in_file(&path, "colour", "blue").is_err());
+
+        let config: Config = read_table(&path)?.unwrap().try_into()?;
+        assert_eq!(config.purge_days, 30);
+        assert_eq!(config.remote.url.as_deref(), Some("https://example.com"));
+        assert_eq!(config.credentials.store, "command");
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use super::*;
+
+use super::clock::Hlc;
+use super::manager::{ContactManager, Index, IndexOptions, PurgePolicy};
+use crate::prelude::HashMap;
+use std::path::PathBuf;
+use storage::StorageMediums;
+use storage::sync_state::{self, SyncState};
+
+/// Builds a `ContactManager` from explicit parts, without reading env vars or config files.
+///
+/// ```no_run
+/// use rusty_rolodex::prelude::*;
+/// use rusty_rolodex::domain::manager::PurgePolicy;
+///
+/// let manager = ContactManager::builder(Box::new(file::JsonStorage::at("./contacts.json")))
+///     .purge(PurgePolicy::Never)
+///     .build()?;
+/// # Ok::<(), AppError>(())
+/// ```
+pub struct ContactManagerBuilder {
+    storage: Box<dyn ContactStore>,
+    purge: PurgePolicy,
+    index: IndexOptions,
+    migrate_from: Vec<Box<dyn ContactStore>>,
+    replaces: Vec<String>,
+    sync_state_dir: Option<PathBuf>,
+    device_id: Option<String>,
+}
+
+impl ContactManagerBuilder {
+    pub fn new(storage: Box<dyn ContactStore>) -> Self {
+        Self {
+            storage,
+            purge: PurgePolicy::default(),
+            index: IndexOptions::default(),
+            migrate_from: Vec::new(),
+            replaces: Vec::new(),
+            sync_state_dir: None,
+            device_id: None,
+        }
+    }
+
+    /// When soft-deleted contacts are dropped for good on save.
+    pub fn purge(mut self, purge: PurgePolicy) -> Self {
+        self.purge = purge;
+        self
+    }
+
+    /// Which search indexes to keep.
+    pub fn index(mut self, index: IndexOptions) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Adds a store whose contacts are merged in on build, e.g. an older file format.
+    pub fn migrate_from(mut self, storage: Box<dyn ContactStore>) -> Self {
+        self.migrate_from.push(storage);
+        self
+    }
+
+    /// Like `migrate_from` for the file the book was kept in before it switched
+    /// format. The file is removed once the book is saved.
+    pub fn replaces(mut self, storage: Box<dyn ContactStore>) -> Self {
+        self.replaces.push(storage.get_location());
+        self.migrate_from(storage)
+    }
+
+    /// Keeps sync snapshots and the conflict queue in `dir`. Without one
+    /// nothing is kept between runs.
+    pub fn sync_state_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.sync_state_dir = Some(dir.into());
+        self
+    }
+
+    /// The id stamped on the clocks of changes made through the manager.
+    /// Defaults to the one kept in the sync state directory, or a random one
+    /// without a directory.
+    pub fn device_id(mut self, id: &str) -> Self {
+        self.device_id = Some(id.to_string());
+        self
+    }
+
+    /// Sets the purge policy from `config`, keeps the sync state of the active
+    /// book under `SYNC_STATE_DIR` with this installation's device id, and
+    /// migrates from the other file format of the same book, like the CLI does.
+    /// That file is removed once the book is saved. In-memory stores migrate nothing.
+    pub fn with_config(self, config: &Config) -> Result<Self, AppError> {
+        let location = self.storage.get_location();
+        // A sibling that can't be opened (e.g. encrypted under a bad key source) is skipped
+        let (legacy, replaced) = match self.storage.get_medium() {
+            "txt" => (
+                storage::open_file(
+                    StorageMediums::Json,
+                    &file::sibling_path(&location, "json"),
+                    config,
+                )
+                .ok(),
+                true,
+            ),
+            "json" => (
+                storage::open_file(
+                    StorageMediums::Txt,
+                    &file::sibling_path(&location, "txt"),
+                    config,
+                )
+                .ok(),
+                true,
+            ),
+            "mem" | "bin" | "jsonl" | "events" | "dir" => (None, false),
+            _ => (
+                storage::open_file(StorageMediums::Txt, &config.storage.txt_path, config).ok(),
+                false,
+            ),
+        };
+
+        let builder = self
+            .purge(PurgePolicy::OlderThanDays(config.purge_days))
+            .sync_state_dir(sync_state::sync_state_dir(config))
+            .device_id(&sync_state::device_id()?);
+        Ok(match legacy {
+            Some(legacy) if replaced => builder.replaces(legacy),
+            Some(legacy) => builder.migrate_from(legacy),
+            None => builder,
+        })
+    }
+
+    /// Loads the contacts and builds the indexes.
+    pub fn build(self) -> Result<ContactManager, AppError> {
+        let device = match (self.device_id, &self.sync_state_dir) {
+            (Some(id), _) => id,
+            (None, Some(dir)) => sync_state::device_id_in(dir)?,
+            (None, None) => sync_state::new_device_id(),
+        };
+
+        let mut manager = ContactManager {
+            mem: HashMap::new(),
+            storage: self.storage,
+            index: Index {
+                options: self.index,
+                ..Index::default()
+            },
+            purge: self.purge,
+            sync_state: SyncState::new(self.sync_state_dir, &device),
+            clock: Hlc::default(),
+            replaced_files: self.replaces,
+        };
+        manager.load()?;
+        manager.index = Index::new(&manager)?;
+
+        for source in &self.migrate_from {
+            manager.migrate_from_storage(source.as_ref())?;
+        }
+        Ok(manager)
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fmt, str::FromStr};
+
+use super::*;
+use crate::prelude::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Hybrid logical clock stamped on a contact at every change.
+///
+/// Wall-clock milliseconds are only ever moved forward: a change made on a
+/// device whose clock is behind still sorts after the version it was made on,
+/// by bumping `counter`. `device` breaks ties between devices.
+///
+/// Stored as a single string (`<millis>-<counter>-<device>`) so every file format can hold it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Hlc {
+    pub millis: i64,
+    pub counter: u32,
+    pub device: String,
+}
+
+impl Hlc {
+    /// Clock for a contact written before clocks existed, taken from its `updated_at`.
+    pub fn seeded(updated_at: DateTime<Utc>) -> Self {
+        Self {
+            millis: updated_at.timestamp_millis(),
+            counter: 0,
+            device: String::new(),
+        }
+    }
+
+    pub fn is_unset(&self) -> bool {
+        self.millis == 0 && self.counter == 0
+    }
+
+    /// Clock for a local change to a contact currently stamped with `self`.
+    pub fn tick(&self, device: &str) -> Self {
+        self.tick_at(Utc::now(), device)
+    }
+
+    /// Like `tick`, with the wall clock reading passed in.
+    pub fn tick_at(&self, wall: DateTime<Utc>, device: &str) -> Self {
+        let wall_millis = wall.timestamp_millis();
+
+        if wall_millis > self.millis {
+            Self {
+                millis: wall_millis,
+                counter: 0,
+                device: device.to_string(),
+            }
+        } else {
+            Self {
+                millis: self.millis,
+                counter: self.counter + 1,
+                device: device.to_string(),
+            }
+        }
+    }
+}
+
+impl Hlc {
+    /// Takes in a clock seen on another device, so the next `tick` sorts after
+    /// it even when this device's wall clock is behind.
+    pub fn receive(&mut self, seen: &Hlc) {
+        if *seen > *self {
+            *self = seen.clone();
+        }
+    }
+}
+
+impl fmt::Display for Hlc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unset() {
+            return Ok(());
+        }
+        write!(f, "{:013}-{:04}-{}", self.millis, self.counter, self.device)
+    }
+}
+
+impl FromStr for Hlc {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let invalid = || AppError::Validation(format!("Invalid clock: {s}"));
+        let mut parts = s.splitn(3, '-');
+
+        Ok(Self {
+            millis: parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)?,
+            counter: parts
+                .next()
+                .and_then(|p| p.parse().ok())
+                .ok_or_else(invalid)?,
+            device: parts.next().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+impl Serialize for Hlc {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hlc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Option::<String>::deserialize(deserializer)?;
+
+        match value {
+            Some(s) => s.parse().map_err(serde::de::Error::custom),
+            None => Ok(Self::default()), // fallback for old contacts
+        }
+    }
+}
+
+/// Gives contacts without a clock one seeded from their `updated_at`.
+pub fn seed_clocks(contacts: &mut HashMap<Uuid, Contact>) {
+    for contact in contacts.values_mut() {
+        if contact.clock.is_unset() {
+            contact.clock = Hlc::seeded(contact.updated_at);
+        }
+    }
+}
+
+/// The latest clock stamped on any of `contacts`.
+pub fn latest(contacts: &HashMap<Uuid, Contact>) -> Hlc {
+    contacts
+        .values()
+        .map(|contact| &contact.clock)
+        .max()
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn tick_moves_forward_on_a_skewed_clock() {
+        let now = Utc::now();
+
+        // Written by a device whose clock is an hour ahead
+        let remote = Hlc::seeded(now + Duration::hours(1));
+        let local_edit = remote.tick_at(now, "laptop");
+
+        assert!(local_edit > remote);
+        assert_eq!(local_edit.millis, remote.millis);
+        assert_eq!(local_edit.counter, 1);
+    }
+
+    #[test]
+    fn receive_moves_past_a_clock_from_a_device_that_is_ahead() {
+        let now = Utc::now();
+        let mut local = Hlc::seeded(now).tick_at(now, "laptop");
+
+        // Another device, an hour ahead, made a change this one has now seen
+        let remote = Hlc::seeded(now).tick_at(now + Duration::hours(1), "phone");
+        local.receive(&remote);
+        assert_eq!(local, remote);
+
+        let next = local.tick_at(now + Duration::seconds(1), "laptop");
+        assert!(next > remote);
+
+        // An older clock doesn't move it back
+        local.receive(&Hlc::seeded(now));
+        assert_eq!(local, remote);
+    }
+
+    #[test]
+    fn clock_round_trips_through_string() -> Result<(), AppError> {
+        let clock = Hlc::seeded(Utc::now()).tick_at(Utc::now(), "ab12-cd34");
+        assert_eq!(clock.to_string().parse::<Hlc>()?, clock);
+
+        assert_eq!("".parse::<Hlc>()?, Hlc::default());
+        assert!("not-a-clock".parse::<Hlc>().is_err());
+        Ok(())
+    }
+}
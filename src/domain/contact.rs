@@ -1,6 +1,7 @@
 use std::hash::{Hash, Hasher};
 
 use super::*;
+use crate::domain::clock::Hlc;
 pub use chrono::{DateTime, Utc};
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -30,6 +31,14 @@ pub struct Contact {
     )]
     pub deleted: bool,
 
+    /// When and on which device the contact was deleted. Kept with the tombstone so
+    /// deletions can be told apart from later edits during sync.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub deleted_by: Option<String>,
+
     #[serde(
         default = "default_timestamp",
         deserialize_with = "deserialize_timestamp"
@@ -41,6 +50,10 @@ pub struct Contact {
         deserialize_with = "deserialize_timestamp"
     )]
     pub updated_at: DateTime<Utc>,
+
+    /// Orders changes during sync; `updated_at` is informational only.
+    #[serde(default)] // Seeded from updated_at by the schema 4 migration.
+    pub clock: Hlc,
 }
 
 impl Contact {
@@ -52,17 +65,42 @@ impl Contact {
             email,
             tag,
             deleted: false,
+            deleted_at: None,
+            deleted_by: None,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            clock: Hlc::default(),
         }
     }
+
+    /// Records a local change made on `device`.
+    pub fn touch(&mut self, device: &str) {
+        self.updated_at = Utc::now();
+        self.clock = self.clock.tick_at(self.updated_at, device);
+    }
+
+    /// Like `touch`, stamped after `seen`, the latest clock the book has seen,
+    /// which then moves up to the new stamp.
+    pub fn touch_after(&mut self, seen: &mut Hlc, device: &str) {
+        self.clock.receive(seen);
+        self.touch(device);
+        seen.receive(&self.clock);
+    }
+
+    /// Soft-deletes the contact, leaving a tombstone for sync.
+    pub fn mark_deleted(&mut self, device: &str) {
+        self.deleted = true;
+        self.touch(device);
+        self.deleted_at = Some(self.updated_at);
+        self.deleted_by = Some(device.to_string());
+    }
     pub fn validate_name(&self) -> Result<bool, AppError> {
-        // Must begin with alphabet
-        // Name may contain spaces, hyphens, and apostrophe between alphabets
-        // Name may end with number or alphabet
+        // Must begin with a letter, in any alphabet ("Chloé", "Ádaeze")
+        // Name may contain spaces, hyphens, and apostrophe between letters
+        // Name may end with number or letter
         // Not more than 50 characters
-        let re = Regex::new(r"^[A-Za-z][A-Za-z\s'-\.]*\w*$")?;
-        Ok((self.name.len() <= 50) && re.is_match(&self.name))
+        let re = Regex::new(r"^\p{L}[\p{L}\p{M}\s'-\.]*\w*$")?;
+        Ok((self.name.chars().count() <= 50) && re.is_match(&self.name))
     }
 
     pub fn validate_number(&self) -> Result<bool, AppError> {
@@ -81,6 +119,22 @@ impl Contact {
         Ok(self.email.is_empty() || (re.is_match(&self.email) && self.email.len() <= 254))
     }
 
+    /// Checks name, phone and email, failing with the rule the first bad one breaks.
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !self.validate_name()? {
+            return Err(AppError::Validation(NAME_REQ_MESSAGE.to_string()));
+        }
+
+        if !self.validate_number()? {
+            return Err(AppError::Validation(PHONE_REQ_MESSAGE.to_string()));
+        }
+
+        if !self.validate_email()? {
+            return Err(AppError::Validation(EMAIL_REQ_MESSAGE.to_string()));
+        }
+        Ok(())
+    }
+
     pub fn already_exist(&self, contactlist: &[&Contact]) -> bool {
         // Check if contact alread exist in contactlist
         contactlist
@@ -225,4 +279,24 @@ mod tests {
         assert!(!contact.validate_email()?);
         Ok(())
     }
+
+    #[test]
+    fn name_validation_accepts_any_alphabet() -> Result<(), AppError> {
+        let named = |name: &str| {
+            Contact::new(
+                name.to_string(),
+                "08132165498".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+        };
+
+        for name in ["Chloé Martin", "Ádaeze Obi", "Zoë O'Neil-Smith", "Jürgen"] {
+            assert!(named(name).validate_name()?, "{name}");
+        }
+        for name in ["1Ada", "-Ada", "", &"é".repeat(51)] {
+            assert!(!named(name).validate_name()?, "{name}");
+        }
+        Ok(())
+    }
 }
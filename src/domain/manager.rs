@@ -1,51 +1,96 @@
-use crate::helper;
-
 use super::*;
 
+use super::builder::ContactManagerBuilder;
+use super::clock::Hlc;
+use super::sync::{
+    self, ConflictResolution, SyncConflict, SyncDecision, SyncOutcome, SyncPolicy, SyncReport,
+};
 use chrono::{Duration, Utc};
-use file::{JsonStorage, TxtStorage};
 use rust_fuzzy_search::fuzzy_compare;
 use std::{
     collections::{HashMap, HashSet},
+    fs,
     sync::{Arc, Mutex},
     thread,
 };
+use storage::sync_state::{SyncSnapshot, SyncState};
 
 const MAX_WORKER_THREADS: usize = 5;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Index {
     pub name: HashMap<String, HashSet<Uuid>>,
     pub domain: HashMap<String, HashSet<Uuid>>,
+    pub options: IndexOptions,
 }
 
-pub enum IndexUpdateType {
-    Add,
-    Remove,
+/// Which search indexes a `ContactManager` keeps. Searches fall back to scanning
+/// every contact when their index is off, trading speed for memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexOptions {
+    pub name: bool,
+    pub email_domain: bool,
 }
 
-pub struct LastWriteWinsPolicy;
-
-pub enum SyncDecision {
-    LocalWins,
-    RemoteWins,
+/// When soft-deleted contacts are dropped for good on save.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PurgePolicy {
+    /// Keep tombstones forever, e.g. for stores synced rarely.
+    Never,
+    OlderThanDays(i64),
 }
 
-pub enum SyncPolicy {
-    LastWriteWinsPolicy(LastWriteWinsPolicy),
+pub enum IndexUpdateType {
+    Add,
+    Remove,
 }
 
 pub struct ContactManager {
     pub mem: HashMap<Uuid, Contact>,
     pub storage: Box<dyn ContactStore>,
     pub index: Index,
+    pub purge: PurgePolicy,
+    /// Sync snapshots, conflict queue and device id of this book
+    pub sync_state: SyncState,
+    /// The latest clock in the book or seen from a remote. Local changes are
+    /// stamped after it.
+    pub clock: Hlc,
+    /// Files this book was kept in before it switched format. Their contacts
+    /// were merged in on load; they are removed once the book is saved.
+    pub replaced_files: Vec<String>,
+}
+
+impl Default for IndexOptions {
+    fn default() -> Self {
+        Self {
+            name: true,
+            email_domain: true,
+        }
+    }
+}
+
+impl Default for PurgePolicy {
+    fn default() -> Self {
+        Self::OlderThanDays(1)
+    }
 }
 
 impl Index {
+    /// Builds the indexes turned on in the manager's current index options.
     pub fn new(storage: &ContactManager) -> Result<Self, AppError> {
+        let options = storage.index.options;
         let mut index = Self {
-            name: storage.create_name_search_index()?,
-            domain: storage.create_email_domain_search_index()?,
+            name: if options.name {
+                storage.create_name_search_index()?
+            } else {
+                HashMap::new()
+            },
+            domain: if options.email_domain {
+                storage.create_email_domain_search_index()?
+            } else {
+                HashMap::new()
+            },
+            options,
         };
 
         index.name.reserve(storage.mem.len() * 2); // Assume each contact has two unique name parts on average
@@ -56,7 +101,7 @@ impl Index {
     pub fn updated_name_index(&mut self, contact: &Contact, update_type: &IndexUpdateType) {
         let name = &contact.name;
 
-        if name.is_empty() {
+        if !self.options.name || name.is_empty() {
             return;
         }
 
@@ -87,7 +132,7 @@ impl Index {
     }
 
     pub fn update_domain_index(&mut self, contact: &Contact, update_type: &IndexUpdateType) {
-        if contact.email.is_empty() {
+        if !self.options.email_domain || contact.email.is_empty() {
             return;
         }
 
@@ -117,77 +162,20 @@ impl Index {
     }
 }
 
-impl LastWriteWinsPolicy {
-    pub fn verify_match(&self, local: &Contact, remote: &Contact) -> bool {
-        local.created_at == remote.created_at
+impl ContactManager {
+    pub fn new() -> Result<Self, AppError> {
+        Self::from_config(&Config::load()?)
     }
 
-    pub fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
-        // If local contact has been deleted, ensure remote counterpart is also marked deleted
-        if local.deleted {
-            remote.updated_at = if !remote.deleted {
-                local.updated_at
-            } else {
-                remote.updated_at
-            };
-            remote.deleted = local.deleted;
-            return SyncDecision::LocalWins;
-        }
-
-        // If local contact has the latest update, no need to update
-        if local.updated_at >= remote.updated_at {
-            return SyncDecision::LocalWins;
-        }
-
-        SyncDecision::RemoteWins
+    /// Opens the active book as configured by `config`.
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        let storage = storage::parse_storage_type_env_config(None, config)?;
+        Self::builder(storage).with_config(config)?.build()
     }
 
-    pub fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
-        // Update name and phone if either has changed
-        if local.name != remote.name || !contact::phone_number_matches(&local.phone, &remote.phone)
-        {
-            local.name = remote.name.clone();
-            local.phone = remote.phone.clone();
-            local.updated_at = remote.updated_at;
-        }
-
-        local.email = remote.email.clone();
-        local.tag = remote.tag.clone();
-
-        // Handle deletion
-        if remote.deleted {
-            local.deleted = true;
-        }
-
-        local.updated_at = remote.updated_at;
-    }
-
-    pub fn is_duplicate(&self, contacts: &HashMap<Uuid, Contact>, remote: &Contact) -> bool {
-        contacts.values().any(|c| c == remote)
-    }
-}
-
-impl ContactManager {
-    pub fn new() -> Result<Self, AppError> {
-        let storage = storage::parse_storage_type_env_config(None)?;
-
-        let mut manager = Self {
-            mem: HashMap::new(),
-            storage,
-            index: Index {
-                name: HashMap::new(),
-                domain: HashMap::new(),
-            },
-        };
-        manager.load()?;
-        manager.index = Index::new(&manager)?;
-
-        if manager.storage.get_medium() == "txt" {
-            manager.migrate_from_storage(&JsonStorage::new()?)?;
-        } else {
-            manager.migrate_from_storage(&TxtStorage::new()?)?;
-        }
-        Ok(manager)
+    /// Starts building a manager over `storage`, with nothing read from env vars or config files.
+    pub fn builder(storage: Box<dyn ContactStore>) -> ContactManagerBuilder {
+        ContactManagerBuilder::new(storage)
     }
 
     pub fn contact_list(&self) -> Vec<&Contact> {
@@ -203,9 +191,13 @@ impl ContactManager {
         let index = &self.index;
         let mut ids_as_set: HashSet<Uuid> = HashSet::new();
 
-        for name_slice in names {
-            let ids = index.name.get(&name_slice.to_ascii_lowercase())?;
-            ids_as_set = ids_as_set.union(ids).copied().collect()
+        if index.options.name {
+            for name_slice in names {
+                let ids = index.name.get(&name_slice.to_ascii_lowercase())?;
+                ids_as_set = ids_as_set.union(ids).copied().collect()
+            }
+        } else {
+            ids_as_set = self.mem.keys().copied().collect();
         }
 
         let ids: Vec<Uuid> = ids_as_set
@@ -234,8 +226,9 @@ impl ContactManager {
     pub fn delete_contact(&mut self, id: &Uuid) -> Result<(), AppError> {
         match self.mem.get_mut(id) {
             Some(deleted_contact) => {
-                deleted_contact.deleted = true;
-                deleted_contact.updated_at = Utc::now();
+                deleted_contact.clock.receive(&self.clock);
+                deleted_contact.mark_deleted(&self.sync_state.device);
+                self.clock.receive(&deleted_contact.clock);
                 self.index
                     .update_both_indexes(deleted_contact, &IndexUpdateType::Remove);
                 Ok(())
@@ -243,19 +236,85 @@ impl ContactManager {
             None => Err(AppError::NotFound("Contact".to_string())),
         }
     }
+    /// Adds a copy of a contact to another book. The copy gets its own id,
+    /// so the two are edited and synced independently.
+    pub fn copy_contact_to(&self, id: &Uuid, target: &mut ContactManager) -> Result<(), AppError> {
+        let contact = self
+            .mem
+            .get(id)
+            .filter(|c| !c.deleted)
+            .ok_or(AppError::NotFound("Contact".to_string()))?;
+
+        let mut copy = Contact::new(
+            contact.name.clone(),
+            contact.phone.clone(),
+            contact.email.clone(),
+            contact.tag.clone(),
+        );
+        copy.touch_after(&mut target.clock, &self.sync_state.device);
+
+        target.add_new_contact(copy)
+    }
+
+    /// Moves a contact to another book, leaving a tombstone here so the
+    /// deletion reaches the remotes this book syncs with.
+    pub fn move_contact_to(
+        &mut self,
+        id: &Uuid,
+        target: &mut ContactManager,
+    ) -> Result<(), AppError> {
+        let mut contact = self
+            .mem
+            .get(id)
+            .filter(|c| !c.deleted)
+            .cloned()
+            .ok_or(AppError::NotFound("Contact".to_string()))?;
+        contact.touch_after(&mut target.clock, &self.sync_state.device);
+
+        target.add_new_contact(contact)?;
+        self.delete_contact(id)
+    }
+
+    fn add_new_contact(&mut self, contact: Contact) -> Result<(), AppError> {
+        if contact.already_exist(&self.contact_list()[0..]) {
+            return Err(AppError::Validation(
+                "Contact with this name and number already exist".to_string(),
+            ));
+        }
+
+        self.add_contact(contact);
+        Ok(())
+    }
+
     /// Permanently remove contacts that were soft-deleted more than `days` days ago.
-    /// Returns the number of contacts removed.
-    pub fn purge_soft_deleted_older_than(&mut self, days: i64) {
+    /// Tombstones that a remote synced with before hasn't seen yet are kept,
+    /// so the deletion still reaches it on the next sync.
+    /// Fails without purging anything when the sync snapshots can't be read,
+    /// as it can't tell which remotes have seen the deletions.
+    pub fn purge_soft_deleted_older_than(&mut self, days: i64) -> Result<(), AppError> {
         let now = Utc::now().date_naive();
         let cutoff_date = now - Duration::days(days);
 
+        // A remote that hasn't synced for as long as tombstones are kept
+        // doesn't hold them back any more
+        let snapshots = self
+            .sync_state
+            .recent_snapshots(days, self.storage.as_ref())?;
+
         // Collect ids to remove to avoid mutating the map while iterating
         let to_remove: Vec<Uuid> = self
             .mem
             .iter()
             .filter_map(|(&id, contact)| {
-                let contact_date = contact.updated_at.date_naive();
-                if contact.deleted && contact_date <= cutoff_date {
+                let contact_date = contact
+                    .deleted_at
+                    .unwrap_or(contact.updated_at)
+                    .date_naive();
+                let unseen_by_remote = snapshots
+                    .iter()
+                    .any(|snapshot| snapshot.get(&id).is_some_and(|c| !c.deleted));
+
+                if contact.deleted && contact_date <= cutoff_date && !unseen_by_remote {
                     Some(id)
                 } else {
                     None
@@ -267,10 +326,12 @@ impl ContactManager {
         for id in &to_remove {
             self.mem.remove(id);
         }
+        Ok(())
     }
 
     pub fn migrate_from_storage(&mut self, storage: &dyn ContactStore) -> Result<(), AppError> {
         let contacts = storage.load()?;
+        self.clock.receive(&clock::latest(&contacts));
 
         for contact in contacts.values() {
             self.index
@@ -283,42 +344,76 @@ impl ContactManager {
 
     pub fn load(&mut self) -> Result<(), AppError> {
         self.mem = self.storage.load()?;
+        self.clock = clock::latest(&self.mem);
         Ok(())
     }
 
     pub fn save(&mut self) -> Result<(), AppError> {
         // Purge soft-deleted contacts older than configured days before persisting.
-        // Default: 1 days.
-        let purge_days: i64 = helper::get_env_value_by_key("PURGE_DAYS")
-            .ok()
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or(1);
+        if let PurgePolicy::OlderThanDays(days) = self.purge {
+            self.purge_soft_deleted_older_than(days)?;
+        }
 
-        self.purge_soft_deleted_older_than(purge_days);
+        self.storage.save(&self.mem)?;
+        self.remove_replaced_files()
+    }
 
-        self.storage.save(&self.mem)
+    /// Deletes the files of `replaced_files`, now that the book holds their contacts.
+    pub fn remove_replaced_files(&mut self) -> Result<(), AppError> {
+        let location = self.storage.get_location();
+        for path in self.replaced_files.drain(..) {
+            if path != location && fs::exists(&path)? {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
     }
 
+    /// Saves a single changed contact: appended or updated in place when the
+    /// store writes records, else through a full `save`.
+    pub fn save_contact(&mut self, id: &Uuid) -> Result<(), AppError> {
+        match self.mem.get(id) {
+            Some(contact) if self.storage.writes_records() => self.storage.upsert(contact),
+            Some(_) => self.save(),
+            None => Err(AppError::NotFound("Contact".to_string())),
+        }
+    }
+
+    /// Merges contacts from `storage` into the local book and saves it.
+    /// Conflicts the policy can't settle are added to the conflict queue and returned;
+    /// everything else is applied.
     pub fn import_contacts_from_storage(
         &mut self,
         storage: Box<dyn ContactStore>,
-    ) -> Result<(), AppError> {
+        policy: &dyn SyncPolicy,
+    ) -> Result<Vec<SyncConflict>, AppError> {
         let mut base = self.mem.clone();
 
-        let sync_status = self.sync_from_storage(
+        let snapshot = self.sync_state.snapshot(&storage.get_location());
+        let remote_contacts = storage
+            .load_iter()?
+            .map(|contact| contact.map(|c| (c.id, c)))
+            .collect::<Result<HashMap<Uuid, Contact>, AppError>>()?;
+
+        let sync_status = self.sync_contacts(
             &mut base,
-            storage,
-            SyncPolicy::LastWriteWinsPolicy(LastWriteWinsPolicy),
+            remote_contacts.clone(),
+            policy,
+            &storage.get_location(),
         );
 
-        if sync_status.is_err() {
-            self.save()?; // rollback to previous state on error
+        let conflicts = match sync_status {
+            Ok(conflicts) => conflicts,
+            Err(err) => {
+                self.save()?; // rollback to previous state on error
 
-            return Err(sync_status.err().unwrap());
-        } else {
-            self.mem = base;
-            self.index = Index::new(self)?
-        }
+                return Err(err);
+            }
+        };
+
+        self.mem = base;
+        self.clock.receive(&clock::latest(&remote_contacts));
+        self.index = Index::new(self)?;
 
         let mut saved: Result<(), AppError> = Err(AppError::Synchronization(
             "Error saving synced data".to_string(),
@@ -335,40 +430,194 @@ impl ContactManager {
                 "Error saving synced data".to_string(),
             ));
         }
-        Ok(())
+
+        self.sync_state
+            .conflicts()
+            .push(conflicts.clone(), self.storage.as_ref())?;
+
+        // Everything the remote had is now merged locally, so it becomes
+        // the common ancestor for the next sync with this remote.
+        record_snapshot(
+            &snapshot,
+            remote_contacts,
+            &conflicts,
+            self.storage.as_ref(),
+        )?;
+
+        Ok(conflicts)
+    }
+
+    /// Runs the sync against a copy of the book and reports what an import
+    /// from `storage` would change. Nothing is saved.
+    pub fn preview_import(
+        &self,
+        storage: &dyn ContactStore,
+        policy: &dyn SyncPolicy,
+    ) -> Result<SyncReport, AppError> {
+        let mut base = self.mem.clone();
+        let remote_contacts = storage.load()?;
+
+        let conflicts = self.sync_contacts(
+            &mut base,
+            remote_contacts.clone(),
+            policy,
+            &storage.get_location(),
+        )?;
+
+        Ok(SyncReport::new(
+            &self.mem,
+            &base,
+            &remote_contacts,
+            conflicts,
+        ))
+    }
+
+    /// Pulls from `storage`, merges and pushes the merged result back to the
+    /// same storage, then saves the local book. The push comes first, so if it
+    /// fails, or the remote changed since it was loaded, the local book, the
+    /// conflict queue and the snapshot are left as they were. Conflicted
+    /// contacts are queued and left as they are on both sides.
+    pub fn sync_with_storage(
+        &mut self,
+        storage: Box<dyn ContactStore>,
+        policy: &dyn SyncPolicy,
+    ) -> Result<SyncOutcome, AppError> {
+        let snapshot = self.sync_state.snapshot(&storage.get_location());
+        let remote_contacts = storage.load()?;
+
+        let mut merged = self.mem.clone();
+        let conflicts = self.sync_contacts(
+            &mut merged,
+            remote_contacts.clone(),
+            policy,
+            &storage.get_location(),
+        )?;
+        let outgoing = outgoing_contacts(&merged, &remote_contacts, &conflicts);
+
+        // Another device may have written since the load; pushing would drop its changes
+        if !sync::same_contacts(&remote_contacts, &storage.load()?) {
+            return Err(AppError::Synchronization(
+                "Remote changed during sync, nothing was pushed. Run sync again".to_string(),
+            ));
+        }
+        storage.save(&outgoing)?;
+
+        // A write racing the push would leave the snapshot out of step with the remote
+        if !sync::same_contacts(&outgoing, &storage.load()?) {
+            return Err(AppError::Synchronization(
+                "Remote changed while pushing, the local book was left as it was. Run sync again"
+                    .to_string(),
+            ));
+        }
+
+        let pulled = SyncReport::new(&self.mem, &merged, &remote_contacts, conflicts.clone());
+        let pushed = SyncReport::new(&remote_contacts, &outgoing, &HashMap::new(), Vec::new());
+
+        self.mem = merged;
+        self.clock.receive(&clock::latest(&remote_contacts));
+        self.index = Index::new(self)?;
+        self.save()?;
+        self.sync_state
+            .conflicts()
+            .push(conflicts.clone(), self.storage.as_ref())?;
+        record_snapshot(&snapshot, outgoing, &conflicts, self.storage.as_ref())?;
+
+        Ok(SyncOutcome { pulled, pushed })
+    }
+
+    /// Reports what `sync_with_storage` would pull and push. Nothing is saved.
+    pub fn preview_sync(
+        &self,
+        storage: &dyn ContactStore,
+        policy: &dyn SyncPolicy,
+    ) -> Result<SyncOutcome, AppError> {
+        let remote_contacts = storage.load()?;
+
+        let mut merged = self.mem.clone();
+        let conflicts = self.sync_contacts(
+            &mut merged,
+            remote_contacts.clone(),
+            policy,
+            &storage.get_location(),
+        )?;
+
+        let outgoing = outgoing_contacts(&merged, &remote_contacts, &conflicts);
+
+        Ok(SyncOutcome {
+            pulled: SyncReport::new(&self.mem, &merged, &remote_contacts, conflicts),
+            pushed: SyncReport::new(&remote_contacts, &outgoing, &HashMap::new(), Vec::new()),
+        })
     }
 
     pub fn export_contacts_to_storage(
         &self,
         storage: Box<dyn ContactStore>,
     ) -> Result<(), AppError> {
-        storage.save(&self.mem)
+        // A plain export isn't a sync peer, so no snapshot is kept for it
+        storage.save_iter(&mut self.mem.values())
     }
 
     pub fn sync_from_storage(
         &self,
         base: &mut HashMap<Uuid, Contact>,
         storage: Box<dyn ContactStore>,
-        policy: SyncPolicy,
-    ) -> Result<(), AppError> {
-        let mut remote_contacts = storage.load()?;
+        policy: &dyn SyncPolicy,
+    ) -> Result<Vec<SyncConflict>, AppError> {
+        let remote_contacts = storage.load()?;
 
-        let SyncPolicy::LastWriteWinsPolicy(policy) = policy;
+        self.sync_contacts(base, remote_contacts, policy, &storage.get_location())
+    }
+
+    /// Merges `remote_contacts` into `base`. Contacts that can't be merged are
+    /// left untouched in `base` and returned as conflicts.
+    pub fn sync_contacts(
+        &self,
+        base: &mut HashMap<Uuid, Contact>,
+        mut remote_contacts: HashMap<Uuid, Contact>,
+        policy: &dyn SyncPolicy,
+        source: &str,
+    ) -> Result<Vec<SyncConflict>, AppError> {
+        let mut conflicts = Vec::new();
+
+        // Contacts from imports or in-memory books without clocks are ordered by
+        // their updated_at. Books read from files already have theirs.
+        clock::seed_clocks(base);
+        clock::seed_clocks(&mut remote_contacts);
 
         for remote_contact in remote_contacts.values_mut() {
             // Check if contact exist in local storage
             if let Some(local_contact) = base.get_mut(&remote_contact.id) {
                 // Confirm contact were created the same time to be sure they are the same
                 if !policy.verify_match(local_contact, remote_contact) {
-                    return Err(AppError::Synchronization(format!(
-                        "Date conflict for contact:{{{}, {}}}",
-                        remote_contact.name, remote_contact.phone
-                    )));
+                    conflicts.push(SyncConflict::new(source, local_contact, remote_contact));
+                    continue;
                 }
 
+                if sync::is_delete_edit_conflict(local_contact, remote_contact)
+                    && let Some(decision) =
+                        sync::deletion_decision(policy.deletion_rule(), local_contact)
+                {
+                    match decision {
+                        SyncDecision::LocalWins => {}
+                        SyncDecision::RemoteWins => {
+                            sync::take_remote_fields(local_contact, remote_contact)
+                        }
+                        SyncDecision::Conflict => {
+                            conflicts.push(SyncConflict::new(source, local_contact, remote_contact))
+                        }
+                    }
+                    continue;
+                }
+
+                let unchanged_local = local_contact.clone();
+
                 match policy.conflict_resolution(local_contact, remote_contact) {
                     SyncDecision::LocalWins => continue,
                     SyncDecision::RemoteWins => policy.merge_changes(local_contact, remote_contact),
+                    SyncDecision::Conflict => {
+                        *local_contact = unchanged_local;
+                        conflicts.push(SyncConflict::new(source, local_contact, remote_contact));
+                    }
                 }
             } else {
                 // Ignore duplicate contacts (same name && phone)
@@ -379,7 +628,59 @@ impl ContactManager {
                 base.insert(remote_contact.id, remote_contact.clone());
             }
         }
-        Ok(())
+        Ok(conflicts)
+    }
+
+    /// Applies the user's choice for a queued conflict, saves the book and
+    /// removes the conflict from the queue.
+    pub fn resolve_conflict(
+        &mut self,
+        conflict: &SyncConflict,
+        resolution: ConflictResolution,
+    ) -> Result<(), AppError> {
+        let mut resolved = match resolution {
+            // The contact may have been edited since the conflict was queued
+            ConflictResolution::KeepLocal => self
+                .mem
+                .get(&conflict.id)
+                .cloned()
+                .unwrap_or_else(|| conflict.local.clone()),
+            ConflictResolution::TakeRemote => conflict.remote.clone(),
+            ConflictResolution::Merged(contact) => *contact,
+        };
+        // The resolution must win over both versions on the next sync
+        resolved.id = conflict.id;
+        resolved.clock = conflict
+            .local
+            .clock
+            .clone()
+            .max(conflict.remote.clock.clone())
+            .max(resolved.clock.clone());
+        resolved.touch_after(&mut self.clock, &self.sync_state.device);
+
+        if let Some(current) = self.mem.get(&conflict.id) {
+            self.index
+                .update_both_indexes(&current.clone(), &IndexUpdateType::Remove);
+        }
+        if !resolved.deleted {
+            resolved.deleted_at = None;
+            resolved.deleted_by = None;
+            self.index
+                .update_both_indexes(&resolved, &IndexUpdateType::Add);
+        }
+        self.mem.insert(resolved.id, resolved);
+        self.save()?;
+
+        // The remote version has now been seen, so it is the ancestor for the next sync.
+        let snapshot = self.sync_state.snapshot(&conflict.source);
+        let mut ancestors = snapshot.load(self.storage.as_ref())?;
+        ancestors.insert(conflict.id, conflict.remote.clone());
+        snapshot.save(&ancestors, self.storage.as_ref())?;
+
+        let queue = self.sync_state.conflicts();
+        let mut pending = queue.load(self.storage.as_ref())?;
+        pending.retain(|c| !(c.id == conflict.id && c.source == conflict.source));
+        queue.save(&pending, self.storage.as_ref())
     }
 
     pub fn create_name_search_index(&self) -> Result<HashMap<String, HashSet<Uuid>>, AppError> {
@@ -584,8 +885,24 @@ impl ContactManager {
         let contacts_map = Arc::new(&self.mem);
 
         let default_set: HashSet<Uuid> = HashSet::new();
+        let scanned_set: HashSet<Uuid>;
 
-        let ids_as_set = index.domain.get(domain).unwrap_or(&default_set);
+        let ids_as_set = if index.options.email_domain {
+            index.domain.get(domain).unwrap_or(&default_set)
+        } else {
+            scanned_set = self
+                .contact_list()
+                .iter()
+                .filter(|c| {
+                    c.email
+                        .rsplit('@')
+                        .next()
+                        .is_some_and(|d| d.eq_ignore_ascii_case(domain))
+                })
+                .map(|c| c.id)
+                .collect();
+            &scanned_set
+        };
         let index_match = Arc::new(ids_as_set.iter().collect::<Vec<&Uuid>>()); // Convert to Vec
 
         let length = index_match.len();
@@ -665,23 +982,56 @@ fn allocate_work_size_for_single_thread(
     (start, end)
 }
 
+/// Stores `contacts` as the common ancestor for the next sync with a remote.
+/// Conflicted contacts keep their previous ancestor until they are resolved.
+fn record_snapshot(
+    snapshot: &SyncSnapshot,
+    mut contacts: HashMap<Uuid, Contact>,
+    conflicts: &[SyncConflict],
+    book: &dyn ContactStore,
+) -> Result<(), AppError> {
+    let previous = snapshot.load(book)?;
+
+    for conflict in conflicts {
+        match previous.get(&conflict.id) {
+            Some(ancestor) => contacts.insert(conflict.id, ancestor.clone()),
+            None => contacts.remove(&conflict.id),
+        };
+    }
+    snapshot.save(&contacts, book)
+}
+
+/// The merged book as it should be pushed: conflicted contacts keep the remote's version.
+fn outgoing_contacts(
+    merged: &HashMap<Uuid, Contact>,
+    remote: &HashMap<Uuid, Contact>,
+    conflicts: &[SyncConflict],
+) -> HashMap<Uuid, Contact> {
+    let mut outgoing = merged.clone();
+
+    for conflict in conflicts {
+        if let Some(remote_contact) = remote.get(&conflict.id) {
+            outgoing.insert(conflict.id, remote_contact.clone());
+        }
+    }
+    outgoing
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
+    use clock::Hlc;
+    use file::{JsonStorage, TxtStorage};
+    use storage::memory::MemStore;
 
     use super::*;
 
     #[test]
     fn adds_persistent_contact_with_txt() -> Result<(), AppError> {
-        let storage_backend = Box::new(TxtStorage::new()?);
-        let mut storage = ContactManager {
-            mem: HashMap::new(),
-            storage: storage_backend,
-            index: Index {
-                name: HashMap::new(),
-                domain: HashMap::new(),
-            },
-        };
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.txt");
+        let mut storage =
+            ContactManager::builder(Box::new(TxtStorage::at(&path.to_string_lossy()))).build()?;
 
         let new_contact = Contact::new(
             "Uche".to_string(),
@@ -713,15 +1063,10 @@ mod tests {
 
     #[test]
     fn delete_persistent_contact_with_txt() -> Result<(), AppError> {
-        let storage_backend = Box::new(TxtStorage::new()?);
-        let mut storage = ContactManager {
-            mem: HashMap::new(),
-            storage: storage_backend,
-            index: Index {
-                name: HashMap::new(),
-                domain: HashMap::new(),
-            },
-        };
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.txt");
+        let mut storage =
+            ContactManager::builder(Box::new(TxtStorage::at(&path.to_string_lossy()))).build()?;
 
         let contact1 = Contact::new(
             "Uche".to_string(),
@@ -746,9 +1091,7 @@ mod tests {
         storage.load()?;
         storage.index = Index::new(&storage)?;
 
-        let index = storage
-            .get_ids_by_name(&"Uche".to_string())
-            .unwrap_or_default();
+        let index = storage.get_ids_by_name("Uche").unwrap_or_default();
         storage.delete_contact(&index[0])?; // delete contact1 (Soft delete)
         storage.save()?;
 
@@ -767,21 +1110,27 @@ mod tests {
 
     #[test]
     fn json_store_is_persistent() -> Result<(), AppError> {
-        let mut storage = ContactManager::new()?;
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.json");
+        let mut storage =
+            ContactManager::builder(Box::new(JsonStorage::at(&path.to_string_lossy()))).build()?;
 
         let created = Utc::now();
         let id_1 = Uuid::new_v4();
         let id_2 = Uuid::new_v4();
 
         let contact1 = Contact {
-            id: id_1.clone(),
+            id: id_1,
             name: "Uche".to_string(),
             phone: "01234567890".to_string(),
             email: "ucheuche@gmail.com".to_string(),
             tag: "".to_string(),
             deleted: false,
-            created_at: created.clone(),
-            updated_at: created.clone(),
+            deleted_at: None,
+            deleted_by: None,
+            created_at: created,
+            updated_at: created,
+            clock: Hlc::default(),
         };
 
         let contact2 = Contact {
@@ -791,8 +1140,11 @@ mod tests {
             email: "".to_string(),
             tag: "".to_string(),
             deleted: false,
-            created_at: created.clone(),
-            updated_at: created.clone(),
+            deleted_at: None,
+            deleted_by: None,
+            created_at: created,
+            updated_at: created,
+            clock: Hlc::default(),
         };
 
         storage.add_contact(contact1);
@@ -843,15 +1195,13 @@ mod tests {
 
     #[test]
     fn migrates_contact() -> Result<(), AppError> {
-        let mut txt_store = ContactManager {
-            mem: HashMap::new(),
-            storage: Box::new(TxtStorage::new()?),
-            index: Index {
-                name: HashMap::new(),
-                domain: HashMap::new(),
-            },
-        };
-        txt_store.mem.clear();
+        let dir = tempfile::tempdir()?;
+        let txt_path = dir.path().join("contacts.txt");
+        let json_path = dir.path().join("contacts.json");
+
+        let mut txt_store =
+            ContactManager::builder(Box::new(TxtStorage::at(&txt_path.to_string_lossy())))
+                .build()?;
 
         let contact1 = Contact::new(
             "Uche".to_string(),
@@ -871,9 +1221,11 @@ mod tests {
         txt_store.save()?;
         txt_store.mem.clear();
 
-        let mut json_store = ContactManager::new()?;
-
-        // json_store.mem = json_store.load()?;
+        // Picks up the txt file next to the json file
+        let mut json_store =
+            ContactManager::builder(Box::new(JsonStorage::at(&json_path.to_string_lossy())))
+                .with_config(&Config::default())?
+                .build()?;
 
         json_store.add_contact(contact2);
         json_store.save()?;
@@ -909,7 +1261,7 @@ mod tests {
 
     #[test]
     fn index_updates_on_add_and_delete() -> Result<(), AppError> {
-        let mut store = ContactManager::new()?;
+        let mut store = ContactManager::builder(Box::new(MemStore::new())).build()?;
 
         let contact1 = Contact::new(
             "Uche".to_string(),
@@ -957,7 +1309,7 @@ mod tests {
 
     #[test]
     fn index_updates_on_edit() -> Result<(), AppError> {
-        let mut store = ContactManager::new()?;
+        let mut store = ContactManager::builder(Box::new(MemStore::new())).build()?;
 
         let contact = Contact::new(
             "John Doe".to_string(),
@@ -1027,7 +1379,7 @@ mod tests {
 
     #[test]
     fn fuzzy_search_name_matches_on_partial() -> Result<(), AppError> {
-        let mut store = ContactManager::new()?;
+        let mut store = ContactManager::builder(Box::new(MemStore::new())).build()?;
 
         let contact = Contact::new(
             "Uche Johnson".to_string(),
@@ -1054,7 +1406,7 @@ mod tests {
 
     #[test]
     fn fuzzy_search_email_domain_returns_contact() -> Result<(), AppError> {
-        let mut store = ContactManager::new()?;
+        let mut store = ContactManager::builder(Box::new(MemStore::new())).build()?;
 
         let contact = Contact::new(
             "Alice".to_string(),
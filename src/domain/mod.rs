@@ -1,4 +1,8 @@
+pub mod builder;
+pub mod clock;
 pub mod contact;
 pub mod manager;
+pub mod render;
+pub mod sync;
 
-use crate::prelude::{AppError, Contact, ContactStore, file, storage, uuid::Uuid};
+use crate::prelude::{AppError, Config, Contact, ContactStore, file, storage, uuid::Uuid};
@@ -0,0 +1,222 @@
+use super::*;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const NO_TAG: &str = "No tag";
+
+/// A document a book can be exported to for reading or printing. Unlike the
+/// storage formats, nothing is read back from it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderFormat {
+    /// A single page with its styles and search script inline
+    Html,
+    Markdown,
+}
+
+impl RenderFormat {
+    /// The format an export path asks for, by its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "html" | "htm" => Some(Self::Html),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn render(&self, title: &str, contacts: &[&Contact]) -> String {
+        match self {
+            Self::Html => to_html(title, contacts),
+            Self::Markdown => to_markdown(title, contacts),
+        }
+    }
+}
+
+/// Contacts grouped by tag, tags and names in alphabetical order. Untagged
+/// contacts come last.
+fn by_tag<'a>(contacts: &[&'a Contact]) -> Vec<(String, Vec<&'a Contact>)> {
+    let mut groups: BTreeMap<(bool, String), (String, Vec<&Contact>)> = BTreeMap::new();
+    for &contact in contacts {
+        let tag = contact.tag.trim();
+        let key = (tag.is_empty(), tag.to_lowercase());
+        let label = if tag.is_empty() { NO_TAG } else { tag };
+        groups
+            .entry(key)
+            .or_insert_with(|| (label.to_string(), Vec::new()))
+            .1
+            .push(contact);
+    }
+
+    groups
+        .into_values()
+        .map(|(tag, mut contacts)| {
+            contacts.sort_by_key(|c| (c.name.to_lowercase(), c.phone.clone()));
+            (tag, contacts)
+        })
+        .collect()
+}
+
+/// A page with one table per tag and a search box that filters all of them.
+pub fn to_html(title: &str, contacts: &[&Contact]) -> String {
+    let mut sections = String::new();
+    for (tag, contacts) in by_tag(contacts) {
+        sections.push_str(&format!(
+            "<section>\n<h2>{} <small>({})</small></h2>\n<table>\n\
+             <thead><tr><th>Name</th><th>Phone</th><th>Email</th></tr></thead>\n<tbody>\n",
+            escape_html(&tag),
+            contacts.len()
+        ));
+        for contact in contacts {
+            let email = match contact.email.as_str() {
+                "" => String::new(),
+                email => format!("<a href=\"mailto:{0}\">{0}</a>", escape_html(email)),
+            };
+            sections.push_str(&format!(
+                "<tr><td>{}</td><td><a href=\"tel:{phone}\">{phone}</a></td><td>{email}</td></tr>\n",
+                escape_html(&contact.name),
+                phone = escape_html(&contact.phone),
+            ));
+        }
+        sections.push_str("</tbody>\n</table>\n</section>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 60rem; padding: 0 1rem; color: #222; }}
+input {{ width: 100%; padding: .5rem; font-size: 1rem; box-sizing: border-box; }}
+table {{ width: 100%; border-collapse: collapse; margin-bottom: 1.5rem; }}
+th, td {{ text-align: left; padding: .35rem .5rem; border-bottom: 1px solid #ddd; }}
+th {{ background: #f4f4f4; }}
+h2 small {{ color: #777; font-weight: normal; }}
+a {{ color: inherit; }}
+@media print {{ input {{ display: none; }} section {{ break-inside: avoid; }} }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>{count} contacts</p>
+<input id="search" type="search" placeholder="Search" aria-label="Search contacts">
+{sections}<script>
+document.getElementById("search").addEventListener("input", function () {{
+  var query = this.value.toLowerCase();
+  document.querySelectorAll("section").forEach(function (section) {{
+    var shown = 0;
+    section.querySelectorAll("tbody tr").forEach(function (row) {{
+      var match = row.textContent.toLowerCase().indexOf(query) !== -1;
+      row.hidden = !match;
+      if (match) shown++;
+    }});
+    section.hidden = shown === 0;
+  }});
+}});
+</script>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        count = contacts.len(),
+    )
+}
+
+/// A document with one table per tag.
+pub fn to_markdown(title: &str, contacts: &[&Contact]) -> String {
+    let mut document = format!("# {}\n\n{} contacts\n", title, contacts.len());
+    for (tag, contacts) in by_tag(contacts) {
+        document.push_str(&format!(
+            "\n## {}\n\n| Name | Phone | Email |\n| --- | --- | --- |\n",
+            escape_markdown(&tag)
+        ));
+        for contact in contacts {
+            document.push_str(&format!(
+                "| {} | {} | {} |\n",
+                escape_markdown(&contact.name),
+                escape_markdown(&contact.phone),
+                escape_markdown(&contact.email)
+            ));
+        }
+    }
+    document
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Keeps a value inside its table cell.
+fn escape_markdown(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str, tag: &str) -> Contact {
+        Contact::new(
+            name.to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            tag.to_string(),
+        )
+    }
+
+    #[test]
+    fn groups_by_tag_with_untagged_last() {
+        let (bola, ada, chidi, dayo) = (
+            contact("Bola", "work"),
+            contact("Ada", "Work"),
+            contact("Chidi", ""),
+            contact("Dayo", "gym"),
+        );
+        let groups = by_tag(&[&bola, &ada, &chidi, &dayo]);
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(tag, contacts)| {
+                (
+                    tag.as_str(),
+                    contacts.iter().map(|c| c.name.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("gym", vec!["Dayo"]),
+                ("work", vec!["Ada", "Bola"]),
+                (NO_TAG, vec!["Chidi"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn escapes_values() {
+        let odd = contact("Ada <Obi> | Co", "a&b");
+        let html = to_html("Team", &[&odd]);
+        assert!(html.contains("Ada &lt;Obi&gt; | Co"));
+        assert!(html.contains("<h2>a&amp;b <small>(1)</small></h2>"));
+        assert!(!html.contains("<Obi>"));
+
+        let markdown = to_markdown("Team", &[&odd]);
+        assert!(markdown.contains("| Ada <Obi> \\| Co | 08012345678 |  |\n"));
+        assert_eq!(
+            RenderFormat::from_path("book.MD"),
+            Some(RenderFormat::Markdown)
+        );
+        assert_eq!(RenderFormat::from_path("book.csv"), None);
+    }
+}
@@ -0,0 +1,476 @@
+use super::*;
+
+use crate::prelude::{ContactStore, HashMap};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use storage::sync_state::SyncState;
+
+pub enum SyncDecision {
+    LocalWins,
+    RemoteWins,
+    Conflict,
+}
+
+/// A contact the sync could not merge on its own, kept until the user resolves it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub id: Uuid,
+    /// Location of the store the remote version came from
+    pub source: String,
+    pub fields: Vec<String>,
+    pub local: Contact,
+    pub remote: Contact,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// What happens when a contact deleted on one side was edited later on the other.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DeletionRule {
+    /// The sync policy settles it like any other change
+    #[default]
+    FollowPolicy,
+    /// The edit brings the contact back
+    EditResurrects,
+    /// The deletion is kept and the edit is dropped
+    DeleteWins,
+    /// Queue a conflict for the user to decide
+    Ask,
+}
+
+pub enum ConflictResolution {
+    KeepLocal,
+    TakeRemote,
+    Merged(Box<Contact>),
+}
+
+/// What a sync did (or would do, for a dry run) to the local book.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub added: Vec<Contact>,
+    pub updated: Vec<ContactUpdate>,
+    pub deleted: Vec<Contact>,
+    /// Remote contacts left out as duplicates of a local contact
+    pub skipped: Vec<Contact>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactUpdate {
+    pub id: Uuid,
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl SyncReport {
+    /// Compares the book before and after merging `remote` into it.
+    pub fn new(
+        before: &HashMap<Uuid, Contact>,
+        after: &HashMap<Uuid, Contact>,
+        remote: &HashMap<Uuid, Contact>,
+        conflicts: Vec<SyncConflict>,
+    ) -> Self {
+        let mut report = Self {
+            conflicts,
+            ..Default::default()
+        };
+
+        for contact in after.values() {
+            let Some(previous) = before.get(&contact.id) else {
+                // Remote tombstones are kept but never shown
+                if !contact.deleted {
+                    report.added.push(contact.clone());
+                }
+                continue;
+            };
+
+            if contact.deleted && !previous.deleted {
+                report.deleted.push(contact.clone());
+                continue;
+            }
+
+            let changes: Vec<FieldChange> = differing_fields(previous, contact)
+                .into_iter()
+                .map(|field| FieldChange {
+                    before: field_value(previous, &field),
+                    after: field_value(contact, &field),
+                    field,
+                })
+                .collect();
+
+            if !changes.is_empty() {
+                report.updated.push(ContactUpdate {
+                    id: contact.id,
+                    name: contact.name.clone(),
+                    changes,
+                });
+            }
+        }
+
+        report.skipped = remote
+            .values()
+            .filter(|c| !after.contains_key(&c.id))
+            .cloned()
+            .collect();
+
+        report.added.sort_by(|a, b| a.name.cmp(&b.name));
+        report.updated.sort_by(|a, b| a.name.cmp(&b.name));
+        report.deleted.sort_by(|a, b| a.name.cmp(&b.name));
+        report.skipped.sort_by(|a, b| a.name.cmp(&b.name));
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.updated.is_empty()
+            && self.deleted.is_empty()
+            && self.skipped.is_empty()
+            && self.conflicts.is_empty()
+    }
+}
+
+/// Both directions of a `sync`: what the local book took from the remote and what the remote was sent.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncOutcome {
+    pub pulled: SyncReport,
+    pub pushed: SyncReport,
+}
+
+impl SyncConflict {
+    pub fn new(source: &str, local: &Contact, remote: &Contact) -> Self {
+        Self {
+            id: local.id,
+            source: source.to_string(),
+            fields: differing_fields(local, remote),
+            local: local.clone(),
+            remote: remote.clone(),
+            detected_at: Utc::now(),
+        }
+    }
+}
+
+/// Decides how a remote contact is merged into the local book during a sync.
+///
+/// Only `conflict_resolution` is required; the other methods have defaults that
+/// match the built-in policies, so custom policies can be kept small.
+pub trait SyncPolicy {
+    /// Confirms that a local and remote contact sharing an id are the same record.
+    /// Ids are random, so by default they are.
+    fn verify_match(&self, _local: &Contact, _remote: &Contact) -> bool {
+        true
+    }
+
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision;
+
+    /// Applies the remote contact onto the local one once the remote has won.
+    fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
+        take_remote_fields(local, remote);
+    }
+
+    fn is_duplicate(&self, contacts: &HashMap<Uuid, Contact>, remote: &Contact) -> bool {
+        contacts.values().any(|c| c == remote)
+    }
+
+    /// Settles a deletion on one side against a later edit on the other.
+    /// Checked before `conflict_resolution`, unless it follows the policy.
+    fn deletion_rule(&self) -> DeletionRule {
+        DeletionRule::default()
+    }
+}
+
+/// Runs any policy with a different deletion rule.
+pub struct WithDeletionRule {
+    pub policy: Box<dyn SyncPolicy>,
+    pub rule: DeletionRule,
+}
+
+impl SyncPolicy for WithDeletionRule {
+    fn verify_match(&self, local: &Contact, remote: &Contact) -> bool {
+        self.policy.verify_match(local, remote)
+    }
+
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
+        self.policy.conflict_resolution(local, remote)
+    }
+
+    fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
+        self.policy.merge_changes(local, remote)
+    }
+
+    fn is_duplicate(&self, contacts: &HashMap<Uuid, Contact>, remote: &Contact) -> bool {
+        self.policy.is_duplicate(contacts, remote)
+    }
+
+    fn deletion_rule(&self) -> DeletionRule {
+        self.rule
+    }
+}
+
+/// Keeps the local version of every contact; only new contacts are pulled in.
+pub struct LocalAlwaysWins;
+
+/// Overwrites local contacts with the remote version whenever they differ.
+pub struct RemoteAlwaysWins;
+
+/// Whole-contact merge: whichever side has the later clock wins every field.
+pub struct LastWriteWins;
+
+/// Field-level merge against the contacts as they were at the last sync with a remote.
+pub struct FieldLevelMerge {
+    pub snapshot: HashMap<Uuid, Contact>,
+}
+
+/// Never picks a side: every disagreement goes to the conflict queue.
+pub struct Manual;
+
+impl SyncPolicy for LocalAlwaysWins {
+    fn conflict_resolution(&self, _local: &mut Contact, _remote: &mut Contact) -> SyncDecision {
+        SyncDecision::LocalWins
+    }
+}
+
+impl SyncPolicy for RemoteAlwaysWins {
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
+        if has_same_fields(local, remote) {
+            SyncDecision::LocalWins
+        } else {
+            SyncDecision::RemoteWins
+        }
+    }
+}
+
+impl SyncPolicy for LastWriteWins {
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
+        // If local contact has been deleted, ensure remote counterpart is also marked deleted
+        if local.deleted {
+            if !remote.deleted {
+                remote.updated_at = local.updated_at;
+                remote.clock = local.clock.clone();
+            }
+            remote.deleted = local.deleted;
+            return SyncDecision::LocalWins;
+        }
+
+        // If local contact has the latest update, no need to update
+        if local.clock >= remote.clock {
+            return SyncDecision::LocalWins;
+        }
+
+        SyncDecision::RemoteWins
+    }
+
+    fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
+        // Update name and phone if either has changed
+        if local.name != remote.name || !contact::phone_number_matches(&local.phone, &remote.phone)
+        {
+            local.name = remote.name.clone();
+            local.phone = remote.phone.clone();
+            local.updated_at = remote.updated_at;
+        }
+
+        local.email = remote.email.clone();
+        local.tag = remote.tag.clone();
+
+        // Handle deletion
+        if remote.deleted {
+            local.deleted = true;
+            local.deleted_at = remote.deleted_at;
+            local.deleted_by = remote.deleted_by.clone();
+        }
+
+        local.updated_at = remote.updated_at;
+        local.clock = remote.clock.clone();
+    }
+}
+
+impl FieldLevelMerge {
+    pub fn new(snapshot: HashMap<Uuid, Contact>) -> Self {
+        Self { snapshot }
+    }
+
+    /// Uses the snapshot `state` recorded at the last sync with `storage` as the
+    /// common ancestor. `book` is the local store the snapshot was sealed by.
+    pub fn for_storage(
+        storage: &dyn ContactStore,
+        state: &SyncState,
+        book: &dyn ContactStore,
+    ) -> Result<Self, AppError> {
+        Ok(Self::new(
+            state.snapshot(&storage.get_location()).load(book)?,
+        ))
+    }
+}
+
+impl SyncPolicy for FieldLevelMerge {
+    /// A field changed on both sides since the last sync is a conflict.
+    /// Without a snapshot of the contact there is no telling which side changed
+    /// it, so any difference is a conflict.
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
+        if has_same_fields(local, remote) {
+            return SyncDecision::LocalWins;
+        }
+
+        let Some(ancestor) = self.snapshot.get(&local.id) else {
+            return SyncDecision::Conflict;
+        };
+
+        let changed_on_both_sides = differing_fields(local, remote).iter().any(|field| {
+            field_value(ancestor, field) != field_value(local, field)
+                && field_value(ancestor, field) != field_value(remote, field)
+        });
+
+        if changed_on_both_sides {
+            SyncDecision::Conflict
+        } else {
+            SyncDecision::RemoteWins
+        }
+    }
+
+    /// Takes every field that changed on only the remote side since the last sync.
+    fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
+        // Contacts without a snapshot are always conflicts
+        let Some(ancestor) = self.snapshot.get(&local.id) else {
+            return;
+        };
+
+        let changes = [
+            merge_field(&ancestor.name, &mut local.name, &remote.name),
+            merge_field(&ancestor.phone, &mut local.phone, &remote.phone),
+            merge_field(&ancestor.email, &mut local.email, &remote.email),
+            merge_field(&ancestor.tag, &mut local.tag, &remote.tag),
+            merge_field(&ancestor.deleted, &mut local.deleted, &remote.deleted),
+        ];
+
+        // Deletion state was taken from the remote, so its tombstone goes with it
+        if changes[4] {
+            local.deleted_at = remote.deleted_at;
+            local.deleted_by = remote.deleted_by.clone();
+        }
+
+        if changes.contains(&true) {
+            local.updated_at = local.updated_at.max(remote.updated_at);
+            local.clock = local.clock.clone().max(remote.clock.clone());
+        }
+    }
+}
+
+impl SyncPolicy for Manual {
+    fn conflict_resolution(&self, local: &mut Contact, remote: &mut Contact) -> SyncDecision {
+        if has_same_fields(local, remote) {
+            SyncDecision::LocalWins
+        } else {
+            SyncDecision::Conflict
+        }
+    }
+}
+
+/// True if both sets hold the same contacts with the same fields.
+///
+/// Ids and timestamps are not compared: files written before contacts had them
+/// get fresh ones on every load.
+pub fn same_contacts(a: &HashMap<Uuid, Contact>, b: &HashMap<Uuid, Contact>) -> bool {
+    fn fields(
+        contacts: &HashMap<Uuid, Contact>,
+    ) -> Vec<(&String, &String, &String, &String, bool)> {
+        let mut fields: Vec<_> = contacts
+            .values()
+            .map(|c| (&c.name, &c.phone, &c.email, &c.tag, c.deleted))
+            .collect();
+        fields.sort();
+        fields
+    }
+
+    a.len() == b.len() && fields(a) == fields(b)
+}
+
+/// Compares the user-editable fields and deletion state, ignoring timestamps.
+pub fn has_same_fields(a: &Contact, b: &Contact) -> bool {
+    a.name == b.name
+        && a.phone == b.phone
+        && a.email == b.email
+        && a.tag == b.tag
+        && a.deleted == b.deleted
+}
+
+/// True if one side deleted the contact and the other edited it after the deletion.
+pub fn is_delete_edit_conflict(local: &Contact, remote: &Contact) -> bool {
+    match (local.deleted, remote.deleted) {
+        (true, false) => remote.clock > local.clock,
+        (false, true) => local.clock > remote.clock,
+        _ => false,
+    }
+}
+
+/// Which side `rule` keeps in a delete-vs-edit conflict, `None` when the
+/// sync policy decides.
+pub fn deletion_decision(rule: DeletionRule, local: &Contact) -> Option<SyncDecision> {
+    let decision = match (rule, local.deleted) {
+        (DeletionRule::FollowPolicy, _) => return None,
+        (DeletionRule::Ask, _) => SyncDecision::Conflict,
+        (DeletionRule::EditResurrects, true) | (DeletionRule::DeleteWins, false) => {
+            SyncDecision::RemoteWins
+        }
+        (DeletionRule::EditResurrects, false) | (DeletionRule::DeleteWins, true) => {
+            SyncDecision::LocalWins
+        }
+    };
+    Some(decision)
+}
+
+/// Replaces the local contact with the remote version, tombstone included.
+pub fn take_remote_fields(local: &mut Contact, remote: &Contact) {
+    local.name = remote.name.clone();
+    local.phone = remote.phone.clone();
+    local.email = remote.email.clone();
+    local.tag = remote.tag.clone();
+    local.deleted = remote.deleted;
+    local.deleted_at = remote.deleted_at;
+    local.deleted_by = remote.deleted_by.clone();
+    local.updated_at = remote.updated_at;
+    local.clock = remote.clock.clone();
+}
+
+/// Names of the fields that differ between two versions of a contact.
+pub fn differing_fields(a: &Contact, b: &Contact) -> Vec<String> {
+    CONFLICT_FIELDS
+        .iter()
+        .filter(|field| field_value(a, field) != field_value(b, field))
+        .map(|field| field.to_string())
+        .collect()
+}
+
+pub const CONFLICT_FIELDS: [&str; 5] = ["name", "phone", "email", "tag", "deleted"];
+
+/// Display value of a contact field by name.
+pub fn field_value(contact: &Contact, field: &str) -> String {
+    match field {
+        "name" => contact.name.clone(),
+        "phone" => contact.phone.clone(),
+        "email" => contact.email.clone(),
+        "tag" => contact.tag.clone(),
+        "deleted" => contact.deleted.to_string(),
+        "created_at" => contact.created_at.to_rfc3339(),
+        "updated_at" => contact.updated_at.to_rfc3339(),
+        _ => String::new(),
+    }
+}
+
+/// Takes the remote value if only the remote changed it. Returns true if it was taken.
+fn merge_field<T: PartialEq + Clone>(ancestor: &T, local: &mut T, remote: &T) -> bool {
+    if local == remote || remote == ancestor {
+        return false;
+    }
+
+    if local == ancestor {
+        *local = remote.clone();
+        return true;
+    }
+
+    false
+}
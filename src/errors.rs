@@ -3,9 +3,12 @@ use std::sync::PoisonError;
 
 #[derive(Debug)]
 pub enum AppError {
+    Config(String),
     CsvError(csv::Error),
     DateTime(chrono::ParseError),
+    Encryption(String),
     FailedRequest(reqwest::Error),
+    Git(String),
     Io(std::io::Error),
     JsonPerser(serde_json::Error),
     NotFound(String),
@@ -15,6 +18,18 @@ pub enum AppError {
     Validation(String),
 }
 
+impl From<toml::de::Error> for AppError {
+    fn from(err: toml::de::Error) -> Self {
+        AppError::Config(err.to_string())
+    }
+}
+
+impl From<toml::ser::Error> for AppError {
+    fn from(err: toml::ser::Error) -> Self {
+        AppError::Config(err.to_string())
+    }
+}
+
 impl From<csv::Error> for AppError {
     fn from(err: csv::Error) -> Self {
         AppError::CsvError(err)
@@ -50,6 +65,12 @@ impl From<regex::Error> for AppError {
     }
 }
 
+impl From<git2::Error> for AppError {
+    fn from(err: git2::Error) -> Self {
+        AppError::Git(err.message().to_string())
+    }
+}
+
 impl From<reqwest::Error> for AppError {
     fn from(err: reqwest::Error) -> Self {
         AppError::FailedRequest(err)
@@ -59,15 +80,24 @@ impl From<reqwest::Error> for AppError {
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            AppError::Config(e) => {
+                write!(f, "Invalid configuration: {}", e)
+            }
             AppError::CsvError(e) => {
                 write!(f, "CSV parser failed: '{}'", e)
             }
             AppError::DateTime(e) => {
                 write!(f, "Invalid Datetime format: {}", e)
             }
+            AppError::Encryption(msg) => {
+                write!(f, "Encryption failed: {}", msg)
+            }
             AppError::FailedRequest(e) => {
                 write!(f, "HTTP request failed: '{}'", e)
             }
+            AppError::Git(msg) => {
+                write!(f, "Git failed: {}", msg)
+            }
             AppError::Io(e) => {
                 write!(f, "I/O error while accessing a file or resource: {}", e)
             }
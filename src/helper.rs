@@ -1,43 +1,51 @@
 use chrono::{DateTime, Utc};
 
-use crate::prelude::{AppError, Contact, HashMap, uuid::Uuid};
+use crate::prelude::{AppError, Contact, HashMap, Hlc, uuid::Uuid};
 use std::env;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::io::BufRead;
 use std::str::FromStr;
 
 pub fn serialize_contacts(contacts: &HashMap<Uuid, Contact>) -> String {
-    let mut data = String::new();
-
-    for contact in contacts.values() {
-        let created_at_str = contact.created_at.to_string();
-
-        let updated_at_str = contact.updated_at.to_string();
-
-        let ser_contact = format!(
-            "{{\n\
-        id: {}\n\
-        name: {}\n\
-        phone: {}\n\
-        email: {}\n\
-        tag: {}\n\
-        deleted: {}\n\
-        created_at: {}\n\
-        updated_at: {}\n\
-        }}\n",
-            contact.id,
-            contact.name,
-            contact.phone,
-            contact.email,
-            contact.tag,
-            contact.deleted,
-            created_at_str,
-            updated_at_str,
-        );
+    contacts.values().map(serialize_contact).collect()
+}
 
-        data.push_str(&ser_contact);
-    }
-    data
+/// One contact in the txt format.
+pub fn serialize_contact(contact: &Contact) -> String {
+    let created_at_str = contact.created_at.to_string();
+
+    let updated_at_str = contact.updated_at.to_string();
+
+    let deleted_at_str = contact
+        .deleted_at
+        .map(|dt| dt.to_string())
+        .unwrap_or_default();
+
+    format!(
+        "{{\n\
+    id: {}\n\
+    name: {}\n\
+    phone: {}\n\
+    email: {}\n\
+    tag: {}\n\
+    deleted: {}\n\
+    deleted_at: {}\n\
+    deleted_by: {}\n\
+    created_at: {}\n\
+    updated_at: {}\n\
+    clock: {}\n\
+    }}\n",
+        contact.id,
+        contact.name,
+        contact.phone,
+        contact.email,
+        contact.tag,
+        contact.deleted,
+        deleted_at_str,
+        contact.deleted_by.clone().unwrap_or_default(),
+        created_at_str,
+        updated_at_str,
+        contact.clock,
+    )
 }
 
 fn split_annotation(line: &str) -> (Option<&str>, &str) {
@@ -49,7 +57,7 @@ fn split_annotation(line: &str) -> (Option<&str>, &str) {
 }
 
 pub fn deserialize_contacts_from_txt_buffer(
-    buffer: BufReader<File>,
+    buffer: impl BufRead,
 ) -> Result<HashMap<Uuid, Contact>, AppError> {
     let mut contacts = HashMap::new();
     let mut test_contact = Contact {
@@ -59,8 +67,11 @@ pub fn deserialize_contacts_from_txt_buffer(
         email: "".to_string(),
         tag: "".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: Utc::now(),
         updated_at: Utc::now(),
+        clock: Hlc::default(),
     };
     let mut test_id = Uuid::new_v4();
     let mut id = test_id;
@@ -69,8 +80,11 @@ pub fn deserialize_contacts_from_txt_buffer(
     let mut email = "".to_string();
     let mut tag: String = "".to_string();
     let mut deleted = false;
+    let mut deleted_at = None;
+    let mut deleted_by = None;
     let mut created_at = Utc::now();
     let mut updated_at = Utc::now();
+    let mut clock = Hlc::default();
 
     for line in buffer.lines() {
         let line = line?;
@@ -95,10 +109,19 @@ pub fn deserialize_contacts_from_txt_buffer(
                 email: email.clone(),
                 tag: tag.clone(),
                 deleted,
+                deleted_at,
+                deleted_by: deleted_by.clone(),
                 created_at,
                 updated_at,
+                // Older files have no clock, damaged ones an unreadable one
+                clock: if clock.is_unset() {
+                    Hlc::seeded(updated_at)
+                } else {
+                    clock.clone()
+                },
             };
             contacts.insert(contact.id, contact);
+            clock = Hlc::default();
             continue;
         }
 
@@ -168,6 +191,25 @@ pub fn deserialize_contacts_from_txt_buffer(
             continue;
         }
 
+        if key == Some("deleted_at") {
+            deleted_at = if value.is_empty() {
+                None
+            } else {
+                Some(DateTime::<Utc>::from_str(value)?.to_utc())
+            };
+            continue;
+        }
+
+        if key == Some("deleted_by") {
+            deleted_by = (!value.is_empty()).then(|| value.to_string());
+            continue;
+        }
+
+        if key == Some("clock") {
+            clock = value.parse().unwrap_or_default();
+            continue;
+        }
+
         if key.is_some() && key == Some("updated_at") {
             if value.is_empty() {
                 continue;
@@ -186,43 +228,7 @@ pub fn get_env_value_by_key(key: &str) -> Result<String, AppError> {
     env::var(key).map_err(|_| AppError::NotFound(format!("env key ({key}) or value")))
 }
 
-/// This function sets a new or updates an  existing env value directly to .env file
-/// so that new configuration like resource ID or url remain persistent.
-pub fn set_env_value_in_file(key: &str, value: &str) -> Result<(), AppError> {
-    let env_path = ".env";
-
-    let content = fs::read_to_string(env_path).unwrap_or(env_path.to_string());
-
-    let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
-
-    let prefix = format!("{}=", key);
-    let new_line = format!("{}={}", key, value);
-
-    // Replace if key exists, otherwise push new line
-    let mut found = false;
-    for line in lines.iter_mut() {
-        if line.starts_with(&prefix) {
-            *line = new_line.clone();
-            found = true;
-            break;
-        }
-    }
-
-    if !found {
-        lines.push(new_line);
-    }
-
-    // Write back to .env
-    fs::write(env_path, lines.join("\n") + "\n")?;
-
-    // Add to running process
-    unsafe { env::set_var(key, value) }
-
-    Ok(())
-}
-
 #[cfg(test)]
-
 mod tests {
     use crate::prelude::ContactManager;
 
@@ -235,14 +241,17 @@ mod tests {
         let id = Uuid::new_v4();
 
         let contact = Contact {
-            id: id.clone(),
+            id,
             name: "Uche".to_string(),
             phone: "012345678901".to_string(),
             email: "ucheuche@gmail.com".to_string(),
             tag: "".to_string(),
             deleted: false,
-            created_at: dt_now.clone(),
-            updated_at: dt_now.clone(),
+            deleted_at: None,
+            deleted_by: None,
+            created_at: dt_now,
+            updated_at: dt_now,
+            clock: Hlc::default(),
         };
 
         let mut contacts = HashMap::new();
@@ -260,18 +269,50 @@ mod tests {
                 email: ucheuche@gmail.com\n\
                 tag: \n\
                 deleted: false\n\
+                deleted_at: \n\
+                deleted_by: \n\
                 created_at: {}\n\
                 updated_at: {}\n\
+                clock: \n\
             }}\n",
                 id.clone(),
-                dt_now.to_string(),
-                dt_now.to_string()
+                dt_now,
+                dt_now
             )
         );
 
         Ok(())
     }
 
+    #[test]
+    fn unreadable_clock_is_seeded_from_updated_at() -> Result<(), AppError> {
+        let updated_at = Utc::now();
+        let txt = format!(
+            "{{\n\
+            name: Ada Obi\n\
+            phone: 08012345678\n\
+            updated_at: {updated_at}\n\
+            clock: not-a-clock\n\
+            }}\n\
+            {{\n\
+            name: Bola Ade\n\
+            phone: 08087654321\n\
+            updated_at: {updated_at}\n\
+            clock: 0001714557600000-0000-laptop01\n\
+            }}\n"
+        );
+
+        let contacts = deserialize_contacts_from_txt_buffer(txt.as_bytes())?;
+        assert_eq!(contacts.len(), 2);
+        for contact in contacts.values() {
+            match contact.name.as_str() {
+                "Ada Obi" => assert_eq!(contact.clock, Hlc::seeded(updated_at)),
+                _ => assert_eq!(contact.clock.device, "laptop01"),
+            }
+        }
+        Ok(())
+    }
+
     #[test]
     fn check_deserialization_from_txt() -> Result<(), AppError> {
         // Testing should be ran explicitly on a single thread to avoid race condition from multiply test threads
@@ -295,11 +336,11 @@ mod tests {
             "".to_string(),
         );
 
-        let id_1 = contact1.id.clone();
-        let id_2 = contact2.id.clone();
+        let id_1 = contact1.id;
+        let id_2 = contact2.id;
 
-        storage.mem.insert(contact1.id.clone(), contact1);
-        storage.mem.insert(contact2.id.clone(), contact2);
+        storage.mem.insert(contact1.id, contact1);
+        storage.mem.insert(contact2.id, contact2);
 
         storage.save()?;
         storage.mem.clear();
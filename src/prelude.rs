@@ -1,7 +1,12 @@
 pub use crate::cli::{command, run_app};
+pub use crate::config::Config;
 pub use crate::domain::{
+    builder::ContactManagerBuilder,
+    clock::{self, Hlc},
     contact::{self, Contact},
     manager::{self, ContactManager},
+    render::{self, RenderFormat},
+    sync::{self, SyncPolicy},
 };
 pub use crate::errors::AppError;
 pub use crate::storage::{self, ContactStore, file, memory, remote::RemoteStorage};
@@ -0,0 +1,377 @@
+use super::*;
+
+use crate::domain::clock::Hlc;
+use chrono::{DateTime, Utc};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+
+/// Start of every binary book file.
+const MAGIC: &[u8; 8] = b"RLDXBIN1";
+/// Magic, schema version (u32), record count (u32) and index offset (u64).
+const HEADER_LEN: usize = 24;
+/// A contact id and the offset of its record.
+const INDEX_ENTRY_LEN: usize = 24;
+
+/// Contacts in a compact binary file, for books too large to load quickly as json.
+///
+/// The file is a header, then one length-prefixed record per contact, then an
+/// index of `(id, offset)` pairs sorted by id. All integers are little-endian.
+pub struct BinaryStorage {
+    pub medium: String,
+    pub path: String,
+}
+
+impl BinaryStorage {
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "bin".to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    /// Reads one contact through the index, without loading the rest of the book.
+    pub fn get(&self, id: &Uuid) -> Result<Option<Contact>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(None);
+        }
+
+        let mut file = File::open(&self.path)?;
+        let mut header = [0u8; HEADER_LEN];
+        file.read_exact(&mut header).map_err(|_| self.truncated())?;
+        let (version, count, index_offset) = read_header(&header, &self.path)?;
+        let index_len = (count * INDEX_ENTRY_LEN) as u64;
+        if index_offset + index_len > file.metadata()?.len() {
+            return Err(self.truncated());
+        }
+
+        let mut index = vec![0u8; index_len as usize];
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_exact(&mut index).map_err(|_| self.truncated())?;
+
+        let entries: Vec<&[u8]> = index.chunks_exact(INDEX_ENTRY_LEN).collect();
+        let Ok(found) = entries.binary_search_by(|entry| entry[..16].cmp(id.as_bytes())) else {
+            return Ok(None);
+        };
+        let offset = u64::from_le_bytes(entries[found][16..].try_into().unwrap_or_default());
+
+        let mut len = [0u8; 4];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut len).map_err(|_| self.truncated())?;
+        let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+        file.read_exact(&mut record).map_err(|_| self.truncated())?;
+
+        let mut contact = Decoder::new(&record, &self.path).contact()?;
+        schema::upgrade_contact(&mut contact, version);
+        Ok(Some(contact))
+    }
+
+    fn encode_iter(
+        &self,
+        contacts: &mut dyn Iterator<Item = &Contact>,
+    ) -> Result<Vec<u8>, AppError> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&schema::CURRENT_VERSION.to_le_bytes());
+        // Count and index offset are filled in once the records are written
+        data.extend_from_slice(&[0u8; 12]);
+
+        let mut index = Vec::new();
+        let mut record = Vec::new();
+        for contact in contacts {
+            index.push((contact.id, data.len() as u64));
+
+            record.clear();
+            encode_contact(contact, &mut record);
+            data.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            data.extend_from_slice(&record);
+        }
+
+        let index_offset = data.len() as u64;
+        index.sort_unstable_by_key(|(id, _)| *id);
+        for (id, offset) in &index {
+            data.extend_from_slice(id.as_bytes());
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        data[12..16].copy_from_slice(&(index.len() as u32).to_le_bytes());
+        data[16..24].copy_from_slice(&index_offset.to_le_bytes());
+        Ok(data)
+    }
+
+    fn truncated(&self) -> AppError {
+        AppError::Validation(format!("'{}' is truncated", self.path))
+    }
+}
+
+impl ContactStore for BinaryStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(HashMap::new());
+        }
+        self.decode(&fs::read(&self.path)?)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        self.write_file(&self.encode(contacts)?)
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn save_iter(&self, contacts: &mut dyn Iterator<Item = &Contact>) -> Result<(), AppError> {
+        self.write_file(&self.encode_iter(contacts)?)
+    }
+}
+
+impl FileStore for BinaryStorage {
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError> {
+        self.encode_iter(&mut contacts.values())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError> {
+        if data.len() < HEADER_LEN {
+            return Err(self.truncated());
+        }
+        let (version, count, index_offset) = read_header(&data[..HEADER_LEN], &self.path)?;
+        let records = data
+            .get(HEADER_LEN..index_offset as usize)
+            .ok_or_else(|| self.truncated())?;
+
+        let mut decoder = Decoder::new(records, &self.path);
+        // A damaged count can't make us reserve more than the file could hold
+        let mut contacts = HashMap::with_capacity(count.min(records.len()));
+        for _ in 0..count {
+            let len = decoder.u32()? as usize;
+            let mut contact = Decoder::new(decoder.take(len)?, &self.path).contact()?;
+            schema::upgrade_contact(&mut contact, version);
+            contacts.insert(contact.id, contact);
+        }
+        Ok(contacts)
+    }
+
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError> {
+        file::write_file(&self.path, data)
+    }
+}
+
+/// Checks the magic and schema version, and returns the version, the record
+/// count and the index offset.
+fn read_header(header: &[u8], location: &str) -> Result<(u32, usize, u64), AppError> {
+    if !header.starts_with(MAGIC) {
+        return Err(AppError::Validation(format!(
+            "'{location}' is not a binary rolodex book"
+        )));
+    }
+
+    let mut decoder = Decoder::new(&header[MAGIC.len()..], location);
+    let version = decoder.u32()?;
+    schema::check_version(version, location)?;
+    Ok((version, decoder.u32()? as usize, decoder.u64()?))
+}
+
+fn encode_contact(contact: &Contact, out: &mut Vec<u8>) {
+    out.extend_from_slice(contact.id.as_bytes());
+    for field in [&contact.name, &contact.phone, &contact.email, &contact.tag] {
+        encode_str(field, out);
+    }
+    out.push(contact.deleted as u8);
+
+    match &contact.deleted_at {
+        Some(at) => {
+            out.push(1);
+            encode_time(at, out);
+        }
+        None => out.push(0),
+    }
+    match &contact.deleted_by {
+        Some(by) => {
+            out.push(1);
+            encode_str(by, out);
+        }
+        None => out.push(0),
+    }
+
+    encode_time(&contact.created_at, out);
+    encode_time(&contact.updated_at, out);
+    out.extend_from_slice(&contact.clock.millis.to_le_bytes());
+    out.extend_from_slice(&contact.clock.counter.to_le_bytes());
+    encode_str(&contact.clock.device, out);
+}
+
+fn encode_str(value: &str, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value.as_bytes());
+}
+
+fn encode_time(time: &DateTime<Utc>, out: &mut Vec<u8>) {
+    out.extend_from_slice(&time.timestamp().to_le_bytes());
+    out.extend_from_slice(&time.timestamp_subsec_nanos().to_le_bytes());
+}
+
+/// Reads fields back in the order `encode_contact` wrote them.
+struct Decoder<'a> {
+    data: &'a [u8],
+    location: &'a str,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8], location: &'a str) -> Self {
+        Self { data, location }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AppError> {
+        if self.data.len() < len {
+            return Err(AppError::Validation(format!(
+                "'{}' is truncated",
+                self.location
+            )));
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], AppError> {
+        let mut bytes = [0u8; N];
+        bytes.copy_from_slice(self.take(N)?);
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, AppError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, AppError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, AppError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn i64(&mut self) -> Result<i64, AppError> {
+        Ok(i64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String, AppError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| {
+            AppError::Validation(format!("'{}' has a field that isn't utf-8", self.location))
+        })
+    }
+
+    fn time(&mut self) -> Result<DateTime<Utc>, AppError> {
+        let (secs, nanos) = (self.i64()?, self.u32()?);
+        DateTime::from_timestamp(secs, nanos).ok_or(AppError::Validation(format!(
+            "'{}' has an invalid timestamp",
+            self.location
+        )))
+    }
+
+    fn contact(&mut self) -> Result<Contact, AppError> {
+        Ok(Contact {
+            id: Uuid::from_bytes(self.array()?),
+            name: self.string()?,
+            phone: self.string()?,
+            email: self.string()?,
+            tag: self.string()?,
+            deleted: self.u8()? != 0,
+            deleted_at: match self.u8()? {
+                0 => None,
+                _ => Some(self.time()?),
+            },
+            deleted_by: match self.u8()? {
+                0 => None,
+                _ => Some(self.string()?),
+            },
+            created_at: self.time()?,
+            updated_at: self.time()?,
+            clock: Hlc {
+                millis: self.i64()?,
+                counter: self.u32()?,
+                device: self.string()?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contacts() -> HashMap<Uuid, Contact> {
+        let mut deleted = Contact::new(
+            "Bola Ade".to_string(),
+            "08087654321".to_string(),
+            "".to_string(),
+            "gym".to_string(),
+        );
+        deleted.mark_deleted("device-a");
+
+        [
+            Contact::new(
+                "Ada Obi".to_string(),
+                "08012345678".to_string(),
+                "ada@example.com".to_string(),
+                "work".to_string(),
+            ),
+            deleted,
+        ]
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect()
+    }
+
+    #[test]
+    fn round_trips_every_field() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = BinaryStorage::at(&dir.path().join("contacts.bin").to_string_lossy());
+        let contacts = contacts();
+        store.save(&contacts)?;
+
+        let loaded = store.load()?;
+        assert_eq!(loaded.len(), 2);
+        for (id, contact) in &contacts {
+            let read = &loaded[id];
+            assert_eq!(read, contact);
+            assert_eq!(read.deleted_at, contact.deleted_at);
+            assert_eq!(read.deleted_by, contact.deleted_by);
+            assert_eq!(read.updated_at, contact.updated_at);
+            assert_eq!(read.clock, contact.clock);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reads_single_records_through_the_index() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = BinaryStorage::at(&dir.path().join("contacts.bin").to_string_lossy());
+        let contacts = contacts();
+        store.save(&contacts)?;
+
+        for (id, contact) in &contacts {
+            assert_eq!(store.get(id)?.as_ref(), Some(contact));
+        }
+        assert!(store.get(&Uuid::new_v4())?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_damaged_files() -> Result<(), AppError> {
+        let store = BinaryStorage::at("contacts.bin");
+        let data = store.encode(&contacts())?;
+
+        assert!(store.decode(&data[..data.len() / 2]).is_err());
+        assert!(store.decode(b"not a binary book at all").is_err());
+
+        let mut newer = data.clone();
+        newer[8..12].copy_from_slice(&(schema::CURRENT_VERSION + 1).to_le_bytes());
+        assert!(store.decode(&newer).is_err());
+        Ok(())
+    }
+}
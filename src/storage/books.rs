@@ -0,0 +1,302 @@
+use super::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The book used when none is chosen. Its storage comes from `STORAGE_CHOICE`
+/// and the `*_STORAGE_PATH` env vars, so it always exists and can't be renamed or deleted.
+pub const DEFAULT_BOOK: &str = "default";
+
+const DEFAULT_BOOKS_FILE: &str = "./.instance/books.json";
+const DEFAULT_BOOKS_DIR: &str = "./.instance/books";
+
+/// Where a named address book keeps its contacts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Book {
+    pub medium: String,
+    pub path: String,
+}
+
+/// Named address books and the default choice, kept in `./.instance/books.json`
+/// (override with `BOOKS_FILE`).
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BookRegistry {
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub books: BTreeMap<String, Book>,
+}
+
+impl BookRegistry {
+    pub fn load() -> Result<Self, AppError> {
+        let path = books_file();
+        if !fs::exists(&path)? {
+            return Ok(Self::default());
+        }
+
+        let data = fs::read_to_string(&path)?;
+        if data.is_empty() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self) -> Result<(), AppError> {
+        let path = books_file();
+        create_file_parent(&path)?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Name of the book used when `--book` isn't given.
+    pub fn default_book(&self) -> &str {
+        self.default.as_deref().unwrap_or(DEFAULT_BOOK)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Book, AppError> {
+        self.books
+            .get(name)
+            .ok_or(AppError::NotFound(format!("Book '{name}'")))
+    }
+
+    /// Every book name, the default book first.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![DEFAULT_BOOK];
+        names.extend(self.books.keys().map(|name| name.as_str()));
+        names
+    }
+
+    /// Registers a new book. Without a path, file books are kept under `./.instance/books`.
+    pub fn create(
+        &mut self,
+        name: &str,
+        medium: StorageMediums,
+        path: Option<String>,
+    ) -> Result<&Book, AppError> {
+        validate_book_name(name)?;
+        if self.books.contains_key(name) {
+            return Err(AppError::Validation(format!(
+                "Book '{name}' already exists"
+            )));
+        }
+
+        let path = match medium {
+            // Without a url the remote configured in .env is used
+            StorageMediums::Remote => path.unwrap_or_default(),
+            StorageMediums::Dir => path.unwrap_or_else(|| {
+                PathBuf::from(DEFAULT_BOOKS_DIR)
+                    .join(name)
+                    .to_string_lossy()
+                    .to_string()
+            }),
+            _ if medium.is_file() => path.unwrap_or_else(|| {
+                PathBuf::from(DEFAULT_BOOKS_DIR)
+                    .join(format!("{name}.{}", medium.is_which()))
+                    .to_string_lossy()
+                    .to_string()
+            }),
+            _ => {
+                return Err(AppError::Validation(
+                    "Books can be stored as json, txt, bin, jsonl, events, dir or remote"
+                        .to_string(),
+                ));
+            }
+        };
+
+        let book = Book {
+            medium: medium.is_which().to_string(),
+            path,
+        };
+        Ok(self.books.entry(name.to_string()).or_insert(book))
+    }
+
+    /// Renames a book. Its contacts stay where they are.
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), AppError> {
+        validate_book_name(new_name)?;
+        if self.books.contains_key(new_name) {
+            return Err(AppError::Validation(format!(
+                "Book '{new_name}' already exists"
+            )));
+        }
+
+        let book = self
+            .books
+            .remove(name)
+            .ok_or(AppError::NotFound(format!("Book '{name}'")))?;
+        self.books.insert(new_name.to_string(), book);
+
+        if self.default.as_deref() == Some(name) {
+            self.default = Some(new_name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Forgets a book and returns it. Falls back to the default book if it was the default choice.
+    pub fn remove(&mut self, name: &str) -> Result<Book, AppError> {
+        if name == DEFAULT_BOOK {
+            return Err(AppError::Validation(
+                "The default book can't be deleted".to_string(),
+            ));
+        }
+
+        let book = self
+            .books
+            .remove(name)
+            .ok_or(AppError::NotFound(format!("Book '{name}'")))?;
+
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+        Ok(book)
+    }
+
+    /// Switches a book to another medium. A file book keeps its file name with the new extension.
+    pub fn set_medium(&mut self, name: &str, medium: StorageMediums) -> Result<&Book, AppError> {
+        let book = self
+            .books
+            .get_mut(name)
+            .ok_or(AppError::NotFound(format!("Book '{name}'")))?;
+
+        book.path = match medium {
+            StorageMediums::Remote => String::new(),
+            StorageMediums::Dir if book.medium == "remote" => PathBuf::from(DEFAULT_BOOKS_DIR)
+                .join(name)
+                .to_string_lossy()
+                .to_string(),
+            StorageMediums::Dir => Path::new(&book.path)
+                .with_extension("")
+                .to_string_lossy()
+                .to_string(),
+            _ if !medium.is_file() => {
+                return Err(AppError::Validation(
+                    "Books can be stored as json, txt, bin, jsonl, events, dir or remote"
+                        .to_string(),
+                ));
+            }
+            _ if book.medium == "remote" => PathBuf::from(DEFAULT_BOOKS_DIR)
+                .join(format!("{name}.{}", medium.is_which()))
+                .to_string_lossy()
+                .to_string(),
+            _ => file::sibling_path(&book.path, medium.is_which()),
+        };
+        book.medium = medium.is_which().to_string();
+        Ok(book)
+    }
+
+    pub fn set_default(&mut self, name: &str) -> Result<(), AppError> {
+        if name == DEFAULT_BOOK {
+            self.default = None;
+        } else {
+            self.get(name)?;
+            self.default = Some(name.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The book picked with `--book` (or `ROLODEX_BOOK`, see `Config::book`), else the default book setting.
+pub fn active_book(config: &Config) -> Result<String, AppError> {
+    match &config.book {
+        Some(name) => Ok(name.clone()),
+        None => Ok(BookRegistry::load()?.default_book().to_string()),
+    }
+}
+
+/// Opens the storage behind a book.
+pub fn book_storage(name: &str, config: &Config) -> Result<Box<dyn ContactStore>, AppError> {
+    if name == DEFAULT_BOOK {
+        let medium = config.storage.choice.as_str().try_into()?;
+        return parse_storage_type_env_config(Some(medium), config);
+    }
+
+    let registry = BookRegistry::load()?;
+    open_book(name, registry.get(name)?, config)
+}
+
+/// Opens the storage described by `book`, which may not be saved in the registry yet.
+pub fn open_book(
+    name: &str,
+    book: &Book,
+    config: &Config,
+) -> Result<Box<dyn ContactStore>, AppError> {
+    match book.medium.as_str().try_into()? {
+        medium @ (StorageMediums::Json
+        | StorageMediums::Txt
+        | StorageMediums::Bin
+        | StorageMediums::Jsonl
+        | StorageMediums::Events) => super::open_file(medium, &book.path, config),
+        StorageMediums::Dir => Ok(Box::new(super::directory_store(&book.path, config)?)),
+        StorageMediums::Remote => {
+            let storage = remote::RemoteStorage::from_config(config, name)?;
+            if !book.path.is_empty() {
+                storage.update_active_url_from_str(&book.path);
+            }
+            Ok(Box::new(storage))
+        }
+        StorageMediums::Csv | StorageMediums::Mem => {
+            Err(AppError::Validation("Storage not supported".to_string()))
+        }
+    }
+}
+
+fn books_file() -> String {
+    env::var("BOOKS_FILE").unwrap_or(DEFAULT_BOOKS_FILE.to_string())
+}
+
+fn validate_book_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name != DEFAULT_BOOK
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Invalid book name '{name}': use letters, digits, '-' or '_' (and not '{DEFAULT_BOOK}')"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_rename_and_remove_books() -> Result<(), AppError> {
+        let mut registry = BookRegistry::default();
+
+        let book = registry.create("work", StorageMediums::Txt, None)?;
+        assert_eq!(book.medium, "txt");
+        assert!(book.path.ends_with("work.txt"));
+
+        assert!(registry.create("work", StorageMediums::Json, None).is_err());
+        assert!(
+            registry
+                .create("no spaces", StorageMediums::Json, None)
+                .is_err()
+        );
+        assert!(
+            registry
+                .create(DEFAULT_BOOK, StorageMediums::Json, None)
+                .is_err()
+        );
+
+        registry.set_default("work")?;
+        registry.rename("work", "office")?;
+        assert_eq!(registry.default_book(), "office");
+        assert_eq!(registry.names(), vec![DEFAULT_BOOK, "office"]);
+
+        let book = registry.set_medium("office", StorageMediums::Json)?;
+        assert_eq!(book.medium, "json");
+        assert!(book.path.ends_with("work.json"));
+        assert!(registry.set_medium("office", StorageMediums::Mem).is_err());
+
+        registry.remove("office")?;
+        assert_eq!(registry.default_book(), DEFAULT_BOOK);
+        assert!(registry.remove(DEFAULT_BOOK).is_err());
+        Ok(())
+    }
+}
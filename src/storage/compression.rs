@@ -0,0 +1,220 @@
+use super::*;
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::fs::File;
+use std::io::BufRead;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// How a storage file is compressed. Reading always recognizes compressed
+/// data by its first bytes; this decides how a file is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// A file being written, compressed on the way. Call `finish` to complete it.
+pub enum FileWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Compression {
+    /// The compression named by a `.gz` or `.zst` extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Some(Self::Gzip),
+            Some("zst") => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gz"),
+            Self::Zstd => Some("zst"),
+        }
+    }
+
+    /// Recognizes compressed data by its first bytes.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, AppError> {
+        match self {
+            Self::None => Ok(data.to_vec()),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Self::Zstd => Ok(zstd::encode_all(data, 0)?),
+        }
+    }
+}
+
+impl TryFrom<&str> for Compression {
+    type Error = AppError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "none" => Ok(Self::None),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(AppError::Validation(format!(
+                "storage.compression must be none, gzip or zstd, not '{value}'"
+            ))),
+        }
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Gzip(writer) => writer.write(buf),
+            Self::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Gzip(writer) => writer.flush(),
+            Self::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+impl FileWriter {
+    /// Writes the end of the compressed stream and flushes the file.
+    pub fn finish(self) -> Result<(), AppError> {
+        match self {
+            Self::Plain(mut writer) => writer.flush()?,
+            Self::Gzip(writer) => writer.finish()?.flush()?,
+            Self::Zstd(writer) => writer.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Undoes whichever compression `data` starts with; plain data is returned as is.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let mut out = Vec::new();
+    match Compression::detect(data) {
+        Compression::None => return Ok(data.to_vec()),
+        Compression::Gzip => {
+            MultiGzDecoder::new(data).read_to_end(&mut out)?;
+        }
+        Compression::Zstd => out = zstd::decode_all(data)?,
+    }
+    Ok(out)
+}
+
+/// The extension under any compression extension: `json` for `contacts.json.gz`.
+pub fn inner_extension(path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let path = match Compression::from_path(&path.to_string_lossy()) {
+        Some(_) => Path::new(path.file_stem()?),
+        None => path,
+    };
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_string())
+}
+
+/// Opens a file for reading, decompressing it if it is compressed.
+pub fn open_reader(path: &str) -> Result<Box<dyn BufRead>, AppError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let start = reader.fill_buf()?;
+
+    Ok(match Compression::detect(start) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Reads a whole text file, decompressing it if it is compressed.
+pub fn read_to_string(path: &str) -> Result<String, AppError> {
+    let mut data = String::new();
+    open_reader(path)?.read_to_string(&mut data)?;
+    Ok(data)
+}
+
+/// Writes a whole file with `compression`, creating its directory if needed.
+pub fn write(path: &str, data: &[u8], compression: Compression) -> Result<(), AppError> {
+    let mut writer = create_writer(path, compression)?;
+    writer.write_all(data)?;
+    writer.finish()
+}
+
+/// Creates (or truncates) a file for writing with `compression`, creating its directory if needed.
+pub fn create_writer(path: &str, compression: Compression) -> Result<FileWriter, AppError> {
+    create_file_parent(path)?;
+    let file = BufWriter::new(File::create(path)?);
+
+    Ok(match compression {
+        Compression::None => FileWriter::Plain(file),
+        Compression::Gzip => FileWriter::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+        Compression::Zstd => FileWriter::Zstd(zstd::Encoder::new(file, 0)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_detects_each_compression() -> Result<(), AppError> {
+        let data = b"schema_version: 3\n".repeat(100);
+
+        for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+            let compressed = compression.compress(&data)?;
+            assert_eq!(Compression::detect(&compressed), compression);
+            assert_eq!(decompress(&compressed)?, data);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn streams_through_files() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir
+            .path()
+            .join("contacts.txt.zst")
+            .to_string_lossy()
+            .to_string();
+
+        let mut writer = create_writer(&path, Compression::from_path(&path).unwrap())?;
+        writer.write_all(b"Ada\n")?;
+        writer.finish()?;
+
+        assert_eq!(Compression::detect(&fs::read(&path)?), Compression::Zstd);
+        let mut line = String::new();
+        open_reader(&path)?.read_line(&mut line)?;
+        assert_eq!(line, "Ada\n");
+        Ok(())
+    }
+
+    #[test]
+    fn reads_extensions_under_compression() {
+        assert_eq!(inner_extension("book.json.gz").as_deref(), Some("json"));
+        assert_eq!(inner_extension("book.csv").as_deref(), Some("csv"));
+        assert_eq!(inner_extension("book.gz"), None);
+        assert_eq!(Compression::from_path("book.json"), None);
+        assert_eq!(file::sibling_path("book.json.gz", "txt"), "book.txt.gz");
+    }
+}
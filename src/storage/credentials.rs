@@ -0,0 +1,177 @@
+use super::*;
+
+use crate::config::{CredentialsConfig, data_dir};
+use std::process::Command;
+
+/// Name of the remote API key in the credential store.
+pub const API_KEY: &str = "remote.api_key";
+
+/// Name of the passphrase of encrypted books in the credential store.
+pub const PASSPHRASE_KEY: &str = "encryption.passphrase";
+
+/// Secrets the credential store may hold.
+pub const SECRET_KEYS: [&str; 2] = [API_KEY, PASSPHRASE_KEY];
+
+/// Where secrets such as the remote API key are kept, instead of plaintext `.env`.
+pub trait CredentialStore {
+    /// Returns `None` if no secret is stored under `key`.
+    fn get(&self, key: &str) -> Result<Option<String>, AppError>;
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AppError>;
+}
+
+/// Secrets in a JSON file only the current user can read (0600 on unix).
+pub struct FileCredentialStore {
+    pub path: PathBuf,
+}
+
+/// Secrets read from an external command such as `pass show rolodex/{key}`.
+/// `{key}` is replaced by the secret's name; the first line of output is the secret.
+pub struct CommandCredentialStore {
+    pub command: String,
+}
+
+impl FileCredentialStore {
+    pub fn new() -> Self {
+        Self {
+            path: data_dir().join("credentials.json"),
+        }
+    }
+
+    fn load(&self) -> Result<HashMap<String, String>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(HashMap::new());
+        }
+
+        let data = fs::read_to_string(&self.path)?;
+        if data.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        Ok(serde_json::from_str(&data)?)
+    }
+}
+
+impl Default for FileCredentialStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        Ok(self.load()?.remove(key))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), AppError> {
+        let mut secrets = self.load()?;
+        secrets.insert(key.to_string(), secret.to_string());
+
+        create_file_parent(&self.path.to_string_lossy())?;
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&self.path)?;
+
+        // The mode only applies to new files, tighten one created some other way
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+
+        file.write_all(serde_json::to_string(&secrets)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl CredentialStore for CommandCredentialStore {
+    fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        let command = self.command.replace("{key}", key);
+
+        #[cfg(unix)]
+        let output = Command::new("sh").arg("-c").arg(&command).output()?;
+        #[cfg(windows)]
+        let output = Command::new("cmd").arg("/C").arg(&command).output()?;
+
+        if !output.status.success() {
+            return Err(AppError::NotFound(format!(
+                "Secret '{key}' (`{command}` failed: {})",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let secret = stdout.lines().next().unwrap_or_default().trim();
+        Ok((!secret.is_empty()).then(|| secret.to_string()))
+    }
+
+    fn set(&self, key: &str, _secret: &str) -> Result<(), AppError> {
+        Err(AppError::Validation(format!(
+            "Secrets are read with `{}`; store '{key}' with that tool",
+            self.command
+        )))
+    }
+}
+
+/// Opens the credential store picked by `credentials.store`.
+pub fn open(config: &CredentialsConfig) -> Result<Box<dyn CredentialStore>, AppError> {
+    match config.store.as_str() {
+        "file" => Ok(Box::new(FileCredentialStore::new())),
+        "command" => {
+            let command = config.command.clone().ok_or(AppError::NotFound(
+                "credentials.command for the command credential store".to_string(),
+            ))?;
+            Ok(Box::new(CommandCredentialStore { command }))
+        }
+        other => Err(AppError::Validation(format!(
+            "credentials.store must be file or command, not '{other}'"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_round_trips_secrets_privately() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = FileCredentialStore {
+            path: dir.path().join("credentials.json"),
+        };
+
+        assert_eq!(store.get(API_KEY)?, None);
+        store.set(API_KEY, "secret")?;
+        assert_eq!(store.get(API_KEY)?.as_deref(), Some("secret"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&store.path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_store_reads_first_line_of_output() -> Result<(), AppError> {
+        let store = CommandCredentialStore {
+            command: "printf 'from-{key}\\nsecond line'".to_string(),
+        };
+        assert_eq!(store.get(API_KEY)?.as_deref(), Some("from-remote.api_key"));
+        assert!(store.set(API_KEY, "secret").is_err());
+
+        let failing = CommandCredentialStore {
+            command: "exit 1".to_string(),
+        };
+        assert!(failing.get(API_KEY).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+use super::*;
+
+use chrono::{DateTime, Utc};
+use csv::StringRecord;
+use serde::{Deserialize, Deserializer};
+
+/// The column layout of a CSV file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum CsvDialect {
+    /// rolodex's own columns (`id`, `name`, `phone`, ...), as written by `export`
+    #[default]
+    Native,
+    /// Google Contacts exports
+    Google,
+    /// Outlook and Outlook.com exports
+    Outlook,
+    /// Common column names such as `Name`, `Phone`, `E-mail` or `Group`, in any case
+    Generic,
+    /// Columns named by a mapping file, see `ColumnMapping`
+    Mapping(ColumnMapping),
+}
+
+impl CsvDialect {
+    /// Guesses the dialect of a file from its header row.
+    pub fn detect(headers: &StringRecord) -> Self {
+        let has = |column: &str| {
+            headers
+                .iter()
+                .any(|h| h.trim().eq_ignore_ascii_case(column))
+        };
+
+        if has("Phone 1 - Value") || has("E-mail 1 - Value") || has("Group Membership") {
+            Self::Google
+        } else if has("E-mail Address") || has("Mobile Phone") || has("Business Phone") {
+            Self::Outlook
+        } else if ["name", "phone", "email", "tag"]
+            .iter()
+            .all(|column| headers.iter().any(|h| h == *column))
+        {
+            Self::Native
+        } else {
+            Self::Generic
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Google => "google",
+            Self::Outlook => "outlook",
+            Self::Generic => "generic",
+            Self::Mapping(_) => "mapped",
+        }
+    }
+
+    /// Which columns hold which field. `None` for the native layout, which is
+    /// read by serde instead.
+    fn mapping(&self) -> Option<ColumnMapping> {
+        let columns = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        match self {
+            Self::Native => None,
+            Self::Google => Some(ColumnMapping {
+                name: columns(&["Name"]),
+                name_parts: columns(&[
+                    "First Name",
+                    "Given Name",
+                    "Middle Name",
+                    "Additional Name",
+                    "Last Name",
+                    "Family Name",
+                ]),
+                phone: columns(&["Phone 1 - Value", "Phone 2 - Value", "Phone 3 - Value"]),
+                email: columns(&["E-mail 1 - Value", "E-mail 2 - Value", "E-mail 3 - Value"]),
+                tag: columns(&["Labels", "Group Membership"]),
+                ..Default::default()
+            }),
+            Self::Outlook => Some(ColumnMapping {
+                name: columns(&["Display Name"]),
+                name_parts: columns(&["First Name", "Middle Name", "Last Name"]),
+                phone: columns(&[
+                    "Mobile Phone",
+                    "Primary Phone",
+                    "Home Phone",
+                    "Business Phone",
+                    "Other Phone",
+                ]),
+                email: columns(&["E-mail Address", "E-mail 2 Address", "E-mail 3 Address"]),
+                tag: columns(&["Categories"]),
+                ..Default::default()
+            }),
+            Self::Generic => Some(ColumnMapping {
+                name: columns(&["name", "full name", "display name", "contact name", "fn"]),
+                name_parts: columns(&[
+                    "first name",
+                    "given name",
+                    "middle name",
+                    "last name",
+                    "family name",
+                    "surname",
+                ]),
+                phone: columns(&[
+                    "phone",
+                    "phone number",
+                    "telephone",
+                    "tel",
+                    "mobile",
+                    "mobile phone",
+                    "cell",
+                    "home phone",
+                    "work phone",
+                ]),
+                email: columns(&["email", "e-mail", "email address", "e-mail address", "mail"]),
+                tag: columns(&["tag", "group", "category", "categories", "label", "labels"]),
+                id: columns(&["id", "uid"]),
+                created_at: columns(&["created_at", "created"]),
+                updated_at: columns(&["updated_at", "updated", "modified"]),
+            }),
+            Self::Mapping(mapping) => Some(mapping.clone()),
+        }
+    }
+
+    /// The header row written by `export`. `None` for the native layout, which
+    /// serde writes.
+    pub fn header(&self) -> Result<Option<Vec<&'static str>>, AppError> {
+        let header = match self {
+            Self::Native => return Ok(None),
+            Self::Google => vec!["Name", "Phone 1 - Value", "E-mail 1 - Value", "Labels"],
+            Self::Outlook => vec![
+                "First Name",
+                "Last Name",
+                "Mobile Phone",
+                "E-mail Address",
+                "Categories",
+            ],
+            Self::Generic => vec!["Name", "Phone", "Email", "Tag"],
+            Self::Mapping(_) => {
+                return Err(AppError::Validation(
+                    "A column mapping can only be used to read CSV files".to_string(),
+                ));
+            }
+        };
+        Ok(Some(header))
+    }
+
+    /// A contact as a row under `header()`. Not used for the native layout.
+    pub fn row(&self, contact: &Contact) -> Vec<String> {
+        let (name, phone, email, tag) = (
+            contact.name.clone(),
+            contact.phone.clone(),
+            contact.email.clone(),
+            contact.tag.clone(),
+        );
+        match self {
+            Self::Outlook => {
+                let (first, last) = match name.trim().rsplit_once(' ') {
+                    Some((first, last)) => (first.to_string(), last.to_string()),
+                    None => (name, String::new()),
+                };
+                vec![first, last, phone, email, tag]
+            }
+            _ => vec![name, phone, email, tag],
+        }
+    }
+}
+
+impl TryFrom<&str> for CsvDialect {
+    type Error = AppError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "native" => Ok(Self::Native),
+            "google" => Ok(Self::Google),
+            "outlook" => Ok(Self::Outlook),
+            "generic" => Ok(Self::Generic),
+            _ => Err(AppError::Validation(format!(
+                "CSV dialect must be native, google, outlook or generic, not '{value}'"
+            ))),
+        }
+    }
+}
+
+/// Names the CSV columns each contact field is read from, for files no built-in
+/// dialect knows. Column names match in any case, and columns missing from a
+/// file are ignored. Read from a TOML file such as:
+///
+/// ```toml
+/// name_parts = ["First", "Surname"]
+/// phone = ["Mobile", "Work phone"]
+/// email = "Mail"
+/// tag = "Team"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnMapping {
+    /// Full-name columns; the first one with a value is the name.
+    #[serde(deserialize_with = "one_or_many")]
+    pub name: Vec<String>,
+    /// Joined with spaces into the name when no `name` column has a value.
+    #[serde(deserialize_with = "one_or_many")]
+    pub name_parts: Vec<String>,
+    /// For the other fields the first column with a value is used.
+    #[serde(deserialize_with = "one_or_many")]
+    pub phone: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub email: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub tag: Vec<String>,
+    /// Ids that aren't UUIDs are replaced by new ones.
+    #[serde(deserialize_with = "one_or_many")]
+    pub id: Vec<String>,
+    /// RFC 3339 times; the current time when missing.
+    #[serde(deserialize_with = "one_or_many")]
+    pub created_at: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub updated_at: Vec<String>,
+}
+
+impl ColumnMapping {
+    pub fn from_file(path: &str) -> Result<Self, AppError> {
+        let data = fs::read_to_string(path)
+            .map_err(|_| AppError::NotFound(format!("Column mapping file '{path}'")))?;
+        let mapping: Self = toml::from_str(&data)
+            .map_err(|err| AppError::Config(format!("'{path}': {}", err.message())))?;
+
+        if mapping.name.is_empty() && mapping.name_parts.is_empty() {
+            return Err(AppError::Config(format!(
+                "'{path}' must map name or name_parts to a column"
+            )));
+        }
+        Ok(mapping)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(column) => vec![column],
+        OneOrMany::Many(columns) => columns,
+    })
+}
+
+/// Turns the rows of one CSV file into contacts, once its header is known.
+pub struct RowReader {
+    headers: StringRecord,
+    columns: Option<Columns>,
+    location: String,
+}
+
+/// A `ColumnMapping` resolved to column indexes of one file.
+struct Columns {
+    name: Vec<usize>,
+    name_parts: Vec<usize>,
+    phone: Vec<usize>,
+    email: Vec<usize>,
+    tag: Vec<usize>,
+    id: Vec<usize>,
+    created_at: Vec<usize>,
+    updated_at: Vec<usize>,
+}
+
+impl RowReader {
+    pub fn new(
+        dialect: &CsvDialect,
+        headers: &StringRecord,
+        location: &str,
+    ) -> Result<Self, AppError> {
+        let columns = match dialect.mapping() {
+            Some(mapping) => {
+                let find = |names: &[String]| -> Vec<usize> {
+                    names
+                        .iter()
+                        .filter_map(|name| {
+                            headers
+                                .iter()
+                                .position(|h| h.trim().eq_ignore_ascii_case(name.trim()))
+                        })
+                        .collect()
+                };
+                let columns = Columns {
+                    name: find(&mapping.name),
+                    name_parts: find(&mapping.name_parts),
+                    phone: find(&mapping.phone),
+                    email: find(&mapping.email),
+                    tag: find(&mapping.tag),
+                    id: find(&mapping.id),
+                    created_at: find(&mapping.created_at),
+                    updated_at: find(&mapping.updated_at),
+                };
+                if columns.name.is_empty() && columns.name_parts.is_empty() {
+                    return Err(AppError::Validation(format!(
+                        "'{location}' has no name column for the {} dialect",
+                        dialect.label()
+                    )));
+                }
+                Some(columns)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            headers: headers.clone(),
+            columns,
+            location: location.to_string(),
+        })
+    }
+
+    /// The contact in `record`. Errors name the line the record starts on.
+    pub fn read(&self, record: &StringRecord) -> Result<Contact, AppError> {
+        self.contact(record).map_err(|err| {
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            AppError::Validation(format!("'{}' line {line}: {err}", self.location))
+        })
+    }
+
+    /// The contact in `record`, or why there is none.
+    pub fn contact(&self, record: &StringRecord) -> Result<Contact, String> {
+        let Some(columns) = &self.columns else {
+            return self.read_native(record).map_err(|err| err.to_string());
+        };
+
+        let first = |indexes: &[usize]| -> String {
+            indexes
+                .iter()
+                .map(|&i| clean(record.get(i).unwrap_or_default()))
+                .find(|value| !value.is_empty())
+                .unwrap_or_default()
+        };
+
+        let mut name = first(&columns.name);
+        if name.is_empty() {
+            let parts: Vec<String> = columns
+                .name_parts
+                .iter()
+                .map(|&i| clean(record.get(i).unwrap_or_default()))
+                .filter(|part| !part.is_empty())
+                .collect();
+            name = parts.join(" ");
+        }
+        // Outlook and others list several categories in one cell
+        let tag = first(&columns.tag)
+            .split([';', ','])
+            .map(str::trim)
+            .find(|tag| !tag.is_empty())
+            .unwrap_or_default()
+            .to_string();
+
+        let phone = normalize_phone(&first(&columns.phone));
+        let mut contact = Contact::new(name, phone, first(&columns.email), tag);
+        if let Ok(id) = Uuid::parse_str(&first(&columns.id)) {
+            contact.id = id;
+        }
+        if let Some(created_at) = parse_time(&first(&columns.created_at))? {
+            contact.created_at = created_at;
+        }
+        if let Some(updated_at) = parse_time(&first(&columns.updated_at))? {
+            contact.updated_at = updated_at;
+        }
+        Ok(contact)
+    }
+
+    /// Serde reads the native layout; an empty `id` gets a new one.
+    fn read_native(&self, record: &StringRecord) -> Result<Contact, csv::Error> {
+        let empty_id = self
+            .headers
+            .iter()
+            .position(|h| h == "id")
+            .filter(|&i| record.get(i).is_some_and(|id| id.trim().is_empty()));
+
+        match empty_id {
+            Some(index) => {
+                let id = Uuid::new_v4().to_string();
+                let filled: StringRecord = record
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| if i == index { id.as_str() } else { value })
+                    .collect();
+                filled.deserialize(Some(&self.headers))
+            }
+            None => record.deserialize(Some(&self.headers)),
+        }
+    }
+}
+
+/// A cell's first value. Google keeps several in one cell (`a ::: b`) and marks
+/// system labels with `*` (`* myContacts`).
+fn clean(value: &str) -> String {
+    value
+        .split(":::")
+        .map(str::trim)
+        .find(|part| !part.is_empty() && !part.starts_with('*'))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Drops the spaces, dashes, dots and brackets other programs format phones
+/// with: `+1 (555) 123-4567` becomes `+15551234567`.
+fn normalize_phone(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, '-' | '.' | '(' | ')' | '[' | ']'))
+        .collect()
+}
+
+fn parse_time(value: &str) -> Result<Option<DateTime<Utc>>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| Some(time.with_timezone(&Utc)))
+        .map_err(|_| format!("invalid time '{value}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(data: &str, dialect: Option<CsvDialect>) -> Result<Vec<Contact>, AppError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(data.as_bytes());
+        let headers = reader.headers()?.clone();
+        let dialect = dialect.unwrap_or_else(|| CsvDialect::detect(&headers));
+        let rows = RowReader::new(&dialect, &headers, "test.csv")?;
+        reader.records().map(|record| rows.read(&record?)).collect()
+    }
+
+    #[test]
+    fn detects_and_reads_google_and_outlook_exports() -> Result<(), AppError> {
+        let google = "Name,Given Name,Family Name,Group Membership,E-mail 1 - Type,E-mail 1 - Value,Phone 1 - Type,Phone 1 - Value\n\
+            Ada Obi,Ada,Obi,* myContacts ::: Work,* Home,ada@example.com,Mobile,+1 555-123-4567 ::: 08087654321\n";
+        let contacts = read(google, None)?;
+        assert_eq!(contacts[0].name, "Ada Obi");
+        assert_eq!(contacts[0].phone, "+15551234567");
+        assert_eq!(contacts[0].email, "ada@example.com");
+        assert_eq!(contacts[0].tag, "Work");
+
+        let outlook = "First Name,Middle Name,Last Name,E-mail Address,Home Phone,Mobile Phone,Categories\n\
+            Chloé,,Ade,chloe@example.com,,(555) 987.6543,Gym;Friends\n";
+        let contacts = read(outlook, None)?;
+        assert_eq!(contacts[0].name, "Chloé Ade");
+        assert_eq!(contacts[0].phone, "5559876543");
+        assert_eq!(contacts[0].tag, "Gym");
+        Ok(())
+    }
+
+    #[test]
+    fn tolerates_missing_ids_and_timestamps() -> Result<(), AppError> {
+        let native = "id,name,phone,email,tag\n,Ada Obi,08012345678,,work\n";
+        let contacts = read(native, None)?;
+        assert_eq!(contacts[0].name, "Ada Obi");
+        assert!(!contacts[0].id.is_nil());
+
+        let mapping = CsvDialect::Mapping(toml::from_str(
+            "name_parts = [\"First\", \"Surname\"]\nphone = \"Mobile\"\nupdated_at = \"Changed\"",
+        )?);
+        let mapped = "First,Surname,Mobile,Changed\nAda,Obi,08012345678,2024-01-02T03:04:05Z\n";
+        let contacts = read(mapped, Some(mapping.clone()))?;
+        assert_eq!(contacts[0].name, "Ada Obi");
+        assert_eq!(
+            contacts[0].updated_at.to_rfc3339(),
+            "2024-01-02T03:04:05+00:00"
+        );
+
+        let err = read("First,Mobile,Changed\nAda,0801,yesterday\n", Some(mapping)).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+        Ok(())
+    }
+}
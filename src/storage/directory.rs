@@ -0,0 +1,272 @@
+use super::*;
+
+use std::cell::RefCell;
+
+/// How a directory book writes its contact files. Both kinds are read either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum RecordFormat {
+    #[default]
+    Json,
+    Vcard,
+}
+
+impl RecordFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Vcard => "vcf",
+        }
+    }
+
+    /// A contact's whole file.
+    pub fn encode(&self, contact: &Contact) -> Result<Vec<u8>, AppError> {
+        match self {
+            Self::Json => {
+                let mut data = serde_json::to_vec_pretty(contact)?;
+                data.push(b'\n');
+                Ok(data)
+            }
+            Self::Vcard => Ok(vcard::to_vcard(contact).into_bytes()),
+        }
+    }
+}
+
+impl TryFrom<&str> for RecordFormat {
+    type Error = AppError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "json" => Ok(Self::Json),
+            "vcf" => Ok(Self::Vcard),
+            _ => Err(AppError::Validation(format!(
+                "storage.dir_format must be json or vcf, not '{value}'"
+            ))),
+        }
+    }
+}
+
+/// One file per contact, `<dir>/<id>.json` or `<dir>/<id>.vcf`, so a book can be
+/// kept in version control and a single contact edited by hand. A file that
+/// can't be read is skipped (see `skipped`) instead of failing the whole book,
+/// and is never deleted by a save.
+pub struct DirectoryStorage {
+    pub medium: String,
+    pub path: String,
+    pub format: RecordFormat,
+    skipped: RefCell<Vec<String>>,
+}
+
+impl DirectoryStorage {
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "dir".to_string(),
+            path: path.to_string(),
+            format: RecordFormat::default(),
+            skipped: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn with_format(mut self, format: RecordFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The file a contact is written to.
+    pub fn record_path(&self, id: &Uuid) -> PathBuf {
+        Path::new(&self.path).join(format!("{id}.{}", self.format.extension()))
+    }
+
+    /// Every `.json` and `.vcf` file in the directory.
+    fn record_files(&self) -> Result<Vec<PathBuf>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.is_file() && record_format(&path).is_some() {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn write_record(&self, contact: &Contact) -> Result<(), AppError> {
+        let path = self.record_path(&contact.id);
+        let data = self.format.encode(contact)?;
+        // Unchanged files are left alone, so their modification time and diffs stay quiet
+        if fs::read(&path).is_ok_and(|existing| existing == data) {
+            return Ok(());
+        }
+        file::write_file(&path.to_string_lossy(), &data)
+    }
+}
+
+impl ContactStore for DirectoryStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let mut skipped = Vec::new();
+        let mut contacts = HashMap::new();
+        for path in self.record_files()? {
+            match read_record(&path) {
+                Ok(contact) => {
+                    contacts.insert(contact.id, contact);
+                }
+                Err(err) => skipped.push(format!("{}: {err}", path.display())),
+            }
+        }
+
+        *self.skipped.borrow_mut() = skipped;
+        Ok(contacts)
+    }
+
+    /// Writes the changed contacts and deletes the files of contacts no longer
+    /// in the book, including any under another name or in the other format.
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        fs::create_dir_all(&self.path)?;
+        for contact in contacts.values() {
+            self.write_record(contact)?;
+        }
+
+        for path in self.record_files()? {
+            let saved = path
+                .file_stem()
+                .and_then(|stem| Uuid::parse_str(&stem.to_string_lossy()).ok())
+                .is_some_and(|id| contacts.contains_key(&id) && path == self.record_path(&id));
+            // Unreadable files are left for the user to fix
+            if !saved && read_record(&path).is_ok() {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn upsert(&self, contact: &Contact) -> Result<(), AppError> {
+        fs::create_dir_all(&self.path)?;
+        self.write_record(contact)
+    }
+
+    fn writes_records(&self) -> bool {
+        true
+    }
+
+    fn remove(&self, id: &Uuid) -> Result<(), AppError> {
+        let mut removed = false;
+        for format in [RecordFormat::Json, RecordFormat::Vcard] {
+            let path = Path::new(&self.path).join(format!("{id}.{}", format.extension()));
+            if fs::exists(&path)? {
+                fs::remove_file(path)?;
+                removed = true;
+            }
+        }
+
+        if removed {
+            Ok(())
+        } else {
+            Err(AppError::NotFound("Contact".to_string()))
+        }
+    }
+
+    fn skipped(&self) -> Vec<String> {
+        self.skipped.borrow().clone()
+    }
+}
+
+pub fn record_format(path: &Path) -> Option<RecordFormat> {
+    match path.extension()?.to_str()? {
+        "json" => Some(RecordFormat::Json),
+        "vcf" => Some(RecordFormat::Vcard),
+        _ => None,
+    }
+}
+
+fn read_record(path: &Path) -> Result<Contact, AppError> {
+    parse_record(path, &fs::read_to_string(path)?)
+}
+
+/// Reads a contact file, in the format its extension names.
+pub fn parse_record(path: &Path, data: &str) -> Result<Contact, AppError> {
+    let mut contact: Contact = match record_format(path) {
+        Some(RecordFormat::Vcard) => {
+            let mut cards = vcard::parse_vcards(data, &path.to_string_lossy())?;
+            if cards.len() != 1 {
+                return Err(AppError::Validation(format!(
+                    "expected one card, found {}",
+                    cards.len()
+                )));
+            }
+            cards.remove(0)
+        }
+        _ => serde_json::from_str(data)?,
+    };
+    // Files carry no schema version and may be written by hand without a clock
+    schema::upgrade_contact(&mut contact, 3);
+    Ok(contact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str) -> Contact {
+        Contact::new(
+            name.to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        )
+    }
+
+    #[test]
+    fn keeps_one_file_per_contact() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = DirectoryStorage::at(&dir.path().to_string_lossy());
+
+        let (ada, bola) = (contact("Ada"), contact("Bola"));
+        store.save(&HashMap::from([
+            (ada.id, ada.clone()),
+            (bola.id, bola.clone()),
+        ]))?;
+        assert!(store.record_path(&ada.id).exists());
+
+        // Switching format rewrites every file in the new one
+        let store = store.with_format(RecordFormat::Vcard);
+        store.save(&HashMap::from([(ada.id, ada.clone())]))?;
+        let files: Vec<PathBuf> = store.record_files()?;
+        assert_eq!(files, vec![store.record_path(&ada.id)]);
+
+        store.upsert(&bola)?;
+        store.remove(&ada.id)?;
+        assert!(store.remove(&ada.id).is_err());
+        assert_eq!(store.load()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn skips_and_keeps_malformed_files() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = DirectoryStorage::at(&dir.path().to_string_lossy());
+        let ada = contact("Ada");
+        store.upsert(&ada)?;
+        let broken = dir.path().join("broken.json");
+        fs::write(&broken, "{ not json")?;
+
+        let loaded = store.load()?;
+        assert_eq!(loaded.len(), 1);
+        let skipped = store.skipped();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].contains("broken.json"), "{skipped:?}");
+
+        store.save(&loaded)?;
+        assert!(broken.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+use super::*;
+
+use super::credentials::{self, PASSPHRASE_KEY};
+use crate::config::CredentialsConfig;
+use argon2::Argon2;
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key,
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload, rand_core::RngCore},
+};
+use std::{cell::OnceCell, fs::File};
+
+/// Env var read by the `env` key source.
+pub const PASSPHRASE_ENV: &str = "ROLODEX_PASSPHRASE";
+
+/// Env var read by `encryption change-passphrase` for the new passphrase.
+pub const NEW_PASSPHRASE_ENV: &str = "ROLODEX_NEW_PASSPHRASE";
+
+/// Start of every encrypted book file, followed by the salt, the nonce and the ciphertext.
+const MAGIC: &[u8; 8] = b"RLDXENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+/// Where the passphrase of encrypted books comes from, picked with `encryption.key_source`.
+#[derive(Debug, Clone)]
+pub enum KeySource {
+    Prompt,
+    Env,
+    /// A key file, whose contents are used as the passphrase.
+    File(String),
+    Credentials(CredentialsConfig),
+}
+
+/// A json or txt store whose file is encrypted with ChaCha20-Poly1305,
+/// under a key derived from a passphrase with Argon2id.
+pub struct EncryptedStorage {
+    inner: Box<dyn FileStore>,
+    key_source: Option<KeySource>,
+    passphrase: OnceCell<String>,
+}
+
+impl KeySource {
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        match config.encryption.key_source.as_str() {
+            "prompt" => Ok(Self::Prompt),
+            "env" => Ok(Self::Env),
+            "file" => {
+                let path = config
+                    .encryption
+                    .key_file
+                    .clone()
+                    .ok_or(AppError::NotFound(
+                        "encryption.key_file for the file key source".to_string(),
+                    ))?;
+                Ok(Self::File(path))
+            }
+            "credentials" => Ok(Self::Credentials(config.credentials.clone())),
+            other => Err(AppError::Validation(format!(
+                "encryption.key_source must be prompt, env, file or credentials, not '{other}'"
+            ))),
+        }
+    }
+
+    pub fn passphrase(&self) -> Result<String, AppError> {
+        let passphrase = match self {
+            Self::Prompt => rpassword::prompt_password("Passphrase: ")?,
+            Self::Env => env::var(PASSPHRASE_ENV)
+                .map_err(|_| AppError::NotFound(format!("Passphrase in {PASSPHRASE_ENV}")))?,
+            Self::File(path) => fs::read_to_string(path)?.trim_end().to_string(),
+            Self::Credentials(config) => {
+                credentials::open(config)?
+                    .get(PASSPHRASE_KEY)?
+                    .ok_or(AppError::NotFound(format!(
+                        "Passphrase in the credential store (set it with `rolodex config set-secret {PASSPHRASE_KEY}`)"
+                    )))?
+            }
+        };
+
+        if passphrase.is_empty() {
+            return Err(AppError::Validation(
+                "Passphrase can't be empty".to_string(),
+            ));
+        }
+        Ok(passphrase)
+    }
+
+    /// A passphrase to encrypt with. Prompts twice so a typo doesn't lock the book.
+    pub fn new_passphrase(&self) -> Result<String, AppError> {
+        let passphrase = self.passphrase()?;
+
+        if matches!(self, Self::Prompt)
+            && rpassword::prompt_password("Repeat passphrase: ")? != passphrase
+        {
+            return Err(AppError::Validation("Passphrases don't match".to_string()));
+        }
+        Ok(passphrase)
+    }
+}
+
+impl EncryptedStorage {
+    /// Asks `key_source` for the passphrase the first time the file is read or written.
+    pub fn new(inner: Box<dyn FileStore>, key_source: KeySource) -> Self {
+        Self {
+            inner,
+            key_source: Some(key_source),
+            passphrase: OnceCell::new(),
+        }
+    }
+
+    pub fn with_passphrase(inner: Box<dyn FileStore>, passphrase: String) -> Self {
+        Self {
+            inner,
+            key_source: None,
+            passphrase: OnceCell::from(passphrase),
+        }
+    }
+
+    fn passphrase(&self) -> Result<&str, AppError> {
+        if self.passphrase.get().is_none() {
+            let key_source = self.key_source.as_ref().ok_or(AppError::NotFound(
+                "Passphrase of the encrypted book".to_string(),
+            ))?;
+            let _ = self.passphrase.set(key_source.passphrase()?);
+        }
+        Ok(self
+            .passphrase
+            .get()
+            .map(String::as_str)
+            .unwrap_or_default())
+    }
+}
+
+impl ContactStore for EncryptedStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let path = self.inner.get_location();
+        if !fs::exists(&path)? {
+            return Ok(HashMap::new());
+        }
+
+        let data = decrypt(&fs::read(&path)?, self.passphrase()?, &path)?;
+        self.inner.decode(&data)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        let data = encrypt(&self.inner.encode(contacts)?, self.passphrase()?)?;
+        self.inner.write_file(&data)
+    }
+
+    fn get_medium(&self) -> &str {
+        self.inner.get_medium()
+    }
+
+    fn get_location(&self) -> String {
+        self.inner.get_location()
+    }
+
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, AppError> {
+        encrypt(data, self.passphrase()?)
+    }
+
+    fn unseal(&self, data: &[u8], location: &str) -> Result<Vec<u8>, AppError> {
+        // State written before the book was encrypted is still plain
+        if !data.starts_with(MAGIC) {
+            return Ok(data.to_vec());
+        }
+        decrypt(data, self.passphrase()?, location)
+    }
+}
+
+/// Tells an encrypted book file from a plain one. Unreadable files count as plain.
+pub fn is_encrypted(path: &str) -> bool {
+    let mut start = [0u8; MAGIC.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|_| &start == MAGIC)
+}
+
+pub fn encrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, AppError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: data,
+                aad: &header,
+            },
+        )
+        .map_err(|_| AppError::Encryption("Could not encrypt the book".to_string()))?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str, location: &str) -> Result<Vec<u8>, AppError> {
+    if data.len() < HEADER_LEN || !data.starts_with(MAGIC) {
+        return Err(AppError::Encryption(format!(
+            "'{location}' is not an encrypted book"
+        )));
+    }
+
+    let (header, ciphertext) = data.split_at(HEADER_LEN);
+    let salt = &header[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = &header[MAGIC.len() + SALT_LEN..];
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(
+            nonce.into(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| AppError::Encryption(format!("Wrong passphrase, or '{location}' is damaged")))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, AppError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| AppError::Encryption(err.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_rejects_wrong_passphrase() -> Result<(), AppError> {
+        let encrypted = encrypt(b"contacts", "correct horse")?;
+        assert!(encrypted.starts_with(MAGIC));
+        assert!(!encrypted.windows(8).any(|w| w == b"contacts"));
+
+        assert_eq!(decrypt(&encrypted, "correct horse", "book")?, b"contacts");
+        assert!(decrypt(&encrypted, "wrong", "book").is_err());
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, "correct horse", "book").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_store_wraps_a_file_store() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir
+            .path()
+            .join("contacts.txt")
+            .to_string_lossy()
+            .to_string();
+        let store = EncryptedStorage::with_passphrase(
+            Box::new(file::TxtStorage::at(&path)),
+            "secret".to_string(),
+        );
+
+        let contact = Contact::new(
+            "Ada Obi".to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        );
+        store.save(&HashMap::from([(contact.id, contact.clone())]))?;
+
+        assert!(is_encrypted(&path));
+        assert!(
+            !fs::read_to_string(&path)
+                .unwrap_or_default()
+                .contains("Ada")
+        );
+        assert_eq!(store.load()?.get(&contact.id), Some(&contact));
+        Ok(())
+    }
+}
@@ -0,0 +1,652 @@
+use super::*;
+
+use crate::domain::clock::Hlc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Seek, SeekFrom};
+
+/// A checkpoint is written each time the event numbers pass a multiple of this.
+pub const CHECKPOINT_EVERY: u64 = 1000;
+
+/// How much of the end of the log is read at a time when looking for its last line.
+const TAIL_BLOCK: u64 = 4096;
+
+/// One change to one contact, as kept in the log. `seq` numbers events from 1
+/// and keeps counting across compactions, so it can serve as a sync offset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub seq: u64,
+    pub id: Uuid,
+    /// Schema the event was written in; a log can mix lines of several versions.
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+impl Event {
+    pub fn new(seq: u64, id: Uuid, change: Change) -> Self {
+        Self {
+            seq,
+            id,
+            schema_version: schema::CURRENT_VERSION,
+            change,
+        }
+    }
+}
+
+fn unversioned() -> u32 {
+    schema::UNVERSIONED_LINES
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Change {
+    /// A new contact, or a whole contact written on its own by `upsert`. Also
+    /// replaces a contact whose change can't be told as the events below, e.g.
+    /// after a sync took the other side's timestamps.
+    Created { contact: Contact },
+    /// `field` is name, phone, email or tag.
+    FieldChanged {
+        field: String,
+        value: String,
+        updated_at: DateTime<Utc>,
+        clock: Hlc,
+    },
+    Deleted {
+        deleted_at: Option<DateTime<Utc>>,
+        deleted_by: Option<String>,
+        updated_at: DateTime<Utc>,
+        clock: Hlc,
+    },
+    Restored {
+        updated_at: DateTime<Utc>,
+        clock: Hlc,
+    },
+    /// Dropped for good, e.g. purged from the trash.
+    Removed,
+}
+
+/// The contacts as of event `seq`, kept next to the log so loading only
+/// replays the events after it.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    schema_version: u32,
+    seq: u64,
+    contacts: HashMap<Uuid, Contact>,
+}
+
+/// Contacts kept as a log of changes, one json event per line, instead of a
+/// snapshot. Loading replays the log on top of the last checkpoint
+/// (`<path>.checkpoint`); saving appends the events between the stored and
+/// the given contacts.
+pub struct EventLogStorage {
+    pub medium: String,
+    pub path: String,
+}
+
+/// Just the number of an event, for reading the last one without the rest.
+#[derive(Deserialize)]
+struct Seq {
+    seq: u64,
+}
+
+/// The replayed log: the contacts, the last event and the event of the checkpoint.
+struct Replay {
+    contacts: HashMap<Uuid, Contact>,
+    last_seq: u64,
+    checkpoint_seq: u64,
+}
+
+impl EventLogStorage {
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "events".to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    pub fn checkpoint_path(&self) -> String {
+        checkpoint_path(&self.path)
+    }
+
+    /// The events after `seq`, for catching up incrementally. Fails if some of
+    /// them were already compacted into the checkpoint.
+    pub fn events_since(&self, seq: u64) -> Result<Vec<Event>, AppError> {
+        let checkpoint_seq = self.read_checkpoint()?.map_or(0, |c| c.seq);
+        if seq < checkpoint_seq {
+            return Err(AppError::Validation(format!(
+                "Events up to {checkpoint_seq} of '{}' were compacted; load the whole book instead",
+                self.path
+            )));
+        }
+
+        let mut events = self.read_events()?;
+        events.retain(|event| event.seq > seq);
+        Ok(events)
+    }
+
+    /// Folds the whole log into a new checkpoint and empties the log.
+    /// Returns the number of events folded in.
+    pub fn compact(&self) -> Result<u64, AppError> {
+        let replay = self.replay()?;
+        self.write_checkpoint(&replay.contacts, replay.last_seq)?;
+        // Any event left behind by a crash here is older than the checkpoint and skipped
+        file::write_file(&self.path, b"")?;
+        Ok(replay.last_seq - replay.checkpoint_seq)
+    }
+
+    fn replay(&self) -> Result<Replay, AppError> {
+        let (mut contacts, checkpoint_seq) = match self.read_checkpoint()? {
+            Some(checkpoint) => (checkpoint.contacts, checkpoint.seq),
+            None => (HashMap::new(), 0),
+        };
+
+        let mut last_seq = checkpoint_seq;
+        for event in self.read_events()? {
+            if event.seq <= checkpoint_seq {
+                continue;
+            }
+            last_seq = event.seq;
+            apply(&mut contacts, event);
+        }
+
+        Ok(Replay {
+            contacts,
+            last_seq,
+            checkpoint_seq,
+        })
+    }
+
+    fn read_events(&self) -> Result<Vec<Event>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(Vec::new());
+        }
+        parse_events(BufReader::new(fs::File::open(&self.path)?), &self.path)
+    }
+
+    fn read_checkpoint(&self) -> Result<Option<Checkpoint>, AppError> {
+        let path = self.checkpoint_path();
+        if !fs::exists(&path)? {
+            return Ok(None);
+        }
+
+        let mut checkpoint: Checkpoint =
+            serde_json::from_reader(BufReader::new(fs::File::open(&path)?))?;
+        schema::check_version(checkpoint.schema_version, &path)?;
+        for contact in checkpoint.contacts.values_mut() {
+            schema::upgrade_contact(contact, checkpoint.schema_version);
+        }
+        Ok(Some(checkpoint))
+    }
+
+    fn write_checkpoint(
+        &self,
+        contacts: &HashMap<Uuid, Contact>,
+        seq: u64,
+    ) -> Result<(), AppError> {
+        let checkpoint = Checkpoint {
+            schema_version: schema::CURRENT_VERSION,
+            seq,
+            contacts: contacts.clone(),
+        };
+        file::write_file(&self.checkpoint_path(), &serde_json::to_vec(&checkpoint)?)
+    }
+
+    /// The number of the last event: read off the end of the log, or from the
+    /// checkpoint when the log was emptied by a compaction.
+    fn last_seq(&self) -> Result<u64, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(self.read_checkpoint()?.map_or(0, |c| c.seq));
+        }
+
+        let mut file = fs::File::open(&self.path)?;
+        let mut end = file.seek(SeekFrom::End(0))?;
+        let mut tail = Vec::new();
+        // Reads back a block at a time until the tail holds the whole last line
+        let line = loop {
+            let start = end.saturating_sub(TAIL_BLOCK);
+            let mut block = vec![0; (end - start) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut block)?;
+            block.append(&mut tail);
+            tail = block;
+            end = start;
+
+            let trimmed = tail.trim_ascii_end();
+            if let Some(newline) = trimmed.iter().rposition(|b| *b == b'\n') {
+                break &trimmed[newline + 1..];
+            }
+            if start == 0 {
+                break trimmed;
+            }
+        };
+
+        if line.trim_ascii().is_empty() {
+            return Ok(self.read_checkpoint()?.map_or(0, |c| c.seq));
+        }
+        let last: Seq = serde_json::from_slice(line)
+            .map_err(|err| AppError::Validation(format!("'{}' last line: {err}", self.path)))?;
+        Ok(last.seq)
+    }
+
+    /// Appends `changes`, numbered after `last_seq`, and returns the number of
+    /// the last one.
+    fn append(&self, last_seq: u64, changes: Vec<(Uuid, Change)>) -> Result<u64, AppError> {
+        let mut seq = last_seq;
+        let mut lines = Vec::new();
+        for (id, change) in changes {
+            seq += 1;
+            serde_json::to_writer(&mut lines, &Event::new(seq, id, change))?;
+            lines.push(b'\n');
+        }
+        if lines.is_empty() {
+            return Ok(seq);
+        }
+
+        create_file_parent(&self.path)?;
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&lines)?;
+        Ok(seq)
+    }
+}
+
+/// Whether the events after `before` up to `after` pass a checkpoint.
+fn checkpoint_due(before: u64, after: u64) -> bool {
+    after / CHECKPOINT_EVERY > before / CHECKPOINT_EVERY
+}
+
+impl ContactStore for EventLogStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        Ok(self.replay()?.contacts)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        let replay = self.replay()?;
+
+        let mut changes = Vec::new();
+        for contact in contacts.values() {
+            changes.extend(diff(replay.contacts.get(&contact.id), contact));
+        }
+        for id in replay.contacts.keys() {
+            if !contacts.contains_key(id) {
+                changes.push((*id, Change::Removed));
+            }
+        }
+
+        let seq = self.append(replay.last_seq, changes)?;
+        if checkpoint_due(replay.last_seq, seq) {
+            self.write_checkpoint(contacts, seq)?;
+        }
+        Ok(())
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    /// Appends the contact as a `created` event after the last line of the log,
+    /// so only the write that passes a checkpoint replays the log.
+    fn upsert(&self, contact: &Contact) -> Result<(), AppError> {
+        let last_seq = self.last_seq()?;
+        let seq = self.append(last_seq, vec![(contact.id, created(contact))])?;
+        if checkpoint_due(last_seq, seq) {
+            let replay = self.replay()?;
+            self.write_checkpoint(&replay.contacts, replay.last_seq)?;
+        }
+        Ok(())
+    }
+
+    fn writes_records(&self) -> bool {
+        true
+    }
+
+    fn remove(&self, id: &Uuid) -> Result<(), AppError> {
+        let mut replay = self.replay()?;
+        if replay.contacts.remove(id).is_none() {
+            return Err(AppError::NotFound("Contact".to_string()));
+        }
+
+        let seq = self.append(replay.last_seq, vec![(*id, Change::Removed)])?;
+        if checkpoint_due(replay.last_seq, seq) {
+            self.write_checkpoint(&replay.contacts, seq)?;
+        }
+        Ok(())
+    }
+}
+
+/// An encrypted event log is written whole through these, so it is compacted
+/// to one `created` event per contact on every save and keeps no history.
+impl FileStore for EventLogStorage {
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError> {
+        let mut data = Vec::new();
+        for (seq, contact) in (1..).zip(contacts.values()) {
+            let event = Event::new(seq, contact.id, created(contact));
+            serde_json::to_writer(&mut data, &event)?;
+            data.push(b'\n');
+        }
+        Ok(data)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let mut contacts = HashMap::new();
+        for event in parse_events(data, &self.path)? {
+            apply(&mut contacts, event);
+        }
+        Ok(contacts)
+    }
+
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError> {
+        file::write_file(&self.path, data)?;
+        // The checkpoint would hold the contacts in the clear, and older than the log
+        let checkpoint = self.checkpoint_path();
+        if fs::exists(&checkpoint)? {
+            fs::remove_file(checkpoint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the checkpoint of the event log at `path` is kept.
+pub fn checkpoint_path(path: &str) -> String {
+    format!("{path}.checkpoint")
+}
+
+fn parse_events(reader: impl BufRead, location: &str) -> Result<Vec<Event>, AppError> {
+    let mut events = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut event: Event = serde_json::from_str(&line).map_err(|err| {
+            AppError::Validation(format!("'{location}' line {}: {err}", number + 1))
+        })?;
+        schema::check_version(event.schema_version, location)?;
+        if let Change::Created { contact } = &mut event.change {
+            schema::upgrade_contact(contact, event.schema_version);
+        }
+        events.push(event);
+    }
+    Ok(events)
+}
+
+fn apply(contacts: &mut HashMap<Uuid, Contact>, event: Event) {
+    if let Change::Created { contact } = event.change {
+        contacts.insert(event.id, contact);
+        return;
+    }
+    if let Change::Removed = event.change {
+        contacts.remove(&event.id);
+        return;
+    }
+    let Some(contact) = contacts.get_mut(&event.id) else {
+        return;
+    };
+
+    match event.change {
+        Change::FieldChanged {
+            field,
+            value,
+            updated_at,
+            clock,
+        } => {
+            match field.as_str() {
+                "name" => contact.name = value,
+                "phone" => contact.phone = value,
+                "email" => contact.email = value,
+                "tag" => contact.tag = value,
+                _ => {}
+            }
+            contact.updated_at = updated_at;
+            contact.clock = clock;
+        }
+        Change::Deleted {
+            deleted_at,
+            deleted_by,
+            updated_at,
+            clock,
+        } => {
+            contact.deleted = true;
+            contact.deleted_at = deleted_at;
+            contact.deleted_by = deleted_by;
+            contact.updated_at = updated_at;
+            contact.clock = clock;
+        }
+        Change::Restored { updated_at, clock } => {
+            contact.deleted = false;
+            contact.deleted_at = None;
+            contact.deleted_by = None;
+            contact.updated_at = updated_at;
+            contact.clock = clock;
+        }
+        Change::Created { .. } | Change::Removed => {}
+    }
+}
+
+/// The events turning `old` into `new`: one per changed field, then a deletion
+/// or restore. Falls back to a `created` event when those don't reproduce `new`.
+fn diff(old: Option<&Contact>, new: &Contact) -> Vec<(Uuid, Change)> {
+    let Some(old) = old else {
+        return vec![(new.id, created(new))];
+    };
+    if same_record(old, new) {
+        return Vec::new();
+    }
+
+    let mut changes = Vec::new();
+    for (field, before, after) in [
+        ("name", &old.name, &new.name),
+        ("phone", &old.phone, &new.phone),
+        ("email", &old.email, &new.email),
+        ("tag", &old.tag, &new.tag),
+    ] {
+        if before != after {
+            changes.push(Change::FieldChanged {
+                field: field.to_string(),
+                value: after.clone(),
+                updated_at: new.updated_at,
+                clock: new.clock.clone(),
+            });
+        }
+    }
+    match (old.deleted, new.deleted) {
+        (false, true) => changes.push(Change::Deleted {
+            deleted_at: new.deleted_at,
+            deleted_by: new.deleted_by.clone(),
+            updated_at: new.updated_at,
+            clock: new.clock.clone(),
+        }),
+        (true, false) => changes.push(Change::Restored {
+            updated_at: new.updated_at,
+            clock: new.clock.clone(),
+        }),
+        _ => {}
+    }
+
+    let changes: Vec<(Uuid, Change)> = changes.into_iter().map(|c| (new.id, c)).collect();
+    let mut replayed = HashMap::from([(old.id, old.clone())]);
+    for (id, change) in &changes {
+        apply(&mut replayed, Event::new(0, *id, change.clone()));
+    }
+
+    match replayed.get(&new.id) {
+        Some(contact) if same_record(contact, new) => changes,
+        _ => vec![(new.id, created(new))],
+    }
+}
+
+fn created(contact: &Contact) -> Change {
+    Change::Created {
+        contact: contact.clone(),
+    }
+}
+
+/// Every stored field is equal; `Contact`'s `==` only compares identity.
+fn same_record(a: &Contact, b: &Contact) -> bool {
+    a.id == b.id
+        && a.name == b.name
+        && a.phone == b.phone
+        && a.email == b.email
+        && a.tag == b.tag
+        && a.deleted == b.deleted
+        && a.deleted_at == b.deleted_at
+        && a.deleted_by == b.deleted_by
+        && a.created_at == b.created_at
+        && a.updated_at == b.updated_at
+        && a.clock == b.clock
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &tempfile::TempDir) -> EventLogStorage {
+        EventLogStorage::at(&dir.path().join("contacts.events").to_string_lossy())
+    }
+
+    fn contact(name: &str) -> Contact {
+        Contact::new(
+            name.to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        )
+    }
+
+    #[test]
+    fn writes_changes_as_events_and_replays_them() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = store(&dir);
+
+        let mut ada = contact("Ada");
+        let bola = contact("Bola");
+        store.save(&HashMap::from([
+            (ada.id, ada.clone()),
+            (bola.id, bola.clone()),
+        ]))?;
+
+        ada.phone = "08099999999".to_string();
+        ada.touch("device-a");
+        store.save(&HashMap::from([
+            (ada.id, ada.clone()),
+            (bola.id, bola.clone()),
+        ]))?;
+        let mut ada_deleted = ada.clone();
+        ada_deleted.mark_deleted("device-a");
+        store.save(&HashMap::from([
+            (ada.id, ada_deleted.clone()),
+            (bola.id, bola.clone()),
+        ]))?;
+        store.remove(&bola.id)?;
+        let chidi = contact("Chidi");
+        store.upsert(&chidi)?;
+
+        let kinds: Vec<String> = fs::read_to_string(&store.path)?
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["event"].to_string()
+            })
+            .collect();
+        assert_eq!(
+            kinds[2..],
+            [
+                "\"field_changed\"",
+                "\"deleted\"",
+                "\"removed\"",
+                "\"created\""
+            ]
+        );
+
+        let loaded = store.load()?;
+        assert_eq!(loaded.len(), 2);
+        assert!(same_record(&loaded[&ada.id], &ada_deleted));
+        assert!(same_record(&loaded[&chidi.id], &chidi));
+
+        // Saving the same contacts again adds nothing
+        store.save(&loaded)?;
+        assert_eq!(store.events_since(0)?.len(), 6);
+        assert_eq!(store.events_since(3)?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn compaction_folds_the_log_into_a_checkpoint() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = store(&dir);
+
+        let contacts: HashMap<Uuid, Contact> = ["Ada", "Bola", "Chidi"]
+            .into_iter()
+            .map(contact)
+            .map(|c| (c.id, c))
+            .collect();
+        store.save(&contacts)?;
+
+        assert_eq!(store.compact()?, 3);
+        assert_eq!(fs::read_to_string(&store.path)?, "");
+        assert_eq!(store.load()?.len(), 3);
+        assert!(store.events_since(1).is_err());
+
+        // Numbering carries on after the checkpoint
+        store.upsert(&contact("Dayo"))?;
+        assert_eq!(store.events_since(3)?[0].seq, 4);
+        assert_eq!(store.load()?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn reads_unversioned_lines_and_refuses_newer_ones() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = store(&dir);
+
+        let mut ada = contact("Ada");
+        ada.clock = Hlc::default();
+        let line = format!(
+            r#"{{"seq":1,"id":"{}","event":"created","contact":{}}}"#,
+            ada.id,
+            serde_json::to_string(&ada)?
+        );
+        fs::write(&store.path, format!("{line}\n"))?;
+        assert_eq!(store.load()?[&ada.id].clock, Hlc::seeded(ada.updated_at));
+
+        store.upsert(&contact("Bola"))?;
+        let newer = fs::read_to_string(&store.path)?.replace(
+            &format!(r#""schema_version":{}"#, schema::CURRENT_VERSION),
+            &format!(r#""schema_version":{}"#, schema::CURRENT_VERSION + 1),
+        );
+        fs::write(&store.path, newer)?;
+        let err = store.load().unwrap_err().to_string();
+        assert!(err.contains("Upgrade rolodex"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn upserts_number_events_from_the_end_of_the_log() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = store(&dir);
+
+        // A last line longer than one block of the tail
+        let mut long = contact("Ada");
+        long.tag = "x".repeat(TAIL_BLOCK as usize * 2);
+        store.upsert(&long)?;
+        store.upsert(&contact("Bola"))?;
+        store.upsert(&long)?;
+        assert_eq!(store.last_seq()?, 3);
+
+        for n in 3..CHECKPOINT_EVERY {
+            store.upsert(&contact(&format!("Contact {n}")))?;
+        }
+        let checkpoint = store.read_checkpoint()?.expect("a checkpoint");
+        assert_eq!(checkpoint.seq, CHECKPOINT_EVERY);
+        assert_eq!(checkpoint.contacts.len(), CHECKPOINT_EVERY as usize - 1);
+        assert_eq!(store.load()?[&long.id].tag, long.tag);
+        Ok(())
+    }
+}
@@ -1,39 +1,108 @@
 use super::*;
 
-use csv::{Reader, Writer};
-use serde_json::Value;
+use compression::Compression;
+use csv::{ReaderBuilder, StringRecord, Writer};
+use csv_dialect::{CsvDialect, RowReader};
+use import::{SourceFormat, SourceRecord, SourceRecords};
+use std::io::{BufRead, Cursor};
 
 pub struct JsonStorage {
     pub medium: String,
     pub path: String,
+    /// How the file is written. Compressed files are always read.
+    pub compression: Compression,
 }
 
 impl JsonStorage {
     pub fn new() -> Result<Self, AppError> {
-        Ok(Self {
+        Ok(Self::at(&Config::load()?.storage.json_path))
+    }
+
+    /// A `.gz` or `.zst` extension compresses the file.
+    pub fn at(path: &str) -> Self {
+        Self {
             medium: "json".to_string(),
-            path: env::var("JSON_STORAGE_PATH").unwrap_or("./.instance/contacts.json".to_string()),
-        })
+            path: path.to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
     }
 }
 
 pub struct TxtStorage {
     pub medium: String,
     pub path: String,
+    /// How the file is written. Compressed files are always read.
+    pub compression: Compression,
 }
 
 impl TxtStorage {
     pub fn new() -> Result<Self, AppError> {
-        Ok(Self {
+        Ok(Self::at(&Config::load()?.storage.txt_path))
+    }
+
+    /// A `.gz` or `.zst` extension compresses the file.
+    pub fn at(path: &str) -> Self {
+        Self {
             medium: "txt".to_string(),
-            path: env::var("TXT_STORAGE_PATH").unwrap_or("./.instance/contacts.txt".to_string()),
-        })
+            path: path.to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+}
+
+/// One contact per line, in the json contact format, below a schema version
+/// line. Adding or changing a contact appends a line; `save` rewrites the file with only the latest line
+/// of each contact.
+pub struct JsonlStorage {
+    pub medium: String,
+    pub path: String,
+    /// How the file is written. Compressed files are rewritten on every change instead of appended to.
+    pub compression: Compression,
+}
+
+impl JsonlStorage {
+    /// A `.gz` or `.zst` extension compresses the file.
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "jsonl".to_string(),
+            path: path.to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
+        }
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// The first line of the file, or `None` if it is missing or empty.
+    fn first_line(&self) -> Result<Option<String>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(None);
+        }
+        Ok(compression::open_reader(&self.path)?
+            .lines()
+            .next()
+            .transpose()?)
     }
 }
 
 pub struct CsvStorage {
     pub medium: String,
     pub path: String,
+    pub compression: Compression,
+    /// Column layout; detected from the header when reading, native when writing, if not set.
+    pub dialect: Option<CsvDialect>,
 }
 
 impl CsvStorage {
@@ -41,7 +110,8 @@ impl CsvStorage {
         let mut path = path;
         let mut file_path = PathBuf::from(path);
 
-        if file_path.is_dir() || file_path.extension().is_some_and(|ext| ext != "csv") {
+        if file_path.is_dir() || compression::inner_extension(path).is_some_and(|ext| ext != "csv")
+        {
             if file_path.is_dir() {
                 file_path = file_path.join("exported.csv");
                 path = file_path.to_str().unwrap();
@@ -54,13 +124,20 @@ impl CsvStorage {
 
         Ok(Self {
             medium: "csv".to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
             path: if !(path.is_empty()) {
                 path.to_string()
             } else {
                 ("./csv/contacts.csv").to_string()
             },
+            dialect: None,
         })
     }
+
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = Some(dialect);
+        self
+    }
 }
 
 impl ContactStore for JsonStorage {
@@ -68,133 +145,286 @@ impl ContactStore for JsonStorage {
         if !fs::exists(Path::new(&self.path))? {
             return Ok(HashMap::new());
         }
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .truncate(false)
-            .create(true)
-            .open(&self.path)?;
 
-        let mut data = String::new();
-        file.read_to_string(&mut data)?;
+        let data = fs::read(&self.path)?;
+        self.decode(&data)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        // user serde to serialize json data
+        self.write_file(&self.encode(contacts)?)
+    }
 
-        // serde_json will give an error if data is empty
-        if data.is_empty() {
-            return Ok(HashMap::new());
-        }
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
 
-        let value: Value = serde_json::from_str(&data)?;
-
-        // New Format: Contacts are now stored in HashMap.
-        // Try if new format has been effected
-        if value.is_object() {
-            let contacts: HashMap<Uuid, Contact> = serde_json::from_value(value)?;
-            Ok(contacts)
-        } else if value.is_array() {
-            // Old Format: Contacts were stored in Vec
-            let contacts: Vec<Contact> = serde_json::from_value(value)?;
-
-            // Convert Vec to HashMap for new feature backward compatibility
-            let mapped_contacts = contacts
-                .into_iter()
-                .map(|cont| (cont.id, cont))
-                .collect::<HashMap<Uuid, Contact>>();
-            Ok(mapped_contacts)
-        } else {
-            Err(AppError::Validation(
-                "Invalid JSON structure: expected object or array".to_string(),
-            ))
+    /// The file is read whole, so only validation can reject a contact. Contacts
+    /// are named by their name, as json has no useful line numbers.
+    fn source_records(&self) -> Result<Option<SourceRecords>, AppError> {
+        let mut contacts: Vec<Contact> = self.load()?.into_values().collect();
+        contacts.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+        let mut records = Vec::new();
+        for contact in contacts {
+            records.push(SourceRecord {
+                position: match contact.name.as_str() {
+                    "" => format!("contact {}", contact.id),
+                    name => format!("contact '{name}'"),
+                },
+                raw: serde_json::to_string_pretty(&contact)?,
+                contact: Ok(contact),
+            });
         }
+        Ok(Some(SourceRecords {
+            format: SourceFormat::Json,
+            header: None,
+            records,
+        }))
+    }
+}
+
+impl ContactStore for TxtStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        load_txt_contacts(&self.path)
     }
 
     fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
-        let path = Path::new(&self.path);
-        if !path.exists() {
-            create_file_parent(&self.path)?;
-            // let _file = OpenOptions::new()
-            //     .write(true)
-            //     .create(true)
-            //     .truncate(true)
-            //     .open(path)?;
-        }
+        self.save_iter(&mut contacts.values())
+    }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
 
-        // user serde to serialize json data
-        let json_contact = serde_json::to_string(&contacts)?;
-        file.write_all(json_contact.as_bytes())?;
-
-        let txt_path =
-            env::var("TXT_STORAGE_PATH").unwrap_or("./.instance/contacts.txt".to_string());
-        let txt_path = Path::new(&txt_path);
-        if fs::exists(txt_path)? {
-            fs::remove_file(txt_path)?;
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn save_iter(&self, contacts: &mut dyn Iterator<Item = &Contact>) -> Result<(), AppError> {
+        let mut writer = compression::create_writer(&self.path, self.compression)?;
+        writer.write_all(schema::txt_header().as_bytes())?;
+
+        // use our helper to serialize data for txt file, one contact at a time
+        for contact in contacts {
+            writer.write_all(helper::serialize_contact(contact).as_bytes())?;
         }
+        writer.finish()
+    }
+}
 
-        Ok(())
+impl FileStore for JsonStorage {
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError> {
+        self.compression
+            .compress(schema::to_json(contacts)?.as_bytes())
     }
 
-    fn get_medium(&self) -> &str {
-        &self.medium
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let data = compression::decompress(data)?;
+        let data = String::from_utf8(data)
+            .map_err(|_| AppError::Validation(format!("'{}' is not a json file", self.path)))?;
+        schema::read_json(&data, &self.path)
+    }
+
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError> {
+        write_file(&self.path, data)
     }
 }
 
-impl ContactStore for TxtStorage {
+impl FileStore for TxtStorage {
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError> {
+        let mut data = schema::txt_header();
+        data.push_str(&helper::serialize_contacts(contacts));
+        self.compression.compress(data.as_bytes())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError> {
+        read_txt(compression::decompress(data)?.as_slice(), &self.path)
+    }
+
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError> {
+        write_file(&self.path, data)
+    }
+}
+
+impl ContactStore for JsonlStorage {
     fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
-        if !fs::exists(Path::new(&self.path))? {
-            return Ok(HashMap::new());
+        // A later line of the same contact supersedes the earlier ones
+        self.load_iter()?
+            .map(|contact| contact.map(|c| (c.id, c)))
+            .collect()
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        self.save_iter(&mut contacts.values())
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    /// Yields every line as read, so a contact changed since the last save comes
+    /// once per version, the latest last. Collecting by id gives what `load` does.
+    fn load_iter(&self) -> Result<ContactIter<'_>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(Box::new(std::iter::empty()));
         }
-        // Read text fom file
-        // Using OpenOptions to open file if already exist
-        // Or create one
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .truncate(false)
-            .create(true)
-            .open(&self.path)?;
 
-        let reader = BufReader::new(file);
-        let contacts = helper::deserialize_contacts_from_txt_buffer(reader)?;
-        Ok(contacts)
+        let mut lines = compression::open_reader(&self.path)?
+            .lines()
+            .enumerate()
+            .peekable();
+        let header = match lines.peek() {
+            Some((_, Ok(line))) => schema::parse_jsonl_header(line),
+            _ => None,
+        };
+        let version = match header {
+            Some(version) => {
+                lines.next();
+                version?
+            }
+            None => schema::UNVERSIONED_LINES,
+        };
+        schema::check_version(version, &self.path)?;
+
+        let location = self.path.clone();
+        Ok(Box::new(
+            lines
+                .filter(|(_, line)| !line.as_ref().is_ok_and(|l| l.trim().is_empty()))
+                .map(move |(number, line)| {
+                    parse_jsonl_line(&line?, version, number + 1, &location)
+                }),
+        ))
     }
 
-    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
-        let path = Path::new(&self.path);
-        if !path.exists() {
-            create_file_parent(&self.path)?;
-            // let _file = OpenOptions::new()
-            //     .write(true)
-            //     .create(true)
-            //     .truncate(true)
-            //     .open(path)?;
+    fn save_iter(&self, contacts: &mut dyn Iterator<Item = &Contact>) -> Result<(), AppError> {
+        let mut writer = compression::create_writer(&self.path, self.compression)?;
+        writer.write_all(schema::jsonl_header().as_bytes())?;
+        for contact in contacts {
+            serde_json::to_writer(&mut writer, contact)?;
+            writer.write_all(b"\n")?;
         }
+        writer.finish()
+    }
 
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-
-        // use our helper to serialize data for txt file
-        let data = helper::serialize_contacts(contacts);
-        file.write_all(data.as_bytes())?;
-
-        let json_path =
-            env::var("JSON_STORAGE_PATH").unwrap_or("./.instance/contacts.json".to_string());
-        let json_path = Path::new(&json_path);
-        if fs::exists(json_path)? {
-            fs::remove_file(json_path)?;
+    fn upsert(&self, contact: &Contact) -> Result<(), AppError> {
+        if !self.writes_records() {
+            let mut contacts = self.load()?;
+            contacts.insert(contact.id, contact.clone());
+            return self.save(&contacts);
         }
+
+        // Only a file with a current header can take the line as it is
+        let mut line = match self
+            .first_line()?
+            .as_deref()
+            .map(schema::parse_jsonl_header)
+        {
+            None => schema::jsonl_header().into_bytes(),
+            Some(Some(version)) => {
+                schema::check_version(version?, &self.path)?;
+                Vec::new()
+            }
+            Some(None) => {
+                let mut contacts = self.load()?;
+                contacts.insert(contact.id, contact.clone());
+                return self.save(&contacts);
+            }
+        };
+
+        create_file_parent(&self.path)?;
+        serde_json::to_writer(&mut line, contact)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)?;
         Ok(())
     }
 
-    fn get_medium(&self) -> &str {
-        &self.medium
+    fn writes_records(&self) -> bool {
+        self.compression == Compression::None
+    }
+
+    fn source_records(&self) -> Result<Option<SourceRecords>, AppError> {
+        let mut records = Vec::new();
+        let mut header = None;
+        for (number, line) in compression::open_reader(&self.path)?.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if number == 0
+                && let Some(version) = schema::parse_jsonl_header(&line)
+            {
+                schema::check_version(version?, &self.path)?;
+                header = Some(line + "\n");
+                continue;
+            }
+            records.push(SourceRecord {
+                position: format!("line {}", number + 1),
+                contact: serde_json::from_str(&line).map_err(|err| err.to_string()),
+                raw: line + "\n",
+            });
+        }
+        Ok(Some(SourceRecords {
+            format: SourceFormat::Jsonl,
+            header,
+            records,
+        }))
+    }
+}
+
+impl FileStore for JsonlStorage {
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError> {
+        let mut data = schema::jsonl_header().into_bytes();
+        for contact in contacts.values() {
+            serde_json::to_writer(&mut data, contact)?;
+            data.push(b'\n');
+        }
+        self.compression.compress(&data)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let data = compression::decompress(data)?;
+        let data = String::from_utf8(data)
+            .map_err(|_| AppError::Validation(format!("'{}' is not a jsonl file", self.path)))?;
+
+        let mut lines = data.lines().enumerate().peekable();
+        let version = match lines
+            .peek()
+            .and_then(|(_, line)| schema::parse_jsonl_header(line))
+        {
+            Some(version) => {
+                lines.next();
+                version?
+            }
+            None => schema::UNVERSIONED_LINES,
+        };
+        schema::check_version(version, &self.path)?;
+
+        let mut contacts = HashMap::new();
+        for (number, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let contact = parse_jsonl_line(line, version, number + 1, &self.path)?;
+            contacts.insert(contact.id, contact);
+        }
+        Ok(contacts)
+    }
+
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError> {
+        write_file(&self.path, data)
     }
 }
 
@@ -203,49 +433,129 @@ impl ContactStore for CsvStorage {
         &self.medium
     }
 
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
     fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let mut contacts: HashMap<Uuid, Contact> = HashMap::new();
+
+        for result in self.load_iter()? {
+            let record = result?;
+            contacts.insert(record.id, record);
+        }
+
+        Ok(contacts)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        self.save_iter(&mut contacts.values())
+    }
+
+    fn load_iter(&self) -> Result<ContactIter<'_>, AppError> {
         let file_path: PathBuf = PathBuf::from(&self.path);
 
         if !file_path.exists() {
             return Err(AppError::NotFound("CSV file".to_string()));
         }
 
-        if file_path.extension().is_some_and(|ext| ext != "csv") {
+        if compression::inner_extension(&self.path).is_some_and(|ext| ext != "csv") {
             return Err(AppError::Validation("File not .csv".to_string()));
         }
 
-        let mut reader = Reader::from_path(&file_path)?;
-
-        let mut contacts: HashMap<Uuid, Contact> = HashMap::new();
+        // Exports from other programs don't always pad short rows
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(compression::open_reader(&self.path)?);
+        let headers = reader.headers()?.clone();
+        let dialect = match &self.dialect {
+            Some(dialect) => dialect.clone(),
+            None => CsvDialect::detect(&headers),
+        };
+        let rows = RowReader::new(&dialect, &headers, &self.path)?;
+
+        Ok(Box::new(
+            reader.into_records().map(move |record| rows.read(&record?)),
+        ))
+    }
 
-        for result in reader.deserialize() {
-            let record: Contact = result?;
-            contacts.insert(record.id, record);
+    /// Rows are read as bytes, so even a row that isn't valid UTF-8 can be
+    /// rejected on its own and copied to the reject file.
+    fn source_records(&self) -> Result<Option<SourceRecords>, AppError> {
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(compression::open_reader(&self.path)?);
+        let headers = reader.headers()?.clone();
+        let dialect = match &self.dialect {
+            Some(dialect) => dialect.clone(),
+            None => CsvDialect::detect(&headers),
+        };
+        let rows = RowReader::new(&dialect, &headers, &self.path)?;
+
+        let mut records = Vec::new();
+        for record in reader.byte_records() {
+            let record = record?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let contact = match StringRecord::from_byte_record(record.clone()) {
+                Ok(record) => rows.contact(&record),
+                Err(_) => Err("not valid UTF-8".to_string()),
+            };
+            records.push(SourceRecord {
+                position: format!("line {line}"),
+                raw: csv_row(record.iter())?,
+                contact,
+            });
         }
 
-        Ok(contacts)
+        Ok(Some(SourceRecords {
+            format: SourceFormat::Csv,
+            header: Some(csv_row(headers.iter().map(str::as_bytes))?),
+            records,
+        }))
     }
 
-    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
-        let file_path = PathBuf::from(&self.path);
-
-        if !file_path.exists() {
-            let _file = fs::OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(&file_path)?;
+    fn save_iter(&self, contacts: &mut dyn Iterator<Item = &Contact>) -> Result<(), AppError> {
+        let mut writer =
+            Writer::from_writer(compression::create_writer(&self.path, self.compression)?);
+
+        let dialect = self.dialect.clone().unwrap_or_default();
+        match dialect.header()? {
+            Some(header) => {
+                writer.write_record(header)?;
+                for contact in contacts {
+                    writer.write_record(dialect.row(contact))?;
+                }
+            }
+            None => {
+                for contact in contacts {
+                    writer.serialize(contact)?;
+                }
+            }
         }
 
-        let mut writer = Writer::from_path(&file_path)?;
+        writer
+            .into_inner()
+            .map_err(|err| AppError::Io(err.into_error()))?
+            .finish()
+    }
+}
 
-        for contact in contacts.values() {
-            writer.serialize(contact)?;
+/// The same file name with another extension, where a book switched to
+/// another medium left its contacts. A compression extension is kept:
+/// `contacts.json.gz` has `contacts.txt.gz` as its txt sibling.
+pub fn sibling_path(path: &str, extension: &str) -> String {
+    match Compression::from_path(path).and_then(|c| c.extension()) {
+        Some(compressed) => {
+            let inner = Path::new(path).with_extension("");
+            format!(
+                "{}.{compressed}",
+                inner.with_extension(extension).to_string_lossy()
+            )
         }
-
-        writer.flush()?;
-
-        Ok(())
+        None => Path::new(path)
+            .with_extension(extension)
+            .to_string_lossy()
+            .to_string(),
     }
 }
 
@@ -253,59 +563,54 @@ pub fn load_txt_contacts(path: &str) -> Result<HashMap<Uuid, Contact>, AppError>
     if !fs::exists(Path::new(path))? {
         return Ok(HashMap::new());
     }
-    // Read text fom file
-    // Using OpenOptions to open file if already exist
-    // Or create one
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .truncate(false)
-        .create(true)
-        .open(path)?;
-    let reader = BufReader::new(file);
-    let contacts = helper::deserialize_contacts_from_txt_buffer(reader)?;
-    Ok(contacts)
+    read_txt(compression::open_reader(path)?, path)
 }
 
-pub fn load_json_contacts(path: &str) -> Result<HashMap<Uuid, Contact>, AppError> {
-    if !fs::exists(Path::new(path))? {
-        return Ok(HashMap::new());
-    }
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .truncate(false)
-        .create(true)
-        .open(path)?;
+/// Parses txt contacts of any known schema version.
+fn read_txt(mut reader: impl BufRead, location: &str) -> Result<HashMap<Uuid, Contact>, AppError> {
+    let mut first_line = String::new();
+    reader.read_line(&mut first_line)?;
 
-    let mut data = String::new();
-    file.read_to_string(&mut data)?;
+    // Files without a header are from before schema versions (version 1)
+    let (version, first_line) = match schema::parse_txt_header(&first_line) {
+        Some(version) => (version?, String::new()),
+        None => (1, first_line),
+    };
+    schema::check_version(version, location)?;
 
-    // serde_json will give an error if data is empty
-    if data.is_empty() {
-        return Ok(HashMap::new());
-    }
+    // The txt parser fills in whatever older versions lack
+    helper::deserialize_contacts_from_txt_buffer(Cursor::new(first_line).chain(reader))
+}
 
-    let value: Value = serde_json::from_str(&data)?;
+/// One csv row, quoted as needed, with its line ending.
+fn csv_row<'a>(fields: impl Iterator<Item = &'a [u8]>) -> Result<String, AppError> {
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(fields)?;
+    let data = writer
+        .into_inner()
+        .map_err(|err| AppError::Io(err.into_error()))?;
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
 
-    // New Format: Contacts are now stored in HashMap.
-    // Try if new format has been effected
-    if value.is_object() {
-        let contacts: HashMap<Uuid, Contact> = serde_json::from_value(value)?;
-        Ok(contacts)
-    } else if value.is_array() {
-        // Old Format: Contacts were stored in Vec
-        let contacts: Vec<Contact> = serde_json::from_value(value)?;
-
-        // Convert Vec to HashMap for new feature backward compatibility
-        let mapped_contacts = contacts
-            .into_iter()
-            .map(|cont| (cont.id, cont))
-            .collect::<HashMap<Uuid, Contact>>();
-        Ok(mapped_contacts)
-    } else {
-        Err(AppError::Validation(
-            "Invalid JSON structure: expected object or array".to_string(),
-        ))
-    }
+fn parse_jsonl_line(
+    line: &str,
+    version: u32,
+    number: usize,
+    location: &str,
+) -> Result<Contact, AppError> {
+    let mut contact: Contact = serde_json::from_str(line)
+        .map_err(|err| AppError::Validation(format!("'{location}' line {number}: {err}")))?;
+    schema::upgrade_contact(&mut contact, version);
+    Ok(contact)
+}
+
+/// Writes a store's whole file, creating its directory if needed.
+pub fn write_file(path: &str, data: &[u8]) -> Result<(), AppError> {
+    create_file_parent(path)?;
+    fs::write(path, data)?;
+    Ok(())
+}
+
+pub fn load_json_contacts(path: &str) -> Result<HashMap<Uuid, Contact>, AppError> {
+    JsonStorage::at(path).load()
 }
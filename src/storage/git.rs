@@ -0,0 +1,436 @@
+use super::*;
+
+use super::directory::{self, RecordFormat};
+use chrono::{DateTime, Utc};
+use git2::{
+    Commit, IndexAddOption, ObjectType, Oid, PushOptions, RemoteCallbacks, Repository, Signature,
+    Sort, Tree,
+};
+use std::cell::RefCell;
+
+/// Branch of the shared bare repository that books are synced through.
+pub const BRANCH: &str = "main";
+
+/// Where the shared branch is fetched to, and where a sync commit waits to be pushed.
+const FETCHED_REF: &str = "refs/rolodex/remote";
+const OUTGOING_REF: &str = "refs/rolodex/outgoing";
+
+/// The local git repository keeping a book's files: the book directory itself
+/// for `dir` books, else the directory holding the book file. Only the book's
+/// own files are committed.
+pub struct GitBook {
+    repo: Repository,
+    paths: Vec<String>,
+}
+
+/// One commit, as shown by `rolodex log`.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub author: String,
+    pub message: String,
+}
+
+impl GitBook {
+    /// The repository of `store`, which must already exist.
+    pub fn open(store: &dyn ContactStore) -> Result<Self, AppError> {
+        let (workdir, paths) = book_paths(store.get_medium(), &store.get_location())?;
+        let repo = Repository::open(&workdir).map_err(|_| {
+            AppError::NotFound(format!("Git repository in '{}'", workdir.display()))
+        })?;
+        Ok(Self { repo, paths })
+    }
+
+    /// The repository of `store`, created on first use.
+    pub fn init(store: &dyn ContactStore) -> Result<Self, AppError> {
+        let (workdir, paths) = book_paths(store.get_medium(), &store.get_location())?;
+        fs::create_dir_all(&workdir)?;
+        let repo = match Repository::open(&workdir) {
+            Ok(repo) => repo,
+            Err(_) => Repository::init(&workdir)?,
+        };
+        Ok(Self { repo, paths })
+    }
+
+    /// Also commits the files of the `medium` book at `location`, such as the
+    /// file a book was migrated from, when they are in the same work tree.
+    pub fn including(mut self, medium: &str, location: &str) -> Result<Self, AppError> {
+        let (workdir, paths) = book_paths(medium, location)?;
+        let same_tree = match (fs::canonicalize(workdir), self.repo.workdir()) {
+            (Ok(workdir), Some(own)) => fs::canonicalize(own).is_ok_and(|own| own == workdir),
+            _ => false,
+        };
+        if same_tree {
+            self.paths.extend(paths);
+        }
+        Ok(self)
+    }
+
+    /// Commits the book's files as they are on disk. Returns the short id of the
+    /// commit, or `None` when nothing changed since the last one.
+    pub fn commit(&self, message: &str) -> Result<Option<String>, AppError> {
+        let mut index = self.repo.index()?;
+        index.add_all(&self.paths, IndexAddOption::DEFAULT, None)?;
+        // Picks up files that were deleted
+        index.update_all(&self.paths, None)?;
+        index.write()?;
+
+        let tree = self.repo.find_tree(index.write_tree()?)?;
+        let parent = self.head()?;
+        if parent.as_ref().is_some_and(|p| p.tree_id() == tree.id()) {
+            return Ok(None);
+        }
+
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let signature = self.signature()?;
+        let id = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )?;
+        Ok(Some(short_id(id)))
+    }
+
+    /// The latest `limit` commits, newest first.
+    pub fn log(&self, limit: usize) -> Result<Vec<LogEntry>, AppError> {
+        if self.head()?.is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut walk = self.repo.revwalk()?;
+        walk.push_head()?;
+        walk.set_sorting(Sort::TIME)?;
+
+        let mut entries = Vec::new();
+        for id in walk.take(limit) {
+            let commit = self.repo.find_commit(id?)?;
+            entries.push(LogEntry {
+                id: short_id(commit.id()),
+                time: DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_default(),
+                author: commit.author().name().unwrap_or_default().to_string(),
+                message: commit.summary().unwrap_or_default().to_string(),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn head(&self) -> Result<Option<Commit<'_>>, AppError> {
+        match self.repo.head() {
+            Ok(head) => Ok(Some(head.peel_to_commit()?)),
+            // A new repository has no commit yet
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The user's git identity, else a generic one.
+    fn signature(&self) -> Result<Signature<'static>, AppError> {
+        match self.repo.signature() {
+            Ok(signature) => Ok(signature),
+            Err(_) => Ok(Signature::now("rolodex", "rolodex@localhost")?),
+        }
+    }
+}
+
+/// How a book's contacts are laid out in a commit.
+enum Layout {
+    /// The book file, encoded by its store.
+    File {
+        name: String,
+        store: Box<dyn FileStore>,
+    },
+    /// One file per contact at the top of the tree.
+    Dir(RecordFormat),
+}
+
+/// A book shared through a bare git repository, e.g. on a shared filesystem.
+/// `load` fetches the shared branch and reads the book from it; `save` commits
+/// the contacts on top of it, with the local history as a second parent, and
+/// pushes. Merging goes through the sync policy like any other storage.
+pub struct GitRemoteStorage {
+    url: String,
+    book: GitBook,
+    layout: Layout,
+    /// The shared branch as last fetched, so a save builds on what was loaded.
+    fetched: RefCell<Option<Oid>>,
+}
+
+impl GitRemoteStorage {
+    /// Shares the book kept in `local` through the bare repository at `url`.
+    pub fn new(url: &str, local: &dyn ContactStore, config: &Config) -> Result<Self, AppError> {
+        let location = local.get_location();
+        let medium: StorageMediums = local.get_medium().try_into()?;
+        let layout = match medium {
+            StorageMediums::Dir => Layout::Dir(config.storage.dir_format.as_str().try_into()?),
+            _ if encryption::is_encrypted(&location) => {
+                return Err(AppError::Validation(
+                    "Encrypted books can't be synced through git".to_string(),
+                ));
+            }
+            _ => Layout::File {
+                name: file_name(&location)?,
+                store: file_store(medium, &location, config)?,
+            },
+        };
+
+        Ok(Self {
+            url: url.to_string(),
+            book: GitBook::init(local)?,
+            layout,
+            fetched: RefCell::new(None),
+        })
+    }
+
+    /// Fetches the shared branch; `None` until something was pushed to it.
+    fn fetch(&self) -> Result<Option<Commit<'_>>, AppError> {
+        let repo = &self.book.repo;
+        if let Ok(mut stale) = repo.find_reference(FETCHED_REF) {
+            stale.delete()?;
+        }
+        repo.remote_anonymous(&self.url)?.fetch(
+            &[format!("+refs/heads/{BRANCH}:{FETCHED_REF}")],
+            None,
+            None,
+        )?;
+
+        let commit = match repo.find_reference(FETCHED_REF) {
+            Ok(reference) => Some(reference.peel_to_commit()?),
+            Err(_) => None,
+        };
+        *self.fetched.borrow_mut() = commit.as_ref().map(|c| c.id());
+        Ok(commit)
+    }
+
+    fn read(&self, tree: &Tree) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let repo = &self.book.repo;
+        let mut contacts = HashMap::new();
+
+        match &self.layout {
+            Layout::File { name, store } => {
+                if let Some(entry) = tree.get_name(name) {
+                    let blob = entry.to_object(repo)?.peel_to_blob()?;
+                    contacts = store.decode(blob.content())?;
+                }
+            }
+            Layout::Dir(_) => {
+                for entry in tree.iter() {
+                    let name = Path::new(entry.name().unwrap_or_default());
+                    if entry.kind() != Some(ObjectType::Blob)
+                        || directory::record_format(name).is_none()
+                    {
+                        continue;
+                    }
+                    let blob = entry.to_object(repo)?.peel_to_blob()?;
+                    // Unreadable files are skipped, as in the local book
+                    if let Ok(contact) =
+                        directory::parse_record(name, &String::from_utf8_lossy(blob.content()))
+                    {
+                        contacts.insert(contact.id, contact);
+                    }
+                }
+            }
+        }
+        Ok(contacts)
+    }
+
+    fn write(
+        &self,
+        base: Option<&Tree>,
+        contacts: &HashMap<Uuid, Contact>,
+    ) -> Result<Oid, AppError> {
+        let repo = &self.book.repo;
+
+        match &self.layout {
+            Layout::File { name, store } => {
+                let mut tree = repo.treebuilder(base)?;
+                tree.insert(name, repo.blob(&store.encode(contacts)?)?, 0o100644)?;
+                Ok(tree.write()?)
+            }
+            Layout::Dir(format) => {
+                let mut tree = repo.treebuilder(None)?;
+                for contact in contacts.values() {
+                    let name = format!("{}.{}", contact.id, format.extension());
+                    tree.insert(name, repo.blob(&format.encode(contact)?)?, 0o100644)?;
+                }
+                Ok(tree.write()?)
+            }
+        }
+    }
+
+    fn push(&self) -> Result<(), AppError> {
+        let rejected = RefCell::new(None);
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.push_update_reference(|_, status| {
+            *rejected.borrow_mut() = status.map(|s| s.to_string());
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+
+        self.book.repo.remote_anonymous(&self.url)?.push(
+            &[format!("{OUTGOING_REF}:refs/heads/{BRANCH}")],
+            Some(&mut options),
+        )?;
+        drop(options);
+
+        match rejected.into_inner() {
+            Some(reason) => Err(AppError::Synchronization(format!(
+                "'{}' refused the push ({reason}). Run sync again",
+                self.url
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ContactStore for GitRemoteStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        match self.fetch()? {
+            Some(commit) => self.read(&commit.tree()?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        let repo = &self.book.repo;
+        let fetched = *self.fetched.borrow();
+        let remote = match fetched {
+            Some(id) => Some(repo.find_commit(id)?),
+            None => self.fetch()?,
+        };
+
+        let base = remote.as_ref().map(|c| c.tree()).transpose()?;
+        let tree = repo.find_tree(self.write(base.as_ref(), contacts)?)?;
+        if base.is_some_and(|base| base.id() == tree.id()) {
+            return Ok(());
+        }
+
+        let local = self.book.head()?;
+        let parents: Vec<&Commit> = remote.iter().chain(local.iter()).collect();
+        let signature = self.book.signature()?;
+        let message = format!(
+            "sync: {} contacts from {}",
+            contacts.len(),
+            sync_state::device_id()?
+        );
+        let id = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+        repo.reference(OUTGOING_REF, id, true, "rolodex sync")?;
+
+        self.push()
+    }
+
+    fn get_medium(&self) -> &str {
+        "git"
+    }
+
+    fn get_location(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// The work tree of a book's repository and the book's files in it.
+fn book_paths(medium: &str, location: &str) -> Result<(PathBuf, Vec<String>), AppError> {
+    let medium: StorageMediums = medium.try_into()?;
+
+    match medium {
+        StorageMediums::Dir => Ok((PathBuf::from(location), vec!["*".to_string()])),
+        _ if medium.is_file() => {
+            let workdir = match Path::new(location).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            let name = file_name(location)?;
+            // An event log keeps its checkpoint next to it
+            let checkpoint = event_log::checkpoint_path(&name);
+            Ok((workdir, vec![name, checkpoint]))
+        }
+        other => Err(AppError::Validation(format!(
+            "Only file and dir books can be kept in git, not {}",
+            other.is_which()
+        ))),
+    }
+}
+
+fn file_name(location: &str) -> Result<String, AppError> {
+    Path::new(location)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or(AppError::Validation(format!(
+            "'{location}' is not a file path"
+        )))
+}
+
+fn short_id(id: Oid) -> String {
+    id.to_string()[..7].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str) -> Contact {
+        Contact::new(
+            name.to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        )
+    }
+
+    #[test]
+    fn commits_only_changes_to_the_book() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = file::JsonStorage::at(&dir.path().join("contacts.json").to_string_lossy());
+        fs::write(dir.path().join("books.json"), "{}")?;
+
+        let ada = contact("Ada");
+        store.save(&HashMap::from([(ada.id, ada.clone())]))?;
+        let book = GitBook::init(&store)?;
+        assert!(book.commit("add: Ada")?.is_some());
+        assert!(book.commit("nothing")?.is_none());
+
+        store.save(&HashMap::new())?;
+        book.commit("delete: Ada")?;
+
+        let log = book.log(10)?;
+        let messages: Vec<&str> = log.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["delete: Ada", "add: Ada"]);
+
+        // Files that aren't the book's stay out of the repository
+        let head = book.repo.head()?.peel_to_tree()?;
+        assert!(head.get_name("books.json").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn syncs_through_a_bare_repository() -> Result<(), AppError> {
+        let shared = tempfile::tempdir()?;
+        Repository::init_bare(shared.path())?;
+        let url = shared.path().to_string_lossy().to_string();
+        let config = Config::default();
+
+        let (first, second) = (tempfile::tempdir()?, tempfile::tempdir()?);
+        let first = directory::DirectoryStorage::at(&first.path().to_string_lossy());
+        let second = directory::DirectoryStorage::at(&second.path().to_string_lossy());
+
+        let remote = GitRemoteStorage::new(&url, &first, &config)?;
+        assert!(remote.load()?.is_empty());
+        let ada = contact("Ada");
+        remote.save(&HashMap::from([(ada.id, ada.clone())]))?;
+
+        let remote = GitRemoteStorage::new(&url, &second, &config)?;
+        let mut contacts = remote.load()?;
+        assert_eq!(contacts[&ada.id].name, "Ada");
+
+        let bola = contact("Bola");
+        contacts.insert(bola.id, bola.clone());
+        remote.save(&contacts)?;
+
+        let remote = GitRemoteStorage::new(&url, &first, &config)?;
+        assert_eq!(remote.load()?.len(), 2);
+        Ok(())
+    }
+}
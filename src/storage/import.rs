@@ -0,0 +1,230 @@
+use super::*;
+
+use compression::Compression;
+use std::fmt;
+
+/// How an import file is laid out, which decides how its rejected records are written back.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceFormat {
+    Csv,
+    Json,
+    Jsonl,
+    Vcard,
+    Ldif,
+}
+
+/// One record of an import file.
+pub struct SourceRecord {
+    /// Where it is in the file, e.g. "line 4"
+    pub position: String,
+    /// The record as written in the file, line ending included
+    pub raw: String,
+    /// The contact, or why the record couldn't be read
+    pub contact: Result<Contact, String>,
+}
+
+/// Every record of an import file, see `ContactStore::source_records`.
+pub struct SourceRecords {
+    pub format: SourceFormat,
+    /// Written again above the rejected records: the csv header row, or the ldif or jsonl version line
+    pub header: Option<String>,
+    pub records: Vec<SourceRecord>,
+}
+
+/// A record left out of an import.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub position: String,
+    pub reason: String,
+    pub raw: String,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.position, self.reason)
+    }
+}
+
+/// An import source with the records that can't be read or fail validation
+/// set aside. It loads only the valid contacts, under the source's location,
+/// so sync snapshots stay keyed to the file.
+pub struct ValidatedImport {
+    source: Box<dyn ContactStore>,
+    contacts: HashMap<Uuid, Contact>,
+    pub rejected: Vec<Rejection>,
+    format: SourceFormat,
+    header: Option<String>,
+}
+
+impl ValidatedImport {
+    /// Reads and validates every record of `source`. Stores that can't list
+    /// their records (e.g. remote storage) have no rejections; their contacts
+    /// are loaded as they are.
+    pub fn check(source: Box<dyn ContactStore>) -> Result<Self, AppError> {
+        let Some(records) = source.source_records()? else {
+            return Ok(Self {
+                contacts: source.load()?,
+                source,
+                rejected: Vec::new(),
+                format: SourceFormat::Json,
+                header: None,
+            });
+        };
+
+        let mut contacts = HashMap::new();
+        let mut rejected = Vec::new();
+        for record in records.records {
+            // Tombstones only carry a deletion, so they are taken as they are
+            let contact = record.contact.and_then(|contact| match contact.validate() {
+                _ if contact.deleted => Ok(contact),
+                Ok(()) => Ok(contact),
+                Err(AppError::Validation(reason)) => Err(reason),
+                Err(err) => Err(err.to_string()),
+            });
+            match contact {
+                Ok(contact) => {
+                    contacts.insert(contact.id, contact);
+                }
+                Err(reason) => rejected.push(Rejection {
+                    position: record.position,
+                    reason,
+                    raw: record.raw,
+                }),
+            }
+        }
+
+        Ok(Self {
+            source,
+            contacts,
+            rejected,
+            format: records.format,
+            header: records.header,
+        })
+    }
+
+    /// Where the rejected records go unless told otherwise: next to the
+    /// source, e.g. `contacts.rejects.csv` for `contacts.csv.gz`.
+    pub fn default_reject_path(&self) -> String {
+        let location = self.source.get_location();
+        let inner = match Compression::from_path(&location).and_then(|c| c.extension()) {
+            Some(_) => Path::new(&location).with_extension(""),
+            None => PathBuf::from(&location),
+        };
+        let extension = inner
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_string())
+            .unwrap_or_default();
+        inner
+            .with_extension(format!("rejects.{extension}"))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Writes the rejected records in the source's own format, so they can be
+    /// fixed and imported again.
+    pub fn write_rejects(&self, path: &str) -> Result<(), AppError> {
+        let records = self.rejected.iter().map(|rejection| rejection.raw.as_str());
+        let data = match self.format {
+            SourceFormat::Json => {
+                let items: Vec<&str> = records.map(str::trim_end).collect();
+                format!("[\n{}\n]\n", items.join(",\n"))
+            }
+            SourceFormat::Csv | SourceFormat::Ldif | SourceFormat::Jsonl => self
+                .header
+                .iter()
+                .map(String::as_str)
+                .chain(records)
+                .collect(),
+            SourceFormat::Vcard => records.collect(),
+        };
+        file::write_file(path, data.as_bytes())
+    }
+}
+
+impl ContactStore for ValidatedImport {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        Ok(self.contacts.clone())
+    }
+
+    fn save(&self, _contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        Err(AppError::Validation(
+            "An import source is only read".to_string(),
+        ))
+    }
+
+    fn get_medium(&self) -> &str {
+        self.source.get_medium()
+    }
+
+    fn get_location(&self) -> String {
+        self.source.get_location()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_valid_rows_and_keeps_the_rest() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.csv");
+        fs::write(
+            &path,
+            "name,phone,email\nAda Obi,08012345678,ada@example.com\nBola,123,\nChidi Eze,08055555555,not-an-email\n",
+        )?;
+        let source = file::CsvStorage::new(&path.to_string_lossy())?;
+
+        let import = ValidatedImport::check(Box::new(source))?;
+        assert_eq!(import.load()?.len(), 1);
+        let positions: Vec<&str> = import
+            .rejected
+            .iter()
+            .map(|r| r.position.as_str())
+            .collect();
+        assert_eq!(positions, ["line 3", "line 4"]);
+
+        let rejects = import.default_reject_path();
+        assert!(rejects.ends_with("contacts.rejects.csv"), "{rejects}");
+        import.write_rejects(&rejects)?;
+        assert_eq!(
+            fs::read_to_string(&rejects)?,
+            "name,phone,email\nBola,123,\nChidi Eze,08055555555,not-an-email\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn takes_tombstones_and_names_in_any_alphabet() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.jsonl");
+        let ada = Contact::new(
+            "Ádaeze Obi".to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        );
+        // Deleted long ago, before phones were validated
+        let mut gone = Contact::new(
+            "Bola Ade".to_string(),
+            "123".to_string(),
+            "".to_string(),
+            "".to_string(),
+        );
+        gone.mark_deleted("a1b2c3d4");
+        fs::write(
+            &path,
+            format!(
+                "{}\n{}\n",
+                serde_json::to_string(&ada)?,
+                serde_json::to_string(&gone)?
+            ),
+        )?;
+
+        let import =
+            ValidatedImport::check(Box::new(file::JsonlStorage::at(&path.to_string_lossy())))?;
+        assert!(import.rejected.is_empty());
+        assert_eq!(import.load()?.len(), 2);
+        Ok(())
+    }
+}
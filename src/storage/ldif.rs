@@ -0,0 +1,394 @@
+use super::*;
+
+use crate::config::LdifConfig;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use compression::Compression;
+use import::{SourceFormat, SourceRecord, SourceRecords};
+
+const OBJECT_CLASSES: [&str; 4] = ["top", "person", "organizationalPerson", "inetOrgPerson"];
+
+/// An LDIF file, as OpenLDAP and other directories export them. `cn` (else
+/// `givenName` and `sn`) is the name, `telephoneNumber` (else `mobile`) the
+/// phone, `mail` the email and `o` the tag. Entries that aren't people, such
+/// as the `ou` they sit under, are ignored.
+///
+/// Saving writes one `inetOrgPerson` entry per contact, `uid=<id>,<base_dn>`,
+/// so the file can be loaded with `ldapadd`. Deleted contacts are left out.
+pub struct LdifStorage {
+    pub medium: String,
+    pub path: String,
+    /// How the file is written. Compressed files are always read.
+    pub compression: Compression,
+    pub base_dn: String,
+}
+
+impl LdifStorage {
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "ldif".to_string(),
+            path: path.to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
+            base_dn: LdifConfig::default().base_dn,
+        }
+    }
+
+    pub fn with_base_dn(mut self, base_dn: &str) -> Self {
+        self.base_dn = base_dn.to_string();
+        self
+    }
+}
+
+impl ContactStore for LdifStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(HashMap::new());
+        }
+
+        let mut contacts = HashMap::new();
+        for entry in read_entries(&compression::read_to_string(&self.path)?) {
+            let contact = entry.contact.map_err(|err| {
+                AppError::Validation(format!("'{}' line {}: {err}", self.path, entry.line))
+            })?;
+            contacts.insert(contact.id, contact);
+        }
+        Ok(contacts)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        let mut contacts: Vec<&Contact> = contacts.values().filter(|c| !c.deleted).collect();
+        contacts.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+
+        let mut data = String::from("version: 1\n\n");
+        for contact in contacts {
+            data.push_str(&to_entry(contact, &self.base_dn));
+            data.push('\n');
+        }
+        compression::write(&self.path, data.as_bytes(), self.compression)
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn source_records(&self) -> Result<Option<SourceRecords>, AppError> {
+        let records = read_entries(&compression::read_to_string(&self.path)?)
+            .into_iter()
+            .map(|entry| SourceRecord {
+                position: format!("line {}", entry.line),
+                raw: entry.raw,
+                contact: entry.contact,
+            })
+            .collect();
+        Ok(Some(SourceRecords {
+            format: SourceFormat::Ldif,
+            header: Some("version: 1\n\n".to_string()),
+            records,
+        }))
+    }
+}
+
+/// A person entry of an LDIF file.
+pub struct Entry {
+    /// The line its `dn` is on
+    pub line: usize,
+    /// The entry as written, followed by a blank line
+    pub raw: String,
+    pub contact: Result<Contact, String>,
+}
+
+/// Reads every person entry in `data`, each with its own result.
+pub fn read_entries(data: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    // The open entry's first line and its lines so far
+    let mut block: Option<(usize, Vec<&str>)> = None;
+
+    for (number, line) in data.split_inclusive('\n').enumerate() {
+        if line.trim_end().is_empty() {
+            entries.extend(
+                block
+                    .take()
+                    .and_then(|(start, lines)| to_entry_result(start, &lines)),
+            );
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        match block.as_mut() {
+            Some((_, lines)) => lines.push(line),
+            None => block = Some((number + 1, vec![line])),
+        }
+    }
+    entries.extend(block.and_then(|(start, lines)| to_entry_result(start, &lines)));
+    entries
+}
+
+/// `None` for blocks that aren't people, or only hold the `version` line.
+fn to_entry_result(start: usize, lines: &[&str]) -> Option<Entry> {
+    let mut attributes = match unfold(lines) {
+        Ok(attributes) => attributes,
+        Err(err) => {
+            return Some(Entry {
+                line: start,
+                raw: raw_entry(lines),
+                contact: Err(err),
+            });
+        }
+    };
+
+    // The version line may run straight into the first entry
+    let mut line = start;
+    if attributes
+        .first()
+        .is_some_and(|(name, _)| name == "version")
+    {
+        attributes.remove(0);
+        line += 1;
+    }
+    if attributes.is_empty() {
+        return None;
+    }
+
+    let raw_lines = &lines[line - start..];
+    let contact = entry_to_contact(&attributes)?;
+    Some(Entry {
+        line,
+        raw: raw_entry(raw_lines),
+        contact,
+    })
+}
+
+fn raw_entry(lines: &[&str]) -> String {
+    let mut raw = lines.concat();
+    if !raw.ends_with('\n') {
+        raw.push('\n');
+    }
+    raw.push('\n');
+    raw
+}
+
+/// Joins folded lines and splits each into a lower-case attribute name
+/// (without options such as `;lang-en`) and its decoded value.
+fn unfold(lines: &[&str]) -> Result<Vec<(String, String)>, String> {
+    let mut joined: Vec<String> = Vec::new();
+    for line in lines {
+        let line = line.trim_end_matches(['\r', '\n']);
+        match (line.strip_prefix(' '), joined.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => joined.push(line.to_string()),
+        }
+    }
+
+    joined
+        .into_iter()
+        .map(|line| {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("'{line}' is not an attribute"))?;
+            let name = name
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            let value = if let Some(encoded) = value.strip_prefix(':') {
+                let bytes = STANDARD
+                    .decode(encoded.trim())
+                    .map_err(|_| format!("{name} is not valid base64"))?;
+                String::from_utf8(bytes).map_err(|_| format!("{name} is not valid UTF-8"))?
+            } else if value.starts_with('<') {
+                return Err(format!("{name} refers to a file, which isn't supported"));
+            } else {
+                value.trim_start().to_string()
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// The first non-empty value of an attribute.
+fn value<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(n, value)| n == name && !value.is_empty())
+        .map(|(_, value)| value.as_str())
+}
+
+/// `None` when the entry isn't a person.
+fn entry_to_contact(attributes: &[(String, String)]) -> Option<Result<Contact, String>> {
+    let classes: Vec<String> = attributes
+        .iter()
+        .filter(|(name, _)| name == "objectclass")
+        .map(|(_, value)| value.to_ascii_lowercase())
+        .collect();
+    let person = ["person", "organizationalperson", "inetorgperson"];
+    if !classes.is_empty() && !classes.iter().any(|class| person.contains(&class.as_str())) {
+        return None;
+    }
+
+    Some(person_to_contact(attributes))
+}
+
+fn person_to_contact(attributes: &[(String, String)]) -> Result<Contact, String> {
+    let get = |name: &str| value(attributes, name);
+
+    if let Some(change) = get("changetype")
+        && !change.eq_ignore_ascii_case("add")
+    {
+        return Err(format!("changetype {change} isn't supported"));
+    }
+
+    let name = match get("cn") {
+        Some(name) => name.to_string(),
+        None => [get("givenname"), get("sn")]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<&str>>()
+            .join(" "),
+    };
+    if name.is_empty() {
+        return Err("entry without cn".to_string());
+    }
+
+    let mut contact = Contact::new(
+        name,
+        get("telephonenumber")
+            .or(get("mobile"))
+            .unwrap_or_default()
+            .to_string(),
+        get("mail").unwrap_or_default().to_string(),
+        get("o").unwrap_or_default().to_string(),
+    );
+    if let Some(id) = [get("uid"), get("entryuuid")]
+        .into_iter()
+        .flatten()
+        .find_map(|id| Uuid::parse_str(id).ok())
+    {
+        contact.id = id;
+    }
+    if let Some(created_at) = get("createtimestamp").map(parse_time).transpose()? {
+        contact.created_at = created_at;
+    }
+    if let Some(updated_at) = get("modifytimestamp").map(parse_time).transpose()? {
+        contact.updated_at = updated_at;
+    }
+    Ok(contact)
+}
+
+/// LDAP generalized time, e.g. `20240102030405Z`.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    let digits = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(digits.split('.').next().unwrap_or_default(), "%Y%m%d%H%M%S")
+        .map(|time| time.and_utc())
+        .map_err(|_| format!("invalid time '{value}'"))
+}
+
+/// A contact as an `inetOrgPerson` entry under `base_dn`, without the blank
+/// line that ends it.
+pub fn to_entry(contact: &Contact, base_dn: &str) -> String {
+    // sn is required; the last word of the name is the best guess
+    let name = contact.name.trim();
+    let (given, surname) = match name.rsplit_once(' ') {
+        Some((given, surname)) => (given.trim(), surname),
+        None => ("", name),
+    };
+
+    let mut entry = attribute("dn", &format!("uid={},{base_dn}", contact.id));
+    for class in OBJECT_CLASSES {
+        entry.push_str(&attribute("objectClass", class));
+    }
+    entry.push_str(&attribute("uid", &contact.id.to_string()));
+    entry.push_str(&attribute("cn", name));
+    if !given.is_empty() {
+        entry.push_str(&attribute("givenName", given));
+    }
+    entry.push_str(&attribute("sn", surname));
+    if !contact.phone.is_empty() {
+        entry.push_str(&attribute("telephoneNumber", &contact.phone));
+    }
+    if !contact.email.is_empty() {
+        entry.push_str(&attribute("mail", &contact.email));
+    }
+    if !contact.tag.is_empty() {
+        entry.push_str(&attribute("o", &contact.tag));
+    }
+    entry
+}
+
+/// One attribute line. Values LDIF can't hold as plain text (non-ASCII, or
+/// starting with a space, `:` or `<`) are base64 encoded.
+fn attribute(name: &str, value: &str) -> String {
+    let safe = value.is_ascii()
+        && !value.contains(['\0', '\r', '\n'])
+        && !value.starts_with([' ', ':', '<'])
+        && !value.ends_with(' ');
+    if safe {
+        format!("{name}: {value}\n")
+    } else {
+        format!("{name}:: {}\n", STANDARD.encode(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_inet_org_person_entries() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = LdifStorage::at(&dir.path().join("people.ldif").to_string_lossy())
+            .with_base_dn("ou=staff,dc=acme,dc=org");
+        let ada = Contact::new(
+            "Ádaeze Obi".to_string(),
+            "08012345678".to_string(),
+            "ada@acme.org".to_string(),
+            "Acme".to_string(),
+        );
+        store.save(&HashMap::from([(ada.id, ada.clone())]))?;
+
+        let data = fs::read_to_string(&store.path)?;
+        assert!(data.contains(&format!("dn: uid={},ou=staff,dc=acme,dc=org\n", ada.id)));
+        assert!(data.contains("objectClass: inetOrgPerson\n"));
+        assert!(data.contains("cn:: "), "{data}");
+        assert!(data.contains("sn: Obi\n"));
+
+        let loaded = store.load()?;
+        assert_eq!(loaded[&ada.id].name, "Ádaeze Obi");
+        assert_eq!(loaded[&ada.id].tag, "Acme");
+        Ok(())
+    }
+
+    #[test]
+    fn reads_directory_exports() {
+        let data = "version: 1\n\
+            dn: ou=people,dc=example,dc=com\n\
+            objectClass: organizationalUnit\n\
+            ou: people\n\
+            \n\
+            # Bola\n\
+            dn: uid=bade,ou=people,dc=example,dc=com\n\
+            objectClass: inetOrgPerson\n\
+            givenName: Bola\n\
+            sn: Ade\n\
+            mobile: 0808765\n 4321\n\
+            modifyTimestamp: 20240102030405Z\n\
+            \n\
+            dn: uid=nobody,ou=people,dc=example,dc=com\n\
+            objectClass: person\n\
+            telephoneNumber: 08011111111\n";
+
+        let entries = read_entries(data);
+        assert_eq!(entries.len(), 2);
+        let bola = entries[0].contact.as_ref().unwrap();
+        assert_eq!(entries[0].line, 7);
+        assert_eq!(bola.name, "Bola Ade");
+        assert_eq!(bola.phone, "08087654321");
+        assert_eq!(bola.updated_at.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+        assert_eq!(entries[1].line, 15);
+        assert!(entries[1].contact.is_err());
+    }
+}
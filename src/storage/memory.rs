@@ -1,33 +1,143 @@
 use super::*;
 
-#[derive(Default)]
+use std::{cell::RefCell, rc::Rc};
+
+/// Contacts kept only in memory, for throwaway sessions, tests and benches.
+/// Clones share the same contacts, so a caller can keep a handle and read back
+/// what a `ContactManager` saved.
+#[derive(Debug, Clone, Default)]
 pub struct MemStore {
-    pub data: Vec<Contact>,
+    contacts: Rc<RefCell<HashMap<Uuid, Contact>>>,
+    fixture: Option<String>,
 }
 
 impl MemStore {
-    pub fn iter(&self) -> MemStoreIter<'_> {
-        MemStoreIter {
-            inner: &self.data,
-            idx: 0,
-        }
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_contacts(contacts: impl IntoIterator<Item = Contact>) -> Self {
+        let store = Self::new();
+        store
+            .contacts
+            .borrow_mut()
+            .extend(contacts.into_iter().map(|c| (c.id, c)));
+        store
+    }
+
+    /// Starts from the contacts in a json or txt file, which may be compressed.
+    /// The file is never written to.
+    pub fn from_fixture(path: &str) -> Result<Self, AppError> {
+        let contacts = match compression::inner_extension(path).as_deref() {
+            Some("txt") => file::TxtStorage::at(path).load()?,
+            Some("json") => file::JsonStorage::at(path).load()?,
+            _ => {
+                return Err(AppError::Validation(format!(
+                    "Fixture '{path}' must be a .json or .txt file"
+                )));
+            }
+        };
+
+        Ok(Self {
+            contacts: Rc::new(RefCell::new(contacts)),
+            fixture: Some(path.to_string()),
+        })
     }
-}
 
-pub struct MemStoreIter<'a> {
-    inner: &'a [Contact],
-    idx: usize,
+    pub fn len(&self) -> usize {
+        self.contacts.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contacts.borrow().is_empty()
+    }
 }
 
-impl<'a> Iterator for MemStoreIter<'a> {
-    type Item = &'a Contact;
+impl ContactStore for MemStore {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        Ok(self.contacts.borrow().clone())
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        *self.contacts.borrow_mut() = contacts.clone();
+        Ok(())
+    }
+
+    fn get_medium(&self) -> &str {
+        "mem"
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.idx >= self.inner.len() {
-            return None;
+    fn get_location(&self) -> String {
+        match &self.fixture {
+            Some(path) => format!("mem:{path}"),
+            None => "mem".to_string(),
         }
-        let contact = &self.inner[self.idx];
-        self.idx += 1;
-        Some(contact)
+    }
+
+    fn upsert(&self, contact: &Contact) -> Result<(), AppError> {
+        self.contacts
+            .borrow_mut()
+            .insert(contact.id, contact.clone());
+        Ok(())
+    }
+
+    fn writes_records(&self) -> bool {
+        true
+    }
+
+    fn remove(&self, id: &Uuid) -> Result<(), AppError> {
+        self.contacts
+            .borrow_mut()
+            .remove(id)
+            .map(|_| ())
+            .ok_or(AppError::NotFound("Contact".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_saved_contacts() -> Result<(), AppError> {
+        let store = MemStore::new();
+        let handle = store.clone();
+
+        let contact = Contact::new(
+            "Ada Obi".to_string(),
+            "08012345678".to_string(),
+            "ada@example.com".to_string(),
+            "".to_string(),
+        );
+        store.save(&HashMap::from([(contact.id, contact.clone())]))?;
+
+        assert_eq!(handle.len(), 1);
+        assert_eq!(handle.load()?.get(&contact.id), Some(&contact));
+        Ok(())
+    }
+
+    #[test]
+    fn fixture_seeds_without_being_written() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir
+            .path()
+            .join("fixture.json")
+            .to_string_lossy()
+            .to_string();
+        let seed = MemStore::with_contacts([Contact::new(
+            "Bola Ade".to_string(),
+            "08087654321".to_string(),
+            "".to_string(),
+            "".to_string(),
+        )]);
+        file::JsonStorage::at(&path).save(&seed.load()?)?;
+
+        let store = MemStore::from_fixture(&path)?;
+        store.save(&HashMap::new())?;
+
+        assert!(store.is_empty());
+        assert_eq!(file::JsonStorage::at(&path).load()?.len(), 1);
+        assert!(MemStore::from_fixture("contacts.csv").is_err());
+        Ok(())
     }
 }
@@ -1,23 +1,117 @@
+pub mod binary;
+pub mod books;
+pub mod compression;
+pub mod credentials;
+pub mod csv_dialect;
+pub mod directory;
+pub mod encryption;
+pub mod event_log;
 pub mod file;
+pub mod git;
+pub mod import;
+pub mod ldif;
 pub mod memory;
 pub mod remote;
+pub mod schema;
+pub mod sync_state;
+pub mod vcard;
 
 use crate::helper;
-use crate::prelude::{AppError, Contact, HashMap, uuid::Uuid};
-use dotenv::dotenv;
+use crate::prelude::{AppError, Config, Contact, HashMap, uuid::Uuid};
 use std::fs::{self, OpenOptions};
-use std::io::{BufReader, Read, Write};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::{
     env,
     path::{Path, PathBuf},
 };
 
+/// A store that keeps all its contacts in one file, so another store can wrap
+/// its bytes (e.g. encrypt them) on the way to and from disk.
+pub trait FileStore: ContactStore {
+    /// The whole file for `contacts`, in the current schema.
+    fn encode(&self, contacts: &HashMap<Uuid, Contact>) -> Result<Vec<u8>, AppError>;
+
+    fn decode(&self, data: &[u8]) -> Result<HashMap<Uuid, Contact>, AppError>;
+
+    /// Replaces the store's file with `data`.
+    fn write_file(&self, data: &[u8]) -> Result<(), AppError>;
+}
+
+/// Contacts read one at a time from a store.
+pub type ContactIter<'a> = Box<dyn Iterator<Item = Result<Contact, AppError>> + 'a>;
+
 pub trait ContactStore {
     fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError>;
 
     fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError>;
 
     fn get_medium(&self) -> &str;
+
+    /// Identifies where the contacts live (file path, URL, ...).
+    /// Used to keep per-store sync state apart.
+    fn get_location(&self) -> String {
+        self.get_medium().to_string()
+    }
+
+    /// Reads the contacts one at a time. The default loads them all first;
+    /// backends that can read record by record override it.
+    fn load_iter(&self) -> Result<ContactIter<'_>, AppError> {
+        Ok(Box::new(self.load()?.into_values().map(Ok)))
+    }
+
+    /// Replaces the stored contacts with `contacts`. The default collects them
+    /// and calls `save`; backends that can write record by record override it.
+    fn save_iter(&self, contacts: &mut dyn Iterator<Item = &Contact>) -> Result<(), AppError> {
+        let contacts: HashMap<Uuid, Contact> = contacts.map(|c| (c.id, c.clone())).collect();
+        self.save(&contacts)
+    }
+
+    /// Adds or replaces one contact. The default rewrites the whole store.
+    fn upsert(&self, contact: &Contact) -> Result<(), AppError> {
+        let mut contacts = self.load()?;
+        contacts.insert(contact.id, contact.clone());
+        self.save(&contacts)
+    }
+
+    /// Whether `upsert` writes just the one record instead of rewriting the
+    /// store, which makes it cheaper than a full `save` after a single change.
+    fn writes_records(&self) -> bool {
+        false
+    }
+
+    /// Records the last `load` skipped because they couldn't be read, each with
+    /// the reason. Stores that fail the whole load instead have none.
+    fn skipped(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Every record of an import file with where it is and its raw text, so an
+    /// import can keep the good records and set the bad ones aside (see
+    /// `import::ValidatedImport`). `None` for stores that can only load whole.
+    fn source_records(&self) -> Result<Option<import::SourceRecords>, AppError> {
+        Ok(None)
+    }
+
+    /// Drops one contact for good, without leaving a tombstone. The default
+    /// rewrites the whole store.
+    fn remove(&self, id: &Uuid) -> Result<(), AppError> {
+        let mut contacts = self.load()?;
+        if contacts.remove(id).is_none() {
+            return Err(AppError::NotFound("Contact".to_string()));
+        }
+        self.save(&contacts)
+    }
+
+    /// Protects state kept next to the book, such as its sync snapshots, the
+    /// way the book itself is protected. Plain stores keep it as it is.
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, AppError> {
+        Ok(data.to_vec())
+    }
+
+    /// Reverses `seal`. `location` names the data in errors.
+    fn unseal(&self, data: &[u8], _location: &str) -> Result<Vec<u8>, AppError> {
+        Ok(data.to_vec())
+    }
 }
 
 #[derive(Debug)]
@@ -26,6 +120,11 @@ pub enum StorageMediums {
     Txt,
     Json,
     Remote,
+    Mem,
+    Bin,
+    Jsonl,
+    Events,
+    Dir,
 }
 
 impl StorageMediums {
@@ -41,13 +140,33 @@ impl StorageMediums {
         matches!(self, StorageMediums::Remote)
     }
 
+    pub fn is_mem(&self) -> bool {
+        matches!(self, StorageMediums::Mem)
+    }
+
+    /// Mediums whose book is a single file on disk (see `file_store`).
+    pub fn is_file(&self) -> bool {
+        matches!(
+            self,
+            StorageMediums::Json
+                | StorageMediums::Txt
+                | StorageMediums::Bin
+                | StorageMediums::Jsonl
+                | StorageMediums::Events
+        )
+    }
+
     pub fn is_which(&self) -> &str {
-        if self.is_json() {
-            "json"
-        } else if self.is_txt() {
-            "txt"
-        } else {
-            "remote"
+        match self {
+            StorageMediums::Csv => "csv",
+            StorageMediums::Txt => "txt",
+            StorageMediums::Json => "json",
+            StorageMediums::Remote => "remote",
+            StorageMediums::Mem => "mem",
+            StorageMediums::Bin => "bin",
+            StorageMediums::Jsonl => "jsonl",
+            StorageMediums::Events => "events",
+            StorageMediums::Dir => "dir",
         }
     }
 }
@@ -60,6 +179,11 @@ impl TryFrom<&str> for StorageMediums {
             "json" => Ok(StorageMediums::Json),
             "txt" => Ok(StorageMediums::Txt),
             "remote" => Ok(StorageMediums::Remote),
+            "mem" => Ok(StorageMediums::Mem),
+            "bin" => Ok(StorageMediums::Bin),
+            "jsonl" => Ok(StorageMediums::Jsonl),
+            "events" => Ok(StorageMediums::Events),
+            "dir" => Ok(StorageMediums::Dir),
             _ => Err(AppError::Validation(
                 "Not a recognized storage medium".to_string(),
             )),
@@ -67,27 +191,100 @@ impl TryFrom<&str> for StorageMediums {
     }
 }
 
+/// Opens the storage to work on: the given medium, else the active book
+/// (`--book`, `ROLODEX_BOOK` or the default book setting).
+/// The default book is stored in the medium picked by `storage.choice`.
 pub fn parse_storage_type_env_config(
     storage_medium: Option<StorageMediums>,
+    config: &Config,
 ) -> Result<Box<dyn ContactStore>, AppError> {
     let medium: StorageMediums;
     if let Some(storage_medium) = storage_medium {
         medium = storage_medium;
     } else {
-        dotenv().ok();
+        let book = books::active_book(config)?;
+        if book != books::DEFAULT_BOOK {
+            return books::book_storage(&book, config);
+        }
 
-        let choice = helper::get_env_value_by_key("STORAGE_CHOICE").unwrap_or("json".to_string());
-        medium = choice.as_str().try_into()?;
+        medium = config.storage.choice.as_str().try_into()?;
     }
 
     match medium {
-        StorageMediums::Json => Ok(Box::new(file::JsonStorage::new()?)),
+        StorageMediums::Json => open_file(medium, &config.storage.json_path, config),
         StorageMediums::Csv => Ok(Box::new(file::CsvStorage::new("")?)),
-        StorageMediums::Txt => Ok(Box::new(file::TxtStorage::new()?)),
-        StorageMediums::Remote => Ok(Box::new(remote::RemoteStorage::new()?)),
+        StorageMediums::Txt => open_file(medium, &config.storage.txt_path, config),
+        StorageMediums::Bin => open_file(medium, &config.storage.bin_path, config),
+        StorageMediums::Jsonl => open_file(medium, &config.storage.jsonl_path, config),
+        StorageMediums::Events => open_file(medium, &config.storage.events_path, config),
+        StorageMediums::Dir => Ok(Box::new(directory_store(&config.storage.dir_path, config)?)),
+        StorageMediums::Remote => Ok(Box::new(remote::RemoteStorage::from_config(
+            config,
+            books::DEFAULT_BOOK,
+        )?)),
+        StorageMediums::Mem => match &config.storage.mem_fixture {
+            Some(fixture) => Ok(Box::new(memory::MemStore::from_fixture(fixture)?)),
+            None => Ok(Box::new(memory::MemStore::new())),
+        },
     }
 }
 
+/// Opens a json, txt, bin, jsonl or events file, decrypting it on the way if it is encrypted.
+pub fn open_file(
+    medium: StorageMediums,
+    path: &str,
+    config: &Config,
+) -> Result<Box<dyn ContactStore>, AppError> {
+    let store = file_store(medium, path, config)?;
+    if encryption::is_encrypted(path) {
+        let key_source = encryption::KeySource::from_config(config)?;
+        return Ok(Box::new(encryption::EncryptedStorage::new(
+            store, key_source,
+        )));
+    }
+    Ok(store)
+}
+
+/// The plain store for a json, txt, bin, jsonl or events file. Text files are compressed
+/// as their extension says or else as `storage.compression` says.
+pub fn file_store(
+    medium: StorageMediums,
+    path: &str,
+    config: &Config,
+) -> Result<Box<dyn FileStore>, AppError> {
+    let compression = match compression::Compression::from_path(path) {
+        Some(compression) => compression,
+        None => config.storage.compression.as_str().try_into()?,
+    };
+
+    match medium {
+        StorageMediums::Json => Ok(Box::new(
+            file::JsonStorage::at(path).with_compression(compression),
+        )),
+        StorageMediums::Txt => Ok(Box::new(
+            file::TxtStorage::at(path).with_compression(compression),
+        )),
+        StorageMediums::Bin => Ok(Box::new(binary::BinaryStorage::at(path))),
+        StorageMediums::Jsonl => Ok(Box::new(
+            file::JsonlStorage::at(path).with_compression(compression),
+        )),
+        StorageMediums::Events => Ok(Box::new(event_log::EventLogStorage::at(path))),
+        other => Err(AppError::Validation(format!(
+            "Only json, txt, bin, jsonl and events books are files, not {}",
+            other.is_which()
+        ))),
+    }
+}
+
+/// A directory book at `path`, writing its files in `storage.dir_format`.
+pub fn directory_store(
+    path: &str,
+    config: &Config,
+) -> Result<directory::DirectoryStorage, AppError> {
+    let format = config.storage.dir_format.as_str().try_into()?;
+    Ok(directory::DirectoryStorage::at(path).with_format(format))
+}
+
 pub fn create_file_parent(path: &str) -> Result<(), AppError> {
     let path = Path::new(path);
 
@@ -98,3 +295,113 @@ pub fn create_file_parent(path: &str) -> Result<(), AppError> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::clock::Hlc;
+
+    fn contact(name: &str) -> Contact {
+        Contact::new(
+            name.to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        )
+    }
+
+    #[test]
+    fn default_streaming_methods_go_through_load_and_save() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = file::JsonStorage::at(&dir.path().join("contacts.json").to_string_lossy());
+
+        let (ada, bola) = (contact("Ada"), contact("Bola"));
+        store.save_iter(&mut [&ada, &bola].into_iter())?;
+
+        let mut bola = bola;
+        bola.tag = "work".to_string();
+        store.upsert(&bola)?;
+        store.remove(&ada.id)?;
+        assert!(store.remove(&ada.id).is_err());
+
+        let loaded = store
+            .load_iter()?
+            .collect::<Result<Vec<Contact>, AppError>>()?;
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].tag, "work");
+        Ok(())
+    }
+
+    #[test]
+    fn csv_streams_records() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let store = file::CsvStorage::new(&dir.path().join("contacts.csv").to_string_lossy())?;
+
+        let contacts = [contact("Ada"), contact("Bola"), contact("Chidi")];
+        store.save_iter(&mut contacts.iter())?;
+
+        let mut names = Vec::new();
+        for loaded in store.load_iter()? {
+            names.push(loaded?.name);
+        }
+        assert_eq!(names, vec!["Ada", "Bola", "Chidi"]);
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_appends_and_compacts_on_save() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.jsonl");
+        let store = file::JsonlStorage::at(&path.to_string_lossy());
+        assert!(store.writes_records());
+
+        let mut ada = contact("Ada");
+        store.upsert(&ada)?;
+        ada.tag = "work".to_string();
+        store.upsert(&ada)?;
+        store.upsert(&contact("Bola"))?;
+        // The version header, then one line per write
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 4);
+
+        // Every version streams; the latest wins when loaded
+        assert_eq!(store.load_iter()?.count(), 3);
+        let loaded = store.load()?;
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[&ada.id].tag, "work");
+
+        store.save(&loaded)?;
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 3);
+
+        fs::write(&path, "{\"name\": \"no id\"}\n")?;
+        let err = store.load().unwrap_err().to_string();
+        assert!(err.contains("line 1"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_versions_its_files() -> Result<(), AppError> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("contacts.jsonl");
+        let store = file::JsonlStorage::at(&path.to_string_lossy());
+
+        // A file from before the header gets one on the next write
+        let mut ada = contact("Ada");
+        ada.clock = Hlc::default();
+        fs::write(&path, format!("{}\n", serde_json::to_string(&ada)?))?;
+        store.upsert(&contact("Bola"))?;
+        let data = fs::read_to_string(&path)?;
+        assert_eq!(data.lines().next(), Some(schema::jsonl_header().trim_end()));
+        assert_eq!(store.load()?[&ada.id].clock, Hlc::seeded(ada.updated_at));
+
+        let newer = format!("{{\"schema_version\":{}}}\n", schema::CURRENT_VERSION + 1);
+        fs::write(&path, &newer)?;
+        for err in [
+            store.load().unwrap_err(),
+            store.upsert(&contact("Chidi")).unwrap_err(),
+        ] {
+            assert!(err.to_string().contains("Upgrade rolodex"), "{err}");
+        }
+        assert_eq!(fs::read_to_string(&path)?, newer);
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
-use crate::helper;
-use std::cell::RefCell;
+use crate::config::{Config, data_dir};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, fs, path::PathBuf};
 
-use super::{AppError, Contact, ContactStore, HashMap, Uuid};
+use super::credentials::{self, API_KEY, CredentialStore};
+use super::{AppError, Contact, ContactStore, HashMap, Uuid, books, create_file_parent, schema};
 use reqwest::blocking;
 use url::Url;
 
@@ -9,125 +11,202 @@ pub struct RemoteStorage {
     pub medium: String,
     pub base_url: Option<String>,
     resource_id: RefCell<Option<String>>,
+    /// Set from `REMOTE_API_KEY`; otherwise the key is read from `credentials`.
+    pub api_key: Option<String>,
+    credentials: Option<Box<dyn CredentialStore>>,
+    state: Option<RemoteState>,
     pub active_url: RefCell<Option<String>>,
 }
 
+/// What a book remembers about its remote between runs, kept under the data dir
+/// (`$XDG_DATA_HOME/rolodex/books/<book>/remote.json`).
+pub struct RemoteState {
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RemoteStateData {
+    resource_id: Option<String>,
+}
+
+impl RemoteState {
+    pub fn for_book(book: &str) -> Self {
+        Self {
+            path: Self::book_dir(book).join("remote.json"),
+        }
+    }
+
+    /// Directory holding everything the data dir keeps for `book`.
+    pub fn book_dir(book: &str) -> PathBuf {
+        data_dir().join("books").join(book)
+    }
+
+    pub fn resource_id(&self) -> Result<Option<String>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(None);
+        }
+        let data: RemoteStateData = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        Ok(data.resource_id)
+    }
+
+    pub fn save_resource_id(&self, resource_id: &str) -> Result<(), AppError> {
+        let data = RemoteStateData {
+            resource_id: Some(resource_id.to_string()),
+        };
+        create_file_parent(&self.path.to_string_lossy())?;
+        fs::write(&self.path, serde_json::to_string(&data)?)?;
+        Ok(())
+    }
+}
+
 impl RemoteStorage {
     pub fn new() -> Result<Self, AppError> {
+        let config = Config::load()?;
+        Self::from_config(&config, &books::active_book(&config)?)
+    }
+
+    /// Remote storage of `book`, with its own resource id.
+    pub fn from_config(config: &Config, book: &str) -> Result<Self, AppError> {
         Ok(Self {
             medium: "remote".to_string(),
-            base_url: helper::get_env_value_by_key("REMOTE_STORAGE_URL").ok(),
-            resource_id: RefCell::new(helper::get_env_value_by_key("RESOURCE_ID").ok()),
+            base_url: config.remote.url.clone(),
+            resource_id: RefCell::new(config.remote.resource_id.clone()),
+            api_key: config.remote.api_key.clone(),
+            credentials: Some(credentials::open(&config.credentials)?),
+            state: Some(RemoteState::for_book(book)),
             active_url: RefCell::new(None),
         })
     }
 
-    /// This method formats a get-request url for the remote storage
-    /// from the predefined base url in .env and uses RefCell interior mutability
-    /// to update the `active_url` field with the formated url for the next request.
-    ///
-    /// Use `Self.update_active_url_from_str()` method as an alternative to explicitly
-    /// parse a url as string if .env is not set.
-    pub fn format_get_req_from_base_url(&self) -> Result<(), AppError> {
-        let mut resource_id = self.resource_id.borrow_mut();
+    fn api_key(&self) -> Result<String, AppError> {
+        if let Some(api_key) = &self.api_key {
+            return Ok(api_key.clone());
+        }
 
-        if resource_id.is_none() {
-            // if resource_id is not set, try read from .env
-            let id_from_env = helper::get_env_value_by_key("RESOURCE_ID").map_or_else(
-                |_| Err(AppError::NotFound("Resource ID in env".to_string())),
-                Ok,
-            )?; // Propagate error to exit Fn if resource_id is not set in .env
+        let stored = match &self.credentials {
+            Some(credentials) => credentials.get(API_KEY)?,
+            None => None,
+        };
+        stored.ok_or(AppError::NotFound(format!(
+            "Remote API key (store it with `rolodex config set-secret {API_KEY}`)"
+        )))
+    }
+
+    /// Returns the resource id, reading it from the book's remote state the first time if it is not set yet.
+    /// A remote state that can't be read is an error, so a broken file never
+    /// leads to a second resource being created.
+    fn resource_id(&self) -> Result<Option<String>, AppError> {
+        let mut resource_id = self.resource_id.borrow_mut();
 
-            *resource_id = Some(id_from_env);
+        if resource_id.is_none()
+            && let Some(state) = &self.state
+        {
+            *resource_id = state.resource_id()?;
         }
+        Ok(resource_id.clone())
+    }
 
-        if let Some(base_url) = &self.base_url {
-            *self.active_url.borrow_mut() =
-                Some(format!("{}/{}", base_url, resource_id.as_ref().unwrap()));
+    /// Builds the get-request url from the configured base url and the book's resource id.
+    pub fn get_req_url(&self) -> Result<String, AppError> {
+        let resource_id = self
+            .resource_id()?
+            .ok_or(AppError::NotFound("Resource ID in env".to_string()))?;
+        let base_url = self
+            .base_url
+            .as_ref()
+            .ok_or(AppError::NotFound("Base URL.".to_string()))?;
+
+        Ok(format!("{}/{}", base_url, resource_id))
+    }
 
-            Ok(())
-        } else {
-            Err(AppError::NotFound("Base URL.".to_string()))
-        }
+    /// Builds the post-request url, incorporating the API key, from the configured base url.
+    pub fn post_req_url(&self) -> Result<String, AppError> {
+        let base_url = self
+            .base_url
+            .as_ref()
+            .ok_or(AppError::NotFound("Base URL.".to_string()))?;
+
+        Ok(format!("{}?apiKey={}", base_url, self.api_key()?))
+    }
+
+    /// Builds the put-request url from the configured base url.
+    /// Falls back to the post-request url to upload fresh data if no resource id is set yet.
+    pub fn put_req_url(&self) -> Result<String, AppError> {
+        let Some(resource_id) = self.resource_id()? else {
+            return self.post_req_url();
+        };
+        let base_url = self
+            .base_url
+            .as_ref()
+            .ok_or(AppError::NotFound("Base URL.".to_string()))?;
+
+        Ok(format!(
+            "{}/{}?apiKey={}",
+            base_url,
+            resource_id,
+            self.api_key()?
+        ))
+    }
+
+    /// This method formats a get-request url for the remote storage
+    /// from the configured base url and uses RefCell interior mutability
+    /// to update the `active_url` field with the formated url for the next request.
+    ///
+    /// Use `Self.update_active_url_from_str()` method as an alternative to explicitly
+    /// parse a url as string if no remote is configured.
+    pub fn format_get_req_from_base_url(&self) -> Result<(), AppError> {
+        *self.active_url.borrow_mut() = Some(self.get_req_url()?);
+        Ok(())
     }
 
     /// This method formats a post-request url that incorperates the API key
-    /// for the remote storage from the predefined base url in .env and uses RefCell
+    /// for the remote storage from the configured base url and uses RefCell
     /// interior mutability to update the `active_url` field with the formated url for
     /// the next request.
     ///
     /// Use `Self.update_active_url_from_str()` method as an alternative to explicitly
-    /// parse a url as string if .env is not set.
+    /// parse a url as string if no remote is configured.
     pub fn format_post_req_from_base_url(&self) -> Result<(), AppError> {
-        if let Some(base_url) = &self.base_url {
-            *self.active_url.borrow_mut() = Some(format!(
-                "{}?apiKey={}",
-                base_url,
-                helper::get_env_value_by_key("REMOTE_API_KEY")?
-            ));
-
-            Ok(())
-        } else {
-            Err(AppError::NotFound("Base URL.".to_string()))
-        }
+        *self.active_url.borrow_mut() = Some(self.post_req_url()?);
+        Ok(())
     }
 
     /// This method formats a put-request url for the remote storage
-    /// from the predefined base url in .env and uses RefCell interior mutability
+    /// from the configured base url and uses RefCell interior mutability
     /// to update the `active_url` field with the formated url for the next request.
     ///
     /// Use `Self.update_active_url_from_str()` method as an alternative to explicitly
-    /// parse a url as string if .env is not set.
+    /// parse a url as string if no remote is configured.
     pub fn format_put_req_from_base_url(&self) -> Result<(), AppError> {
-        let mut resource_id = self.resource_id.borrow_mut();
-
-        if resource_id.is_none() {
-            // if resource_id is not set, try read from .env
-            let id_from_env = helper::get_env_value_by_key("RESOURCE_ID").map_or_else(
-                |_| Err(AppError::NotFound("Resource ID in env".to_string())),
-                Ok,
-            );
-
-            // If resource id not found then just use post format to upload fresh data (not update existing)
-            if id_from_env.is_err() {
-                return self.format_post_req_from_base_url();
-            }
-            *resource_id = Some(id_from_env.unwrap());
-        }
-
-        if let Some(base_url) = &self.base_url {
-            *self.active_url.borrow_mut() = Some(format!(
-                "{}/{}?apiKey={}",
-                base_url,
-                resource_id.as_ref().unwrap(),
-                helper::get_env_value_by_key("REMOTE_API_KEY")?
-            ));
-
-            Ok(())
-        } else {
-            Err(AppError::NotFound("Base URL.".to_string()))
-        }
+        *self.active_url.borrow_mut() = Some(self.put_req_url()?);
+        Ok(())
     }
 
     /// This function extracts the resource id from the `uri` arguement based on the
     /// url pattern documented in https://app.jsonstorage.net, which was used during
-    /// the development of this project
-    pub fn extract_resource_id_from_successful_post_req(&self, uri: Option<&String>) {
+    /// the development of this project, and remembers it in the book's remote state.
+    pub fn extract_resource_id_from_successful_post_req(
+        &self,
+        uri: Option<&String>,
+    ) -> Result<(), AppError> {
         if uri.is_none() {
-            return;
+            return Ok(());
         }
 
         let uri = uri.unwrap();
         let uri_parts: Vec<&str> = uri.split("json/").collect();
         let resource_id = uri_parts[uri_parts.len() - 1].to_string();
 
-        let _ = helper::set_env_value_in_file("RESOURCE_ID", &resource_id);
+        if let Some(state) = &self.state {
+            state.save_resource_id(&resource_id)?;
+        }
 
         *self.resource_id.borrow_mut() = Some(resource_id);
+        Ok(())
     }
 
     /// This method updates the `active_url` field directly from string arguement.
-    /// It can be used as an alternative when url is not set in .env.
+    /// It can be used as an alternative when no remote url is configured.
     ///
     /// ## Caution!
     /// This method **does not validate/verify url.** Validate url before
@@ -142,35 +221,51 @@ impl ContactStore for RemoteStorage {
         &self.medium
     }
 
+    fn get_location(&self) -> String {
+        self.active_url
+            .borrow()
+            .clone()
+            .or(self.get_req_url().ok())
+            .or(self.base_url.clone())
+            .unwrap_or(self.medium.clone())
+    }
+
+    /// Reads from `active_url`, or from the configured get-request url if none is set.
     fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
         let active_uri = self.active_url.borrow().clone();
-        let active_uri = active_uri.ok_or(AppError::NotFound("active url".to_string()))?;
 
-        let url = active_uri;
-        let response = blocking::get(url)?;
+        let url = match active_uri {
+            Some(url) => url,
+            None => self.get_req_url()?,
+        };
+        let response = blocking::get(&url)?;
 
         let response = response.error_for_status()?;
         let res_str = response.text()?;
-        let contacts: HashMap<Uuid, Contact> = serde_json::from_str(&res_str)?;
-        Ok(contacts)
+        schema::read_json(&res_str, &url)
     }
 
+    /// Writes to `active_url`, or to the configured put-request url if none is set.
     fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
         let url = self.active_url.borrow().clone();
-        let url = url.ok_or(AppError::NotFound("active url".to_string()))?;
+        let url = match url {
+            Some(url) => url,
+            None => self.put_req_url()?,
+        };
 
+        let body = schema::to_json(contacts)?;
         let blocking_client = blocking::Client::new();
         let mut res = blocking_client
             .put(&url)
             .header(reqwest::header::CONTENT_TYPE, "application/json")
-            .body(serde_json::to_vec(contacts)?)
+            .body(body.clone())
             .send()?;
 
         if !res.status().is_success() {
             res = blocking_client
                 .post(&url)
                 .header(reqwest::header::CONTENT_TYPE, "application/json")
-                .body(serde_json::to_vec(contacts)?)
+                .body(body)
                 .send()?;
         }
 
@@ -178,8 +273,7 @@ impl ContactStore for RemoteStorage {
         let res = res.error_for_status()?;
 
         let res_map: HashMap<String, String> = serde_json::from_str(&res.text()?)?;
-        self.extract_resource_id_from_successful_post_req(res_map.get("uri"));
-        Ok(())
+        self.extract_resource_id_from_successful_post_req(res_map.get("uri"))
     }
 }
 
@@ -335,10 +429,42 @@ mod tests {
         assert!(contacts.values().any(|c| c.name == "Adamu"));
     }
 
+    #[test]
+    fn load_and_save_build_urls_when_no_active_url() {
+        let get_mock = mock("GET", "/sync-resource")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(CONTACTS_JSON)
+            .create();
+
+        let put_mock = mock("PUT", "/sync-resource?apiKey=test-key")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"success":"true"}"#)
+            .create();
+
+        let storage = RemoteStorage {
+            medium: "remote".to_string(),
+            base_url: Some(server_url()),
+            resource_id: RefCell::new(Some("sync-resource".to_string())),
+            api_key: Some("test-key".to_string()),
+            credentials: None,
+            state: None,
+            active_url: RefCell::new(None),
+        };
+
+        let contacts = storage.load().unwrap();
+        storage.save(&contacts).unwrap();
+
+        get_mock.assert();
+        put_mock.assert();
+        assert!(storage.active_url.borrow().is_none());
+    }
+
     #[test]
     fn save_prefers_put_then_post_when_put_fails() {
         // construct a small contacts map with serde-serializable data matching your types.
-        let contacts: HashMap<Uuid, Contact> = serde_json::from_str(&CONTACTS_JSON).unwrap();
+        let contacts: HashMap<Uuid, Contact> = serde_json::from_str(CONTACTS_JSON).unwrap();
 
         // Return 500 for PUT, then 200 for POST with small body.
         let put_mock = mock("PUT", "/resource-id").with_status(500).create();
@@ -365,4 +491,72 @@ mod tests {
         put_mock.assert();
         post_mock.assert();
     }
+
+    #[test]
+    fn new_resource_id_is_kept_in_book_state() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // The API key comes from the credential store when REMOTE_API_KEY isn't set
+        let credentials = credentials::FileCredentialStore {
+            path: dir.path().join("credentials.json"),
+        };
+        credentials.set(API_KEY, "stored-key").unwrap();
+
+        let put_mock = mock("PUT", "/new-book?apiKey=stored-key")
+            .with_status(404)
+            .create();
+        let post_mock = mock("POST", "/new-book?apiKey=stored-key")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"uri":"https://api.jsonstorage.net/v1/json/created-id"}"#)
+            .create();
+
+        let state = RemoteState {
+            path: dir.path().join("books/work/remote.json"),
+        };
+        let storage = RemoteStorage {
+            medium: "remote".to_string(),
+            base_url: Some(format!("{}/new-book", server_url())),
+            resource_id: RefCell::new(None),
+            api_key: None,
+            credentials: Some(Box::new(credentials)),
+            state: Some(state),
+            active_url: RefCell::new(None),
+        };
+
+        storage.save(&HashMap::new()).unwrap();
+
+        put_mock.assert();
+        post_mock.assert();
+        let state = RemoteState {
+            path: dir.path().join("books/work/remote.json"),
+        };
+        assert_eq!(state.resource_id().unwrap().as_deref(), Some("created-id"));
+        assert_eq!(
+            storage.resource_id().unwrap().as_deref(),
+            Some("created-id")
+        );
+    }
+
+    #[test]
+    fn unreadable_remote_state_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("books/work/remote.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        let storage = RemoteStorage {
+            medium: "remote".to_string(),
+            base_url: Some(format!("{}/new-book", server_url())),
+            resource_id: RefCell::new(None),
+            api_key: Some("key".to_string()),
+            credentials: None,
+            state: Some(RemoteState { path }),
+            active_url: RefCell::new(None),
+        };
+
+        // Falling back to a post would create a second resource
+        assert!(storage.put_req_url().is_err());
+        assert!(storage.get_req_url().is_err());
+    }
 }
@@ -0,0 +1,289 @@
+use super::*;
+
+use crate::domain::clock::{self, Hlc};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the files this build writes.
+///
+/// 1. json: a list of contacts. txt: no header.
+/// 2. json: an object of contacts keyed by id.
+/// 3. json: `{"schema_version": 3, "contacts": {..}}`. txt: a `schema_version: 3` first line.
+///    bin: the version is in the file header. jsonl: no header, one contact per line.
+///    events: the version is in the checkpoint; log lines have none.
+/// 4. Every contact has a clock. Contacts from older files get one seeded from
+///    their `updated_at`, kept once the book is saved.
+/// 5. jsonl: a `{"schema_version": 5}` first line. events: every log line has a
+///    `schema_version`. Files and lines without one are read as schema 3.
+pub const CURRENT_VERSION: u32 = 5;
+
+/// Version of jsonl files and event log lines written before they carried one.
+pub const UNVERSIONED_LINES: u32 = 3;
+
+/// Key of the version in json files, in the first line of txt and jsonl files
+/// and in event log lines.
+pub const VERSION_KEY: &str = "schema_version";
+
+/// One step of the upgrade path, from `from` to `from + 1`.
+pub struct Migration {
+    pub from: u32,
+    pub description: &'static str,
+    /// Takes the contacts as written at version `from`.
+    pub apply: fn(Value) -> Result<Value, AppError>,
+}
+
+/// Every upgrade step, in order. Add one here whenever a format changes.
+pub const MIGRATIONS: [Migration; 4] = [
+    Migration {
+        from: 1,
+        description: "Key the contact list by id, giving contacts without one a new id",
+        apply: list_to_map,
+    },
+    Migration {
+        from: 2,
+        description: "Add the schema version header; contacts are unchanged",
+        apply: Ok,
+    },
+    Migration {
+        from: 3,
+        description: "Give contacts without a clock one seeded from their updated_at",
+        apply: seed_clocks,
+    },
+    Migration {
+        from: 4,
+        description: "Add the version header to jsonl files and a version to event log lines; contacts are unchanged",
+        apply: Ok,
+    },
+];
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    schema_version: u32,
+    contacts: &'a HashMap<Uuid, Contact>,
+}
+
+#[derive(Deserialize)]
+struct OwnedEnvelope {
+    schema_version: u32,
+    contacts: HashMap<Uuid, Contact>,
+}
+
+/// Refuses files written by a newer build, which may hold data this one would drop on save.
+pub fn check_version(version: u32, location: &str) -> Result<(), AppError> {
+    if version > CURRENT_VERSION {
+        return Err(AppError::Validation(format!(
+            "'{location}' uses storage schema {version}, but this rolodex only reads up to {CURRENT_VERSION}. Upgrade rolodex to open it"
+        )));
+    }
+    if version == 0 {
+        return Err(AppError::Validation(format!(
+            "'{location}' has an invalid storage schema version 0"
+        )));
+    }
+    Ok(())
+}
+
+/// Brings contacts written at `version` up to the current schema.
+pub fn upgrade(
+    mut contacts: Value,
+    version: u32,
+    location: &str,
+) -> Result<HashMap<Uuid, Contact>, AppError> {
+    check_version(version, location)?;
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+        contacts = (migration.apply)(contacts)?;
+    }
+    Ok(serde_json::from_value(contacts)?)
+}
+
+/// Brings a contact read at `version` by a format other than json up to the
+/// current schema. Those formats keep their own layout, so only the steps that
+/// change the contact itself apply.
+pub fn upgrade_contact(contact: &mut Contact, version: u32) {
+    if version < 4 && contact.clock.is_unset() {
+        contact.clock = Hlc::seeded(contact.updated_at);
+    }
+}
+
+/// Reads json contacts of any known version.
+pub fn read_json(data: &str, location: &str) -> Result<HashMap<Uuid, Contact>, AppError> {
+    // serde_json will give an error if data is empty
+    if data.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Current files deserialize straight into contacts; only older ones take
+    // the slower pass through `Value` for the migrations
+    if let Ok(envelope) = serde_json::from_str::<OwnedEnvelope>(data)
+        && envelope.schema_version == CURRENT_VERSION
+    {
+        return Ok(envelope.contacts);
+    }
+
+    let (version, contacts) =
+        match serde_json::from_str(data)? {
+            Value::Object(mut document) if document.contains_key(VERSION_KEY) => {
+                let version = document.get(VERSION_KEY).and_then(|v| v.as_u64()).ok_or(
+                    AppError::Validation(format!("'{location}' has an unreadable schema version")),
+                )?;
+                let contacts = document
+                    .remove("contacts")
+                    .unwrap_or(Value::Object(Default::default()));
+                (version as u32, contacts)
+            }
+            contacts @ Value::Array(_) => (1, contacts),
+            contacts @ Value::Object(_) => (2, contacts),
+            _ => {
+                return Err(AppError::Validation(
+                    "Invalid JSON structure: expected object or array".to_string(),
+                ));
+            }
+        };
+
+    upgrade(contacts, version, location)
+}
+
+/// Contacts as a current-version json document.
+pub fn to_json(contacts: &HashMap<Uuid, Contact>) -> Result<String, AppError> {
+    Ok(serde_json::to_string(&Envelope {
+        schema_version: CURRENT_VERSION,
+        contacts,
+    })?)
+}
+
+/// The header line written at the top of txt files.
+pub fn txt_header() -> String {
+    format!("{VERSION_KEY}: {CURRENT_VERSION}\n")
+}
+
+/// The version in a txt header line, or `None` if the line isn't a header.
+pub fn parse_txt_header(line: &str) -> Option<Result<u32, AppError>> {
+    let (key, value) = line.split_once(':')?;
+    if key.trim() != VERSION_KEY {
+        return None;
+    }
+    Some(
+        value.trim().parse().map_err(|_| {
+            AppError::Validation(format!("Unreadable schema version '{}'", value.trim()))
+        }),
+    )
+}
+
+/// The header line written at the top of jsonl files.
+pub fn jsonl_header() -> String {
+    format!("{{\"{VERSION_KEY}\":{CURRENT_VERSION}}}\n")
+}
+
+/// The version in a jsonl header line, or `None` if the line is a contact.
+pub fn parse_jsonl_header(line: &str) -> Option<Result<u32, AppError>> {
+    let Ok(Value::Object(header)) = serde_json::from_str(line) else {
+        return None;
+    };
+    let version = header.get(VERSION_KEY)?;
+    Some(
+        version
+            .as_u64()
+            .map(|version| version as u32)
+            .ok_or(AppError::Validation(format!(
+                "Unreadable schema version '{version}'"
+            ))),
+    )
+}
+
+fn seed_clocks(contacts: Value) -> Result<Value, AppError> {
+    let mut contacts: HashMap<Uuid, Contact> = serde_json::from_value(contacts)?;
+    clock::seed_clocks(&mut contacts);
+    Ok(serde_json::to_value(contacts)?)
+}
+
+fn list_to_map(contacts: Value) -> Result<Value, AppError> {
+    let Value::Array(list) = contacts else {
+        return Ok(contacts);
+    };
+
+    let mut map = serde_json::Map::new();
+    for mut contact in list {
+        let id = match contact.get("id").and_then(|id| id.as_str()) {
+            Some(id) => id.to_string(),
+            None => {
+                let id = Uuid::new_v4().to_string();
+                if let Some(fields) = contact.as_object_mut() {
+                    fields.insert("id".to_string(), Value::String(id.clone()));
+                }
+                id
+            }
+        };
+        map.insert(id, contact);
+    }
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_json_version() -> Result<(), AppError> {
+        let list = r#"[{"name":"Ada","phone":"08012345678","email":"","tag":""}]"#;
+        let contacts = read_json(list, "list.json")?;
+        let ada = contacts.values().next().unwrap().clone();
+        assert_eq!(ada.name, "Ada");
+
+        let map = serde_json::to_string(&contacts)?;
+        assert_eq!(read_json(&map, "map.json")?, contacts);
+
+        let current = to_json(&contacts)?;
+        assert!(current.starts_with(r#"{"schema_version":5"#));
+        assert_eq!(read_json(&current, "current.json")?, contacts);
+        Ok(())
+    }
+
+    #[test]
+    fn seeds_the_clocks_of_older_files() -> Result<(), AppError> {
+        let mut ada = Contact::new(
+            "Ada".to_string(),
+            "08012345678".to_string(),
+            "".to_string(),
+            "".to_string(),
+        );
+        ada.clock = Hlc::default();
+        let v3 = format!(
+            r#"{{"schema_version":3,"contacts":{}}}"#,
+            serde_json::to_string(&HashMap::from([(ada.id, ada.clone())]))?
+        );
+
+        let contacts = read_json(&v3, "v3.json")?;
+        assert_eq!(contacts[&ada.id].clock, Hlc::seeded(ada.updated_at));
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let newer = format!(
+            r#"{{"schema_version":{},"contacts":{{}}}}"#,
+            CURRENT_VERSION + 1
+        );
+        let err = read_json(&newer, "future.json").unwrap_err();
+        assert!(err.to_string().contains("Upgrade rolodex"));
+
+        assert!(matches!(parse_txt_header("schema_version: 9"), Some(Ok(9))));
+        assert!(parse_txt_header("name: Ada").is_none());
+
+        assert!(matches!(
+            parse_jsonl_header(&jsonl_header()),
+            Some(Ok(CURRENT_VERSION))
+        ));
+        assert!(parse_jsonl_header(r#"{"schema_version":"five"}"#).is_some_and(|v| v.is_err()));
+        assert!(parse_jsonl_header(r#"{"name":"Ada"}"#).is_none());
+    }
+
+    #[test]
+    fn migrations_are_ordered_and_end_at_current() {
+        for (step, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.from, step as u32 + 1);
+        }
+        assert_eq!(MIGRATIONS.len() as u32 + 1, CURRENT_VERSION);
+    }
+}
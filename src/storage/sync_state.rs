@@ -0,0 +1,342 @@
+use super::*;
+
+use crate::config::data_dir;
+use crate::domain::sync::SyncConflict;
+use blake2::{Blake2s256, Digest};
+use std::time::Duration;
+
+const CONFLICTS_FILE: &str = "conflicts.json";
+
+/// Where a book keeps its sync snapshots and conflict queue, and the device id
+/// its changes are stamped with. Without a directory nothing is kept between
+/// runs, e.g. for in-memory books.
+#[derive(Debug, Clone)]
+pub struct SyncState {
+    pub dir: Option<PathBuf>,
+    pub device: String,
+}
+
+/// The last-synced copy of a remote's contacts.
+///
+/// A snapshot is kept per remote (keyed by the storage location) and is used as
+/// the common ancestor in a three-way merge on the next sync with that remote.
+pub struct SyncSnapshot {
+    /// `None` when the book keeps no sync state
+    pub path: Option<PathBuf>,
+}
+
+/// Sync conflicts waiting for the user to pick a side, shared by all remotes.
+pub struct ConflictQueue {
+    /// `None` when the book keeps no sync state
+    pub path: Option<PathBuf>,
+}
+
+impl SyncState {
+    pub fn new(dir: Option<PathBuf>, device: &str) -> Self {
+        Self {
+            dir,
+            device: device.to_string(),
+        }
+    }
+
+    /// The snapshot of the remote at `location`.
+    pub fn snapshot(&self, location: &str) -> SyncSnapshot {
+        let file_name = format!("{}.json", snapshot_key(location));
+        SyncSnapshot {
+            path: self.dir.as_ref().map(|dir| dir.join(file_name)),
+        }
+    }
+
+    /// Loads the snapshot of every remote synced with so far.
+    pub fn snapshots(
+        &self,
+        book: &dyn ContactStore,
+    ) -> Result<Vec<HashMap<Uuid, Contact>>, AppError> {
+        self.snapshots_within(None, book)
+    }
+
+    /// Loads the snapshots of the remotes synced with in the last `days` days.
+    /// Older ones are of remotes that are gone or were only written to once.
+    pub fn recent_snapshots(
+        &self,
+        days: i64,
+        book: &dyn ContactStore,
+    ) -> Result<Vec<HashMap<Uuid, Contact>>, AppError> {
+        let max_age = Duration::from_secs(days.max(0) as u64 * 24 * 60 * 60);
+        self.snapshots_within(Some(max_age), book)
+    }
+
+    fn snapshots_within(
+        &self,
+        max_age: Option<Duration>,
+        book: &dyn ContactStore,
+    ) -> Result<Vec<HashMap<Uuid, Contact>>, AppError> {
+        let mut snapshots = Vec::new();
+        for path in self.files()? {
+            if path.file_name().is_some_and(|name| name == CONFLICTS_FILE) {
+                continue;
+            }
+            // A time in the future counts as just synced
+            let age = fs::metadata(&path)?
+                .modified()?
+                .elapsed()
+                .unwrap_or_default();
+            if max_age.is_none_or(|max_age| age <= max_age) {
+                snapshots.push(SyncSnapshot { path: Some(path) }.load(book)?);
+            }
+        }
+        Ok(snapshots)
+    }
+
+    pub fn conflicts(&self) -> ConflictQueue {
+        ConflictQueue {
+            path: self.dir.as_ref().map(|dir| dir.join(CONFLICTS_FILE)),
+        }
+    }
+
+    /// Rewrites the snapshots and the conflict queue sealed by `from` so they
+    /// are sealed by `to`, e.g. when the book gets encrypted or its
+    /// passphrase changes.
+    pub fn reseal(&self, from: &dyn ContactStore, to: &dyn ContactStore) -> Result<(), AppError> {
+        for path in self.files()? {
+            let data = read_state(&path, from)?;
+            write_state(&path, &data, to)?;
+        }
+        Ok(())
+    }
+
+    /// The snapshot and conflict files kept so far.
+    fn files(&self) -> Result<Vec<PathBuf>, AppError> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        if !fs::exists(dir)? {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl SyncSnapshot {
+    /// Returns an empty map if no snapshot was taken yet for this remote.
+    pub fn load(&self, book: &dyn ContactStore) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let Some(path) = &self.path else {
+            return Ok(HashMap::new());
+        };
+        if !fs::exists(path)? {
+            return Ok(HashMap::new());
+        }
+
+        let data = read_state(path, book)?;
+        if data.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(
+        &self,
+        contacts: &HashMap<Uuid, Contact>,
+        book: &dyn ContactStore,
+    ) -> Result<(), AppError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_state(path, &serde_json::to_vec(contacts)?, book)
+    }
+}
+
+impl ConflictQueue {
+    pub fn load(&self, book: &dyn ContactStore) -> Result<Vec<SyncConflict>, AppError> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        if !fs::exists(path)? {
+            return Ok(Vec::new());
+        }
+
+        let data = read_state(path, book)?;
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    pub fn save(
+        &self,
+        conflicts: &[SyncConflict],
+        book: &dyn ContactStore,
+    ) -> Result<(), AppError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_state(path, &serde_json::to_vec(conflicts)?, book)
+    }
+
+    /// Adds new conflicts, replacing any older entry for the same contact and source.
+    pub fn push(
+        &self,
+        conflicts: Vec<SyncConflict>,
+        book: &dyn ContactStore,
+    ) -> Result<(), AppError> {
+        if conflicts.is_empty() {
+            return Ok(());
+        }
+
+        let mut queue = self.load(book)?;
+        queue.retain(|queued| {
+            !conflicts
+                .iter()
+                .any(|c| c.id == queued.id && c.source == queued.source)
+        });
+        queue.extend(conflicts);
+
+        self.save(&queue, book)
+    }
+}
+
+/// Reads a state file the way `book` sealed it.
+fn read_state(path: &Path, book: &dyn ContactStore) -> Result<Vec<u8>, AppError> {
+    let data = fs::read(path)?;
+    if data.is_empty() {
+        return Ok(data);
+    }
+    book.unseal(&data, &path.to_string_lossy())
+}
+
+/// Writes a state file sealed by `book`, so an encrypted book's state is
+/// encrypted too.
+fn write_state(path: &Path, data: &[u8], book: &dyn ContactStore) -> Result<(), AppError> {
+    create_file_parent(&path.to_string_lossy())?;
+    fs::write(path, book.seal(data)?)?;
+    Ok(())
+}
+
+/// Id of this installation, stamped on the clocks of local changes.
+/// Created on first use, shared by every book.
+pub fn device_id() -> Result<String, AppError> {
+    device_id_in(&sync_state_base_dir())
+}
+
+/// The device id kept in `dir`, created on first use.
+pub fn device_id_in(dir: &Path) -> Result<String, AppError> {
+    let path = dir.join("device_id");
+
+    if let Ok(id) = fs::read_to_string(&path)
+        && !id.trim().is_empty()
+    {
+        return Ok(id.trim().to_string());
+    }
+
+    let id = new_device_id();
+    create_file_parent(&path.to_string_lossy())?;
+    fs::write(&path, &id)?;
+    Ok(id)
+}
+
+/// A fresh random device id.
+pub fn new_device_id() -> String {
+    Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+/// Snapshots and conflicts of the active book.
+pub fn sync_state_dir(config: &Config) -> PathBuf {
+    match books::active_book(config) {
+        Ok(book) => book_state_dir(&book),
+        Err(_) => sync_state_base_dir(),
+    }
+}
+
+/// Snapshots and conflicts of `book`. Named books keep theirs under `books/<name>`.
+pub fn book_state_dir(book: &str) -> PathBuf {
+    if book == books::DEFAULT_BOOK {
+        return sync_state_base_dir();
+    }
+    sync_state_base_dir().join("books").join(book)
+}
+
+/// `SYNC_STATE_DIR`, else `sync` under the data dir, so state never lands in
+/// the working directory.
+fn sync_state_base_dir() -> PathBuf {
+    match env::var("SYNC_STATE_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => data_dir().join("sync"),
+    }
+}
+
+/// Turns a storage location into a file name: the last part of the location,
+/// to tell the files apart at a glance, and a hash of the whole location, so
+/// no two remotes share a snapshot. Query strings are dropped so credentials
+/// in remote URLs never end up on disk.
+fn snapshot_key(location: &str) -> String {
+    let location = absolute_location(location.split('?').next().unwrap_or_default());
+
+    let name: String = location
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .take(40)
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash: String = Blake2s256::digest(location.as_bytes())[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{name}-{hash}")
+}
+
+/// A file location as an absolute path, so a file is the same remote from any
+/// working dir. URLs are kept as they are.
+fn absolute_location(location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    fs::canonicalize(location)
+        .or_else(|_| std::path::absolute(location))
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| location.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_key_strips_the_query() {
+        let key = snapshot_key("https://api.example.com/v1/json/abc?apiKey=secret");
+        assert!(key.starts_with("abc-"), "{key}");
+        assert!(!key.contains("secret"));
+        assert_eq!(key, snapshot_key("https://api.example.com/v1/json/abc"));
+    }
+
+    #[test]
+    fn snapshot_keys_are_distinct_per_absolute_location() -> Result<(), AppError> {
+        assert_ne!(snapshot_key("/a/b_c.json"), snapshot_key("/a_b/c.json"));
+        assert!(snapshot_key("/a/b_c.json").starts_with("b_c.json-"));
+
+        let cwd = std::env::current_dir()?;
+        assert_eq!(
+            snapshot_key("./csv/contacts.csv"),
+            snapshot_key(&cwd.join("csv/contacts.csv").to_string_lossy())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use super::*;
+
+use chrono::{DateTime, Utc};
+use compression::Compression;
+use import::{SourceFormat, SourceRecord, SourceRecords};
+
+/// Writes `contact` as a vCard 3.0. Fields vCard has no property for are kept
+/// in `X-ROLODEX-*` properties so the card reads back unchanged.
+pub fn to_vcard(contact: &Contact) -> String {
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:3.0".to_string(),
+        format!("UID:{}", contact.id),
+        format!("FN:{}", escape(&contact.name)),
+        format!("N:{};;;;", escape(&contact.name)),
+        format!("TEL:{}", escape(&contact.phone)),
+    ];
+    if !contact.email.is_empty() {
+        lines.push(format!("EMAIL:{}", escape(&contact.email)));
+    }
+    if !contact.tag.is_empty() {
+        lines.push(format!("CATEGORIES:{}", escape(&contact.tag)));
+    }
+    lines.push(format!("REV:{}", contact.updated_at.to_rfc3339()));
+    lines.push(format!(
+        "X-ROLODEX-CREATED:{}",
+        contact.created_at.to_rfc3339()
+    ));
+    if !contact.clock.is_unset() {
+        lines.push(format!("X-ROLODEX-CLOCK:{}", contact.clock));
+    }
+    if contact.deleted {
+        let deleted_at = contact
+            .deleted_at
+            .map(|at| at.to_rfc3339())
+            .unwrap_or_default();
+        lines.push(format!("X-ROLODEX-DELETED:{deleted_at}"));
+    }
+    if let Some(device) = &contact.deleted_by {
+        lines.push(format!("X-ROLODEX-DELETED-BY:{}", escape(device)));
+    }
+    lines.push("END:VCARD".to_string());
+
+    let mut card = lines.join("\r\n");
+    card.push_str("\r\n");
+    card
+}
+
+/// A `.vcf` file of contacts, for imports and exports.
+pub struct VcardStorage {
+    pub medium: String,
+    pub path: String,
+    /// How the file is written. Compressed files are always read.
+    pub compression: Compression,
+}
+
+impl VcardStorage {
+    pub fn at(path: &str) -> Self {
+        Self {
+            medium: "vcf".to_string(),
+            path: path.to_string(),
+            compression: Compression::from_path(path).unwrap_or_default(),
+        }
+    }
+}
+
+impl ContactStore for VcardStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        if !fs::exists(&self.path)? {
+            return Ok(HashMap::new());
+        }
+        let cards = parse_vcards(&compression::read_to_string(&self.path)?, &self.path)?;
+        Ok(cards.into_iter().map(|c| (c.id, c)).collect())
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        let mut contacts: Vec<&Contact> = contacts.values().collect();
+        contacts.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        let data: String = contacts.into_iter().map(to_vcard).collect();
+        compression::write(&self.path, data.as_bytes(), self.compression)
+    }
+
+    fn get_medium(&self) -> &str {
+        &self.medium
+    }
+
+    fn get_location(&self) -> String {
+        self.path.clone()
+    }
+
+    fn source_records(&self) -> Result<Option<SourceRecords>, AppError> {
+        let data = compression::read_to_string(&self.path)?;
+        let records = read_cards(&data)
+            .into_iter()
+            .map(|card| SourceRecord {
+                position: format!("line {}", card.line),
+                raw: card.raw,
+                contact: card.contact,
+            })
+            .collect();
+        Ok(Some(SourceRecords {
+            format: SourceFormat::Vcard,
+            header: None,
+            records,
+        }))
+    }
+}
+
+/// One card of a vCard file.
+pub struct Card {
+    /// The line its `BEGIN:VCARD` is on
+    pub line: usize,
+    /// The card as written, line endings included
+    pub raw: String,
+    pub contact: Result<Contact, String>,
+}
+
+/// Reads every card in `data`, failing at the first card that can't be read.
+/// Cards from other programs work too: the name comes from `FN` (else `N`),
+/// and the first `TEL` and `EMAIL` are used.
+pub fn parse_vcards(data: &str, location: &str) -> Result<Vec<Contact>, AppError> {
+    read_cards(data)
+        .into_iter()
+        .map(|card| {
+            card.contact.map_err(|err| {
+                AppError::Validation(format!("'{location}' line {}: {err}", card.line))
+            })
+        })
+        .collect()
+}
+
+/// Reads every card in `data`, each with its own result.
+pub fn read_cards(data: &str) -> Vec<Card> {
+    let mut cards = Vec::new();
+    // The open card's first line and its lines so far
+    let mut card: Option<(usize, Vec<&str>)> = None;
+
+    for (number, line) in data.split_inclusive('\n').enumerate() {
+        let property = line.trim_end().to_ascii_uppercase();
+        match card.as_mut() {
+            None if property == "BEGIN:VCARD" => card = Some((number + 1, vec![line])),
+            None => {}
+            Some((_, lines)) => {
+                lines.push(line);
+                if property == "END:VCARD"
+                    && let Some((start, lines)) = card.take()
+                {
+                    let raw = lines.concat();
+                    let contact = card_to_contact(&unfold(&raw));
+                    cards.push(Card {
+                        line: start,
+                        raw,
+                        contact,
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some((start, lines)) = card {
+        cards.push(Card {
+            line: start,
+            raw: lines.concat(),
+            contact: Err("card without END:VCARD".to_string()),
+        });
+    }
+    cards
+}
+
+fn card_to_contact(properties: &[(String, String)]) -> Result<Contact, String> {
+    let get = |name: &str| {
+        properties
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let time = |name: &str| -> Result<Option<DateTime<Utc>>, String> {
+        match get(name) {
+            Some(value) if !value.is_empty() => Ok(Some(parse_time(value)?)),
+            _ => Ok(None),
+        }
+    };
+
+    let name = match get("FN") {
+        Some(name) if !name.is_empty() => unescape(name),
+        _ => {
+            // N is family;given;additional;prefix;suffix
+            let parts: Vec<String> = split_unescaped(get("N").unwrap_or_default(), ';');
+            let given = parts.get(1).cloned().unwrap_or_default();
+            let family = parts.first().cloned().unwrap_or_default();
+            format!("{given} {family}").trim().to_string()
+        }
+    };
+    if name.is_empty() {
+        return Err("card without a name".to_string());
+    }
+
+    let mut contact = Contact::new(
+        name,
+        unescape(get("TEL").unwrap_or_default()),
+        unescape(get("EMAIL").unwrap_or_default()),
+        split_unescaped(get("CATEGORIES").unwrap_or_default(), ',')
+            .first()
+            .cloned()
+            .unwrap_or_default(),
+    );
+    if let Some(id) =
+        get("UID").and_then(|uid| Uuid::parse_str(uid.trim_start_matches("urn:uuid:")).ok())
+    {
+        contact.id = id;
+    }
+    if let Some(updated_at) = time("REV")? {
+        contact.updated_at = updated_at;
+    }
+    if let Some(created_at) = time("X-ROLODEX-CREATED")? {
+        contact.created_at = created_at;
+    }
+    if let Some(clock) = get("X-ROLODEX-CLOCK") {
+        contact.clock = clock
+            .parse()
+            .map_err(|_| "invalid X-ROLODEX-CLOCK".to_string())?;
+    }
+    if get("X-ROLODEX-DELETED").is_some() {
+        contact.deleted = true;
+        contact.deleted_at = time("X-ROLODEX-DELETED")?;
+    }
+    contact.deleted_by = get("X-ROLODEX-DELETED-BY").map(unescape);
+    Ok(contact)
+}
+
+/// Joins folded lines and splits each into an upper-case property name (without
+/// group or parameters) and its raw value.
+fn unfold(data: &str) -> Vec<(String, String)> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in data.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let name = key.split(';').next().unwrap_or_default();
+            let name = name.rsplit('.').next().unwrap_or_default();
+            Some((name.trim().to_ascii_uppercase(), value.to_string()))
+        })
+        .collect()
+}
+
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            // Basic format, as in REV:20240102T030405Z
+            chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+                .map(|time| time.and_utc())
+        })
+        .map_err(|_| format!("invalid time '{value}'"))
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// Splits on `separator` where it isn't escaped, and unescapes the parts.
+fn split_unescaped(value: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                part.push(c);
+                part.extend(chars.next());
+            }
+            c if c == separator => parts.push(unescape(&std::mem::take(&mut part))),
+            c => part.push(c),
+        }
+    }
+    parts.push(unescape(&part));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_contact() -> Result<(), AppError> {
+        let mut contact = Contact::new(
+            "Ada, Obi".to_string(),
+            "08012345678".to_string(),
+            "ada@example.com".to_string(),
+            "work".to_string(),
+        );
+        contact.mark_deleted("device-a");
+
+        let read = parse_vcards(&to_vcard(&contact), "ada.vcf")?;
+        assert_eq!(read.len(), 1);
+        let read = &read[0];
+        assert_eq!(read.id, contact.id);
+        assert_eq!(read.name, "Ada, Obi");
+        assert_eq!(read.tag, "work");
+        assert_eq!(read.deleted_at, contact.deleted_at);
+        assert_eq!(read.deleted_by, contact.deleted_by);
+        assert_eq!(read.clock, contact.clock);
+        Ok(())
+    }
+
+    #[test]
+    fn reads_cards_from_other_programs() -> Result<(), AppError> {
+        let data = "BEGIN:VCARD\nVERSION:4.0\nN:Obi;Ada;;;\nitem1.TEL;TYPE=cell:+2348012\n 345678\nEMAIL;TYPE=work:ada@example.com\nREV:20240102T030405Z\nEND:VCARD\n";
+
+        let read = parse_vcards(data, "export.vcf")?;
+        assert_eq!(read[0].name, "Ada Obi");
+        assert_eq!(read[0].phone, "+2348012345678");
+        assert_eq!(read[0].email, "ada@example.com");
+        assert_eq!(read[0].updated_at.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+
+        assert!(parse_vcards("BEGIN:VCARD\nFN:Ada\n", "cut.vcf").is_err());
+        Ok(())
+    }
+}
@@ -12,7 +12,7 @@ fn add_contact() {
     // Add a contact
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Alice",
@@ -30,7 +30,7 @@ fn add_contact() {
     // Confirm newly added contact exist
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["list"])
+        .args(["list"])
         .assert()
         .success()
         .stdout(predicate::str::contains(listing_format(
@@ -44,7 +44,7 @@ fn add_contact() {
     // Attempt to Add duplicate contacts
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Alice",
@@ -65,7 +65,7 @@ fn add_contact() {
     // Clear memory
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Alice"])
+        .args(["delete", "--name", "Alice"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Contact deleted successfully"));
@@ -76,7 +76,7 @@ fn invalid_inputs() {
     // INVALID COMMAND
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "and",
             "--name",
             "Alice",
@@ -94,7 +94,7 @@ fn invalid_inputs() {
     // INVALID NAME
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "123",
@@ -116,7 +116,7 @@ fn invalid_inputs() {
 
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "A very very very very very very very very very \
@@ -141,7 +141,7 @@ fn invalid_inputs() {
     // INVALID PHONE NUMBER
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Alice",
@@ -162,7 +162,7 @@ fn invalid_inputs() {
     // INVALID EMAIL
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Alice",
@@ -182,7 +182,7 @@ fn invalid_inputs() {
 
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Alice",
@@ -0,0 +1,114 @@
+use assert_cmd::Command;
+use predicates::{prelude::PredicateBooleanExt, str::contains};
+use std::path::Path;
+use tempfile::tempdir;
+
+#[test]
+fn books_keep_contacts_apart() -> Result<(), Box<dyn std::error::Error>> {
+    // Keep every file of this test out of ./.instance
+    let dir = tempdir()?;
+    let path_of = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    let work_path = path_of("work.json");
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+        cmd.env("STORAGE_CHOICE", "json")
+            .env("JSON_STORAGE_PATH", path_of("contacts.json"))
+            .env("TXT_STORAGE_PATH", path_of("contacts.txt"))
+            .env("BOOKS_FILE", path_of("books.json"))
+            .env("SYNC_STATE_DIR", path_of("sync"))
+            .env_remove("ROLODEX_BOOK");
+        Ok(cmd)
+    };
+
+    rolodex()?
+        .args(["book", "create", "work", "--path", &work_path])
+        .assert()
+        .success()
+        .stdout(contains("Book 'work' created"));
+
+    rolodex()?
+        .args(["book", "list"])
+        .assert()
+        .success()
+        .stdout(contains("* default"))
+        .stdout(contains("work"));
+
+    for (name, phone) in [("Bob", "08011112222"), ("Carol", "08033334444")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone])
+            .assert()
+            .success();
+    }
+
+    rolodex()?
+        .args(["copy", "--name", "Bob", "--to", "work"])
+        .assert()
+        .success()
+        .stdout(contains("Contact copied to book 'work'"));
+
+    rolodex()?
+        .args(["move", "--name", "Carol", "--to", "work"])
+        .assert()
+        .success()
+        .stdout(contains("Contact moved to book 'work'"));
+
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("Bob").and(contains("Carol").not()));
+
+    rolodex()?
+        .args(["list", "--book", "work"])
+        .assert()
+        .success()
+        .stdout(contains("Bob").and(contains("Carol")));
+
+    rolodex()?
+        .env("ROLODEX_BOOK", "work")
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("Carol"));
+
+    // The default book setting applies when --book isn't given
+    rolodex()?
+        .args(["book", "default", "work"])
+        .assert()
+        .success();
+
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("Carol"));
+
+    rolodex()?
+        .args(["book", "rename", "work", "office"])
+        .assert()
+        .success();
+
+    rolodex()?
+        .args(["--book", "office", "list"])
+        .assert()
+        .success()
+        .stdout(contains("Carol"));
+
+    rolodex()?
+        .args(["book", "delete", "office", "--purge"])
+        .assert()
+        .success()
+        .stdout(contains("Book 'office' deleted"));
+
+    assert!(!Path::new(&work_path).exists());
+
+    // Back on the default book
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("Bob").and(contains("Carol").not()));
+
+    Ok(())
+}
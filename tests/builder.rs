@@ -0,0 +1,146 @@
+use chrono::{Duration, Utc};
+
+use rusty_rolodex::domain::manager::{IndexOptions, PurgePolicy};
+use rusty_rolodex::prelude::*;
+use rusty_rolodex::storage::file::{JsonStorage, TxtStorage};
+use rusty_rolodex::storage::memory::MemStore;
+
+fn contact(name: &str, email: &str) -> Contact {
+    Contact::new(
+        name.to_string(),
+        "08012345678".to_string(),
+        email.to_string(),
+        "".to_string(),
+    )
+}
+
+fn old_tombstone() -> Contact {
+    let mut deleted = contact("Gone Person", "gone@example.com");
+    deleted.deleted = true;
+    deleted.deleted_at = Some(Utc::now() - Duration::days(10));
+    deleted
+}
+
+#[test]
+fn builder_loads_storage_and_migrates_sources() -> Result<(), AppError> {
+    let main = MemStore::with_contacts([contact("Ada Obi", "ada@example.com")]);
+    let legacy = MemStore::with_contacts([contact("Bola Ade", "bola@work.com")]);
+
+    let manager = ContactManager::builder(Box::new(main))
+        .migrate_from(Box::new(legacy))
+        .build()?;
+
+    let mut names: Vec<&str> = manager
+        .contact_list()
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["Ada Obi", "Bola Ade"]);
+    assert!(manager.get_ids_by_name("Bola Ade").is_some());
+    Ok(())
+}
+
+#[test]
+fn replaced_files_are_removed_once_the_book_is_saved() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+    let ada = contact("Ada Obi", "ada@example.com");
+    let bola = contact("Bola Ade", "bola@work.com");
+    JsonStorage::at(&path("contacts.json")).save(&HashMap::from([(ada.id, ada)]))?;
+    JsonStorage::at(&path("other.json")).save(&HashMap::from([(bola.id, bola)]))?;
+
+    let mut manager = ContactManager::builder(Box::new(TxtStorage::at(&path("contacts.txt"))))
+        .replaces(Box::new(JsonStorage::at(&path("contacts.json"))))
+        .migrate_from(Box::new(JsonStorage::at(&path("other.json"))))
+        .build()?;
+    assert!(dir.path().join("contacts.json").exists());
+
+    manager.save()?;
+    assert!(!dir.path().join("contacts.json").exists());
+    assert!(dir.path().join("other.json").exists());
+    assert_eq!(TxtStorage::at(&path("contacts.txt")).load()?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn purge_policy_decides_if_tombstones_survive_save() -> Result<(), AppError> {
+    let kept = MemStore::with_contacts([old_tombstone()]);
+    ContactManager::builder(Box::new(kept.clone()))
+        .purge(PurgePolicy::Never)
+        .build()?
+        .save()?;
+    assert_eq!(kept.len(), 1);
+
+    let purged = MemStore::with_contacts([old_tombstone()]);
+    ContactManager::builder(Box::new(purged.clone()))
+        .purge(PurgePolicy::OlderThanDays(1))
+        .build()?
+        .save()?;
+    assert!(purged.is_empty());
+    Ok(())
+}
+
+#[test]
+fn sync_state_and_device_come_from_the_builder() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let ada = contact("Ada Obi", "ada@example.com");
+    let id = ada.id;
+
+    let mut manager = ContactManager::builder(Box::new(MemStore::with_contacts([ada.clone()])))
+        .sync_state_dir(dir.path())
+        .device_id("laptop01")
+        .build()?;
+    manager.delete_contact(&id)?;
+    assert_eq!(manager.mem[&id].deleted_by.as_deref(), Some("laptop01"));
+
+    // Tombstones the remote hasn't seen survive the purge
+    let snapshot = manager.sync_state.snapshot("elsewhere");
+    snapshot.save(&HashMap::from([(id, ada)]), manager.storage.as_ref())?;
+    manager.mem.get_mut(&id).unwrap().deleted_at = Some(Utc::now() - Duration::days(10));
+    manager.purge_soft_deleted_older_than(1)?;
+    assert!(manager.mem.contains_key(&id));
+    assert!(
+        snapshot
+            .path
+            .is_some_and(|path| path.starts_with(dir.path()) && path.exists())
+    );
+
+    // A device id is created in the directory when none is given
+    let manager = ContactManager::builder(Box::new(MemStore::new()))
+        .sync_state_dir(dir.path())
+        .build()?;
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("device_id"))?,
+        manager.sync_state.device
+    );
+    Ok(())
+}
+
+#[test]
+fn searches_work_with_indexes_turned_off() -> Result<(), AppError> {
+    let storage = MemStore::with_contacts([
+        contact("Ada Obi", "ada@example.com"),
+        contact("Bola Ade", "bola@work.com"),
+    ]);
+
+    let mut manager = ContactManager::builder(Box::new(storage))
+        .index(IndexOptions {
+            name: false,
+            email_domain: false,
+        })
+        .build()?;
+    manager.add_contact(contact("Chidi Eze", "chidi@work.com"));
+
+    assert!(manager.index.name.is_empty());
+    assert!(manager.index.domain.is_empty());
+    assert_eq!(
+        manager.get_ids_by_name("chidi eze").map(|ids| ids.len()),
+        Some(1)
+    );
+    assert_eq!(
+        manager.fuzzy_search_email_domain_index("work.com")?.len(),
+        2
+    );
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use assert_cmd::Command;
+use rusty_rolodex::config::CONFIG_KEYS;
+use std::path::Path;
+
+/// Env vars read besides the settings in `CONFIG_KEYS`.
+const OTHER_VARS: [&str; 5] = [
+    "ROLODEX_BOOK",
+    "BOOKS_FILE",
+    "SYNC_STATE_DIR",
+    "ROLODEX_PASSPHRASE",
+    "ROLODEX_NEW_PASSPHRASE",
+];
+
+/// The rolodex binary run in `project`, with its config and data dirs under
+/// `xdg`. Every file it writes lives in those dirs, the book under
+/// ./.instance of the working dir. Settings left in the environment are
+/// cleared, so the defaults apply unless the test sets them.
+pub fn rolodex(project: &Path, xdg: &Path) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))?;
+    cmd.current_dir(project)
+        .env("XDG_CONFIG_HOME", xdg.join("config"))
+        .env("XDG_DATA_HOME", xdg.join("data"));
+    for (_, var) in CONFIG_KEYS {
+        cmd.env_remove(var);
+    }
+    for var in OTHER_VARS {
+        cmd.env_remove(var);
+    }
+    Ok(cmd)
+}
@@ -0,0 +1,60 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn compressed_books_and_exports() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let book = project_dir.path().join(".instance").join("contacts.json");
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&book)?.contains("Ada Obi"));
+
+    // The setting compresses the book on its next save; it still reads either way
+    rolodex()?
+        .args(["config", "set", "storage.compression", "zstd"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["add", "--name", "Bola Ade", "--phone", "08087654321"])
+        .assert()
+        .success();
+    assert!(fs::read(&book)?.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]));
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"));
+
+    // Exports and imports pick the compression from the extension
+    for name in ["out.json.gz", "out.csv.gz", "out.vcf.gz", "out.ldif.gz"] {
+        let out = project_dir.path().join(name);
+        rolodex()?
+            .args(["export", "-t", "f", "-d"])
+            .arg(&out)
+            .assert()
+            .success();
+        assert!(fs::read(&out)?.starts_with(&[0x1f, 0x8b]));
+
+        rolodex()?
+            .args(["import", "-f", "f", "--dry-run", "-s"])
+            .arg(&out)
+            .assert()
+            .success();
+    }
+
+    rolodex()?
+        .args(["config", "set", "storage.compression", "bzip"])
+        .assert()
+        .failure();
+    Ok(())
+}
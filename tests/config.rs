@@ -0,0 +1,121 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn config_layers_files_env_and_flags() -> Result<(), Box<dyn std::error::Error>> {
+    // The user config lives under XDG_CONFIG_HOME, the project config in the working dir
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args(["config", "set", "purge_days", "30"])
+        .assert()
+        .success()
+        .stdout(contains("Set purge_days"));
+
+    assert!(xdg_dir.path().join("config/rolodex/config.toml").exists());
+
+    rolodex()?
+        .args(["config", "get", "purge_days"])
+        .assert()
+        .success()
+        .stdout("30\n");
+
+    // The project file wins over the user file
+    rolodex()?
+        .args(["config", "set", "purge_days", "7", "--project"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["config", "set", "storage.choice", "txt", "--project"])
+        .assert()
+        .success();
+
+    rolodex()?
+        .args(["config", "list"])
+        .assert()
+        .success()
+        .stdout(contains("purge_days = 7"))
+        .stdout(contains("storage.choice = txt"));
+
+    // Env vars win over files, flags win over env vars
+    rolodex()?
+        .env("PURGE_DAYS", "5")
+        .args(["config", "get", "purge_days"])
+        .assert()
+        .success()
+        .stdout("5\n");
+
+    // A malformed override is reported and the file's value kept
+    rolodex()?
+        .env("PURGE_DAYS", "abc")
+        .args(["config", "get", "purge_days"])
+        .assert()
+        .success()
+        .stdout("7\n")
+        .stderr(contains("PURGE_DAYS"));
+
+    rolodex()?
+        .env("STORAGE_CHOICE", "txt")
+        .args([
+            "--storage-choice",
+            "json",
+            "config",
+            "get",
+            "storage.choice",
+        ])
+        .assert()
+        .success()
+        .stdout("json\n");
+
+    rolodex()?
+        .args(["config", "set", "purge_days", "soon"])
+        .assert()
+        .failure();
+
+    rolodex()?
+        .args(["config", "get", "colour"])
+        .assert()
+        .failure();
+
+    assert!(fs::read_to_string(project_dir.path().join("rolodex.toml"))?.contains("[storage]"));
+
+    Ok(())
+}
+
+#[test]
+fn state_goes_to_the_data_dir_and_dotenv_supplies_settings()
+-> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    fs::write(project_dir.path().join(".env"), "PURGE_DAYS=3\n")?;
+    rolodex()?
+        .args(["config", "get", "purge_days"])
+        .assert()
+        .success()
+        .stdout("3\n");
+
+    rolodex()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    fs::write(project_dir.path().join("shared.json"), "[]")?;
+    rolodex()?
+        .args(["sync", "--with", "f", "--src", "./shared.json"])
+        .assert()
+        .success();
+
+    let sync_dir = xdg_dir.path().join("data/rolodex/sync");
+    assert!(sync_dir.join("device_id").exists());
+    assert!(fs::read_dir(&sync_dir)?.count() > 1, "no snapshot written");
+    assert!(!project_dir.path().join(".instance/sync").exists());
+    Ok(())
+}
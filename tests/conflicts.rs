@@ -0,0 +1,226 @@
+use predicates::{prelude::*, str::contains};
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn queue_and_resolve_conflict() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let book_path = project_dir.path().join("book.json");
+    let book_path_str = book_path.to_string_lossy().to_string();
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args([
+            "add",
+            "--name",
+            "Bob",
+            "--phone",
+            "08011112222",
+            "--tag",
+            "gym",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("Contact added successfully"));
+
+    // Sync records the common ancestor for the next import
+    fs::write(&book_path, "[]")?;
+    rolodex()?
+        .args(["sync", "--with", "f", "--src", &book_path_str])
+        .assert()
+        .success()
+        .stdout(contains("Pushed: 1 added"));
+
+    // Both sides change the tag
+    rolodex()?
+        .args([
+            "edit",
+            "--name",
+            "Bob",
+            "--phone",
+            "08011112222",
+            "--new-tag",
+            "work",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("Contact updated successfully"));
+
+    let exported = fs::read_to_string(&book_path)?;
+    fs::write(&book_path, exported.replace("\"gym\"", "\"family\""))?;
+
+    rolodex()?
+        .args(["import", "--from", "f", "--src", &book_path_str])
+        .args(["--policy", "field-level-merge"])
+        .assert()
+        .success()
+        .stdout(contains("1 conflict(s) need your attention"));
+
+    rolodex()?
+        .arg("conflicts")
+        .assert()
+        .success()
+        .stdout(contains("Bob"))
+        .stdout(contains("fields: tag"));
+
+    // Interactive resolution: take the remote side
+    rolodex()?
+        .args(["conflicts", "resolve", "1"])
+        .write_stdin("r\n")
+        .assert()
+        .success()
+        .stdout(contains("* tag        work"))
+        .stdout(contains("Conflict resolved"));
+
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("family"));
+
+    rolodex()?
+        .arg("conflicts")
+        .assert()
+        .success()
+        .stdout(contains("No conflicts"));
+
+    Ok(())
+}
+
+#[test]
+fn merge_resolution_needs_a_conflict_number() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let remote = project_dir.path().join("remote.json");
+    let remote_str = remote.to_string_lossy().to_string();
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    for (name, phone) in [("Bob", "08011112222"), ("Ann", "08033334444")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone, "--tag", "gym"])
+            .assert()
+            .success();
+    }
+    rolodex()?
+        .args(["export", "--to", "f", "--des", &remote_str])
+        .assert()
+        .success();
+    let exported = fs::read_to_string(&remote)?;
+    fs::write(&remote, exported.replace("\"gym\"", "\"family\""))?;
+
+    rolodex()?
+        .args([
+            "import",
+            "--from",
+            "f",
+            "--src",
+            &remote_str,
+            "--policy",
+            "manual",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("2 conflict(s) need your attention"));
+
+    // Without a number the merged fields would land on both contacts
+    rolodex()?
+        .args(["conflicts", "resolve", "--take", "merge", "--tag", "work"])
+        .assert()
+        .failure()
+        .stderr(contains("need the number of a conflict"));
+    rolodex()?
+        .args(["conflicts", "resolve", "--take", "local", "--name", "Carl"])
+        .assert()
+        .failure();
+
+    rolodex()?
+        .args([
+            "conflicts",
+            "resolve",
+            "1",
+            "--take",
+            "merge",
+            "--tag",
+            "work",
+        ])
+        .assert()
+        .success()
+        .stdout(contains("Conflict resolved"));
+    rolodex()?
+        .args(["conflicts"])
+        .assert()
+        .success()
+        .stdout(contains("  1. "))
+        .stdout(contains("  2. ").not());
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("work"));
+
+    Ok(())
+}
+
+#[test]
+fn first_import_takes_the_last_write_without_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let remote = project_dir.path().join("remote.json");
+    let remote_str = remote.to_string_lossy().to_string();
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args([
+            "add",
+            "--name",
+            "Bob",
+            "--phone",
+            "08011112222",
+            "--tag",
+            "gym",
+        ])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["export", "--to", "f", "--des", &remote_str])
+        .assert()
+        .success();
+    rolodex()?
+        .args([
+            "edit",
+            "--name",
+            "Bob",
+            "--phone",
+            "08011112222",
+            "--new-tag",
+            "work",
+        ])
+        .assert()
+        .success();
+
+    // No sync with the file yet, so there is no ancestor to merge against
+    rolodex()?
+        .args(["import", "--from", "f", "--src", &remote_str])
+        .assert()
+        .success()
+        .stdout(contains("Contacts imported successfully"))
+        .stdout(contains("conflict(s)").not());
+    rolodex()?
+        .arg("conflicts")
+        .assert()
+        .success()
+        .stdout(contains("No conflicts"));
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("work"));
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn imports_csv_from_other_programs() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    // Detected from the header
+    let google = project_dir.path().join("google.csv");
+    fs::write(
+        &google,
+        "Name,Given Name,Family Name,Group Membership,E-mail 1 - Value,Phone 1 - Value\n\
+         Ada Obi,Ada,Obi,* myContacts ::: Work,ada@example.com,+1 555-123-4567\n",
+    )?;
+    rolodex()?
+        .args(["import", "--from", "f", "--src"])
+        .arg(&google)
+        .assert()
+        .success();
+
+    // Named explicitly, with short rows
+    let outlook = project_dir.path().join("outlook.csv");
+    fs::write(
+        &outlook,
+        "First Name,Last Name,E-mail Address,Mobile Phone,Categories\nChloé,Ade,,(555) 987 6543\n",
+    )?;
+    rolodex()?
+        .args(["import", "--from", "f", "--csv-dialect", "outlook", "--src"])
+        .arg(&outlook)
+        .assert()
+        .success();
+
+    // Anything else through a mapping file
+    let custom = project_dir.path().join("staff.csv");
+    fs::write(&custom, "Surname,Forename,Cell\nEze,Chidi,0805.555.5555\n")?;
+    let mapping = project_dir.path().join("staff.toml");
+    fs::write(
+        &mapping,
+        "name_parts = [\"Forename\", \"Surname\"]\nphone = \"Cell\"\n",
+    )?;
+    rolodex()?
+        .args(["import", "--from", "f", "--src"])
+        .arg(&custom)
+        .arg("--csv-mapping")
+        .arg(&mapping)
+        .assert()
+        .success();
+
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Work"))
+        .stdout(contains("+15551234567"))
+        .stdout(contains("Chloé Ade"))
+        .stdout(contains("5559876543"))
+        .stdout(contains("Chidi Eze"));
+
+    // Exports can be written for another program too
+    let out = project_dir.path().join("for-google.csv");
+    rolodex()?
+        .args(["export", "--to", "f", "--csv-dialect", "google", "--des"])
+        .arg(&out)
+        .assert()
+        .success();
+    let exported = fs::read_to_string(&out)?;
+    assert!(exported.starts_with("Name,Phone 1 - Value,E-mail 1 - Value,Labels"));
+    assert!(exported.contains("Chidi Eze,08055555555"));
+
+    let json = project_dir.path().join("book.json");
+    fs::write(&json, "[]")?;
+    rolodex()?
+        .args(["import", "--from", "f", "--csv-dialect", "google", "--src"])
+        .arg(&json)
+        .assert()
+        .failure()
+        .stderr(contains("only apply to .csv files"));
+    Ok(())
+}
@@ -9,7 +9,7 @@ fn deleting_contacts() {
     // Attempt to delete non existing contact
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Alice"])
+        .args(["delete", "--name", "Alice"])
         .assert()
         .success()
         .stderr(predicate::str::contains("Contact Not found"));
@@ -17,7 +17,7 @@ fn deleting_contacts() {
     // Add a contacts 1
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Patricia",
@@ -35,7 +35,7 @@ fn deleting_contacts() {
     // Add a contacts 2
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Diane",
@@ -53,7 +53,7 @@ fn deleting_contacts() {
     // Add a contacts 3
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "John",
@@ -71,7 +71,7 @@ fn deleting_contacts() {
     // Add a contacts 4
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Wayne",
@@ -89,7 +89,7 @@ fn deleting_contacts() {
     // Add a contacts 5
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "Thomas",
@@ -107,7 +107,7 @@ fn deleting_contacts() {
     // Add a contacts 6
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&[
+        .args([
             "add",
             "--name",
             "John",
@@ -125,7 +125,7 @@ fn deleting_contacts() {
     // LISTING ADDED CONTACT
     let normal_list_output = Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["list"])
+        .args(["list"])
         .assert()
         .success()
         .get_output()
@@ -143,7 +143,7 @@ fn deleting_contacts() {
     // Delete 1 out of 6
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Patricia"])
+        .args(["delete", "--name", "Patricia"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Contact deleted successfully"));
@@ -151,7 +151,7 @@ fn deleting_contacts() {
     // Delete 2 out of 6
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Diane"])
+        .args(["delete", "--name", "Diane"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Contact deleted successfully"));
@@ -159,7 +159,7 @@ fn deleting_contacts() {
     // LISTING REMAINING CONTACTS
     let normal_list_output = Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["list"])
+        .args(["list"])
         .assert()
         .success()
         .get_output()
@@ -177,14 +177,14 @@ fn deleting_contacts() {
     // Verify that deleted contact no longer exist
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Patricia"])
+        .args(["delete", "--name", "Patricia"])
         .assert()
         .success()
         .stderr(predicate::str::contains("Contact Not found"));
 
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "Diane"])
+        .args(["delete", "--name", "Diane"])
         .assert()
         .success()
         .stderr(predicate::str::contains("Contact Not found"));
@@ -192,7 +192,7 @@ fn deleting_contacts() {
     // ATTEMPT TO DELETE CONTACT WITH IDENTICAL NAME "John" ADDED EARLIER
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "John"])
+        .args(["delete", "--name", "John"])
         .assert()
         .success()
         .stdout(predicate::str::contains(
@@ -202,7 +202,7 @@ fn deleting_contacts() {
     // Delete 3 out of 6
     Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["delete", "--name", "John", "--phone", "+2348031234567"])
+        .args(["delete", "--name", "John", "--phone", "+2348031234567"])
         .assert()
         .success()
         .stdout(predicate::str::contains("Contact deleted successfully"));
@@ -210,7 +210,7 @@ fn deleting_contacts() {
     // LISTING REMAINING CONTACTS
     let normal_list_output = Command::cargo_bin("rusty-rolodex")
         .unwrap()
-        .args(&["list"])
+        .args(["list"])
         .assert()
         .success()
         .get_output()
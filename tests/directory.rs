@@ -0,0 +1,64 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn dir_book_keeps_a_file_per_contact() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let book = project_dir.path().join(".instance").join("contacts");
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("STORAGE_CHOICE", "dir");
+        Ok(cmd)
+    };
+    let files = |extension: &str| -> Result<usize, std::io::Error> {
+        Ok(fs::read_dir(&book)?
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .is_ok_and(|e| e.path().extension().is_some_and(|ext| ext == extension))
+            })
+            .count())
+    };
+
+    for (name, phone) in [("Ada Obi", "08012345678"), ("Bola Ade", "08087654321")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone])
+            .assert()
+            .success();
+    }
+    assert_eq!(files("json")?, 2);
+
+    // A broken file is reported and skipped, the rest of the book still loads
+    fs::write(book.join("broken.json"), "{ not json")?;
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"))
+        .stderr(contains("broken.json"));
+
+    // Switching to vCard rewrites the contacts on the next save and keeps the broken file
+    rolodex()?
+        .args(["config", "set", "storage.dir_format", "vcf"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["delete", "--name", "Bola Ade"])
+        .assert()
+        .success();
+    assert_eq!(files("vcf")?, 2);
+    assert_eq!(files("json")?, 1);
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"));
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use rusty_rolodex::prelude::*;
+use rusty_rolodex::storage::encryption::EncryptedStorage;
+use rusty_rolodex::storage::{file::JsonStorage, memory::MemStore, sync_state::SyncState};
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn encrypt_change_passphrase_and_decrypt() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let book = project_dir.path().join(".instance").join("contacts.json");
+
+    let rolodex = |passphrase: &str| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("ENCRYPTION_KEY_SOURCE", "env")
+            .env("ROLODEX_PASSPHRASE", passphrase);
+        Ok(cmd)
+    };
+
+    rolodex("first")?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    rolodex("first")?
+        .args(["encryption", "enable"])
+        .assert()
+        .success()
+        .stdout(contains("is now encrypted"));
+    assert!(!String::from_utf8_lossy(&fs::read(&book)?).contains("Ada"));
+
+    rolodex("first")?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"));
+    rolodex("wrong")?
+        .args(["list"])
+        .assert()
+        .failure()
+        .stderr(contains("Wrong passphrase"));
+
+    rolodex("first")?
+        .args(["encryption", "change-passphrase"])
+        .env("ROLODEX_NEW_PASSPHRASE", "second")
+        .assert()
+        .success();
+    rolodex("first")?.args(["list"]).assert().failure();
+
+    // Edits are written back encrypted
+    rolodex("second")?
+        .args(["add", "--name", "Bola Ade", "--phone", "08087654321"])
+        .assert()
+        .success();
+    assert!(!String::from_utf8_lossy(&fs::read(&book)?).contains("Bola"));
+
+    let copy = project_dir.path().join("plain.json");
+    rolodex("second")?
+        .args(["encryption", "decrypt", "--out"])
+        .arg(&copy)
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&copy)?.contains("Bola Ade"));
+    assert!(!String::from_utf8_lossy(&fs::read(&book)?).contains("Bola"));
+
+    rolodex("second")?
+        .args(["encryption", "decrypt"])
+        .assert()
+        .success();
+    assert!(fs::read_to_string(&book)?.contains("Ada Obi"));
+    Ok(())
+}
+
+#[test]
+fn sync_state_of_an_encrypted_book_is_encrypted() -> Result<(), AppError> {
+    let dir = tempdir()?;
+    let state_dir = dir.path().join("sync");
+    let path = dir
+        .path()
+        .join("contacts.json")
+        .to_string_lossy()
+        .to_string();
+    let sealed = |passphrase: &str| {
+        EncryptedStorage::with_passphrase(Box::new(JsonStorage::at(&path)), passphrase.to_string())
+    };
+
+    // State kept while the book was plain is sealed when it gets encrypted
+    let plain = JsonStorage::at(&path);
+    let ada = Contact::new(
+        "Ada Obi".to_string(),
+        "08012345678".to_string(),
+        "ada@example.com".to_string(),
+        "".to_string(),
+    );
+    let state = SyncState::new(Some(state_dir.clone()), "laptop01");
+    state
+        .snapshot("elsewhere")
+        .save(&HashMap::from([(ada.id, ada.clone())]), &plain)?;
+    state.reseal(&plain, &sealed("first"))?;
+
+    let mut manager = ContactManager::builder(Box::new(sealed("first")))
+        .sync_state_dir(&state_dir)
+        .device_id("laptop01")
+        .build()?;
+    manager.add_contact(ada);
+    manager.sync_with_storage(Box::new(MemStore::new()), &sync::LastWriteWins)?;
+
+    let files: Vec<_> = fs::read_dir(&state_dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(
+        files
+            .iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "json"))
+            .count(),
+        2
+    );
+    for file in files
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+    {
+        let data = fs::read(file)?;
+        assert!(data.starts_with(b"RLDXENC1"), "{} is plain", file.display());
+        assert!(!String::from_utf8_lossy(&data).contains("Ada"));
+    }
+
+    // Read back with the book's passphrase, and not without it
+    assert_eq!(state.snapshots(manager.storage.as_ref())?.len(), 2);
+    assert!(state.snapshots(&sealed("wrong")).is_err());
+    Ok(())
+}
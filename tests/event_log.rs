@@ -0,0 +1,95 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn events_book_logs_changes_and_compacts() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let log = project_dir.path().join(".instance").join("contacts.events");
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("STORAGE_CHOICE", "events");
+        Ok(cmd)
+    };
+
+    for (name, phone) in [("Ada Obi", "08012345678"), ("Bola Ade", "08087654321")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone])
+            .assert()
+            .success();
+    }
+    rolodex()?
+        .args(["edit", "--name", "Ada Obi", "--phone", "08012345678"])
+        .args(["--new-phone", "08099999999"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["delete", "--name", "Bola Ade"])
+        .assert()
+        .success();
+
+    let events = fs::read_to_string(&log)?;
+    assert_eq!(events.lines().count(), 4);
+    assert!(events.contains(r#""event":"field_changed""#));
+    assert!(events.contains(r#""event":"deleted""#));
+
+    rolodex()?
+        .args(["compact"])
+        .assert()
+        .success()
+        .stdout(contains("Folded 4 events"));
+    assert_eq!(fs::read_to_string(&log)?, "");
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("08099999999"));
+
+    rolodex()?
+        .env("STORAGE_CHOICE", "json")
+        .args(["compact"])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+#[test]
+fn migrating_a_compacted_events_book_onto_itself_keeps_it() -> Result<(), Box<dyn std::error::Error>>
+{
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let log = project_dir.path().join(".instance").join("contacts.events");
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("STORAGE_CHOICE", "events");
+        Ok(cmd)
+    };
+
+    for (name, phone) in [("Ada Obi", "08012345678"), ("Bola Ade", "08087654321")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone])
+            .assert()
+            .success();
+    }
+    rolodex()?.args(["compact"]).assert().success();
+    rolodex()?
+        .args(["migrate", "--to", "events"])
+        .assert()
+        .success();
+
+    assert!(fs::exists(format!("{}.checkpoint", log.display()))?);
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"));
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn exports_html_and_markdown_phone_lists() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    for (name, phone, tag) in [
+        ("Ada Obi", "08012345678", "work"),
+        ("Bola Ade", "08087654321", "gym"),
+        ("Chidi Eze", "08055555555", ""),
+    ] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone, "--tag", tag])
+            .assert()
+            .success();
+    }
+
+    rolodex()?
+        .args(["export", "-t", "f", "-d", "book.html"])
+        .assert()
+        .success();
+    let html = fs::read_to_string(project_dir.path().join("book.html"))?;
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<input id=\"search\""));
+    let gym = html.find("<h2>gym").unwrap();
+    let work = html.find("<h2>work").unwrap();
+    let untagged = html.find("<h2>No tag").unwrap();
+    assert!(gym < work && work < untagged);
+    assert!(html.contains("<td>Ada Obi</td>"));
+    // Self-contained: nothing loaded from elsewhere
+    assert!(!html.contains("src=") && !html.contains("<link"));
+
+    rolodex()?
+        .args(["export", "-t", "f", "-d", "book.md"])
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(project_dir.path().join("book.md"))?;
+    assert!(markdown.starts_with("# Contacts\n\n3 contacts\n"));
+    assert!(markdown.contains(
+        "## work\n\n| Name | Phone | Email |\n| --- | --- | --- |\n| Ada Obi | 08012345678 |  |\n"
+    ));
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+
+fn rolodex(project: &Path, xdg: &Path) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = common::rolodex(project, xdg)?;
+    cmd.env("STORAGE_CHOICE", "dir").env("GIT_ENABLED", "true");
+    Ok(cmd)
+}
+
+#[test]
+fn changes_are_committed_and_logged() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let rolodex = || rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    rolodex()?
+        .args([
+            "edit",
+            "--name",
+            "Ada Obi",
+            "--phone",
+            "08012345678",
+            "--new-phone",
+            "08087654321",
+        ])
+        .assert()
+        .success();
+    // Reading the book commits nothing
+    rolodex()?.args(["list"]).assert().success();
+
+    let log = rolodex()?.args(["log"]).assert().success();
+    let stdout = String::from_utf8(log.get_output().stdout.clone())?;
+    let commits: Vec<&str> = stdout.lines().filter(|l| l.contains("Ada Obi")).collect();
+    assert_eq!(commits.len(), 2, "{stdout}");
+    // Newest first
+    assert!(commits[0].contains("edit: Ada Obi phone"), "{stdout}");
+    assert!(stdout.contains("add: Ada Obi"));
+    Ok(())
+}
+
+#[test]
+fn books_sync_through_a_bare_repository() -> Result<(), Box<dyn std::error::Error>> {
+    let shared = tempdir()?;
+    git2::Repository::init_bare(shared.path())?;
+    let shared = shared.path().to_string_lossy().to_string();
+
+    let (xdg_a, project_a) = (tempdir()?, tempdir()?);
+    let (xdg_b, project_b) = (tempdir()?, tempdir()?);
+    let laptop = || rolodex(project_a.path(), xdg_a.path());
+    let phone = || rolodex(project_b.path(), xdg_b.path());
+
+    laptop()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    laptop()?
+        .args(["sync", "--with", "g", "--src", &shared])
+        .assert()
+        .success();
+
+    phone()?
+        .args(["add", "--name", "Bola Ade", "--phone", "08087654321"])
+        .assert()
+        .success();
+    // The remote can also come from git.remote
+    phone()?
+        .args(["config", "set", "git.remote", &shared])
+        .assert()
+        .success();
+    phone()?.args(["sync", "--with", "g"]).assert().success();
+    phone()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"));
+
+    laptop()?
+        .args(["sync", "--with", "g", "--src", &shared])
+        .assert()
+        .success();
+    laptop()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Bola Ade"));
+    Ok(())
+}
+
+#[test]
+fn moves_and_migrations_commit_every_book_they_change() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("STORAGE_CHOICE", "json");
+        Ok(cmd)
+    };
+    let work_path = project_dir.path().join("work").join("work.json");
+
+    rolodex()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["book", "create", "work", "--path"])
+        .arg(&work_path)
+        .assert()
+        .success();
+    rolodex()?
+        .args(["move", "--name", "Ada Obi", "--to", "work"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["--book", "work", "log"])
+        .assert()
+        .success()
+        .stdout(contains("move: Ada Obi to work"));
+
+    rolodex()?
+        .args(["--book", "work", "migrate", "--to", "txt"])
+        .assert()
+        .success();
+    rolodex()?
+        .args(["--book", "work", "log"])
+        .assert()
+        .success()
+        .stdout(contains("migrate: to txt"));
+
+    // The new file and the removal of the old one are in the same commit
+    let repo = git2::Repository::open(work_path.parent().unwrap())?;
+    let tree = repo.head()?.peel_to_tree()?;
+    assert!(tree.get_name("work.txt").is_some());
+    assert!(tree.get_name("work.json").is_none());
+    Ok(())
+}
@@ -3,6 +3,8 @@ use predicates::str::contains;
 use std::{fs, path::Path};
 use tempfile::tempdir;
 
+mod common;
+
 fn listing_format(i: i32, name: &str, phone: &str, email: &str, tag: &str) -> String {
     format!("{i:>3}. {name:<20} {phone:15} {email:^30} {tag:<15}")
 }
@@ -96,3 +98,98 @@ fn export_import() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn dry_run_import() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    // A colleague's CSV with one contact
+    let src_path = project_dir.path().join("colleague.csv");
+    let src_path_str = src_path.to_string_lossy().to_string();
+    fs::write(
+        &src_path,
+        "name,phone,email,tag\nCarol,08098765432,carol@example.com,work\n",
+    )?;
+
+    rolodex()?
+        .args(["import", "--from", "f", "--src", &src_path_str, "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains("Added (1)"))
+        .stdout(contains("Carol"));
+
+    let output = rolodex()?
+        .args(["import", "--from", "f", "--src", &src_path_str, "--dry-run"])
+        .args(["--format", "json"])
+        .output()?;
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(report["added"][0]["name"], "Carol");
+
+    // Nothing was imported, and no book or snapshot was written
+    rolodex()?
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(contains("No contact yet"));
+    assert!(!project_dir.path().join(".instance/contacts.json").exists());
+    let state = fs::read_dir(xdg_dir.path().join("data/rolodex/sync"))?;
+    for entry in state {
+        assert_eq!(entry?.file_name(), "device_id");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sync_with_file() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let state_dir = tempdir()?;
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("SYNC_STATE_DIR", state_dir.path());
+        Ok(cmd)
+    };
+
+    let shared_path = project_dir.path().join("shared.json");
+    let shared_path_str = shared_path.to_string_lossy().to_string();
+    fs::write(
+        &shared_path,
+        r#"[{"name":"Dave","phone":"08012345678","email":"","tag":""}]"#,
+    )?;
+
+    rolodex()?
+        .args(["add", "--name", "Erin", "--phone", "08087654321"])
+        .assert()
+        .success();
+
+    rolodex()?
+        .args(["sync", "--with", "f", "--src", &shared_path_str])
+        .assert()
+        .success()
+        .stdout(contains("Pulled: 1 added, 0 updated, 0 deleted"))
+        .stdout(contains("Pushed: 1 added, 0 updated, 0 deleted"));
+
+    let shared = fs::read_to_string(&shared_path)?;
+    assert!(shared.contains("Dave") && shared.contains("Erin"));
+
+    // Both sides now agree
+    rolodex()?
+        .args(["sync", "--with", "f", "--src", &shared_path_str])
+        .assert()
+        .success()
+        .stdout(contains("Pulled: 0 added, 0 updated, 0 deleted"))
+        .stdout(contains("Pushed: 0 added, 0 updated, 0 deleted"));
+
+    // The book and its snapshot stayed in the temp dirs
+    assert!(project_dir.path().join(".instance/contacts.json").exists());
+    assert!(fs::read_dir(state_dir.path())?.count() > 1);
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use predicates::{prelude::*, str::contains};
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn invalid_records_are_set_aside() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    let csv = project_dir.path().join("people.csv");
+    fs::write(
+        &csv,
+        "Name,Phone,Email\n\
+         Ada Obi,08012345678,ada@example.com\n\
+         Bola Ade,12,\n\
+         Chidi Eze,08055555555,chidi-at-example\n",
+    )?;
+
+    // --strict imports nothing
+    rolodex()?
+        .args(["import", "--from", "f", "--strict", "--src"])
+        .arg(&csv)
+        .assert()
+        .failure()
+        .stderr(contains("line 3: Number must contain"))
+        .stderr(contains("line 4: Email"));
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("No contact yet"));
+
+    // Otherwise the valid rows go in and the rest go to the reject file
+    rolodex()?
+        .args(["import", "--from", "f", "--src"])
+        .arg(&csv)
+        .assert()
+        .success()
+        .stderr(contains("Rejected line 3"))
+        .stderr(contains("2 invalid record(s) written to"));
+    let rejects = fs::read_to_string(project_dir.path().join("people.rejects.csv"))?;
+    assert_eq!(
+        rejects,
+        "Name,Phone,Email\nBola Ade,12,\nChidi Eze,08055555555,chidi-at-example\n"
+    );
+
+    // vCard imports go through the same checks, one card at a time
+    let vcf = project_dir.path().join("phone.vcf");
+    fs::write(
+        &vcf,
+        "BEGIN:VCARD\nVERSION:3.0\nFN:Dayo Bello\nTEL:08099999999\nEND:VCARD\n\
+         BEGIN:VCARD\nVERSION:3.0\nTEL:08011111111\nEND:VCARD\n",
+    )?;
+    let vcf_rejects = project_dir.path().join("bad-cards.vcf");
+    rolodex()?
+        .args(["import", "--from", "f", "--src"])
+        .arg(&vcf)
+        .arg("--rejects")
+        .arg(&vcf_rejects)
+        .assert()
+        .success()
+        .stderr(contains("Rejected line 6: card without a name"));
+    assert!(fs::read_to_string(&vcf_rejects)?.contains("TEL:08011111111"));
+
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Dayo Bello"))
+        .stdout(contains("Bola Ade").not());
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use assert_cmd::Command;
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn jsonl_book_appends_and_exports() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let book = project_dir.path().join(".instance").join("contacts.jsonl");
+
+    let rolodex = || -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = common::rolodex(project_dir.path(), xdg_dir.path())?;
+        cmd.env("STORAGE_CHOICE", "jsonl");
+        Ok(cmd)
+    };
+
+    for (name, phone) in [("Ada Obi", "08012345678"), ("Bola Ade", "08087654321")] {
+        rolodex()?
+            .args(["add", "--name", name, "--phone", phone])
+            .assert()
+            .success();
+    }
+
+    // A version line, then one contact per line, each added by appending
+    let lines: Vec<String> = fs::read_to_string(&book)?
+        .lines()
+        .map(String::from)
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with(r#"{"schema_version":"#));
+    assert!(lines[1].contains("Ada Obi"));
+
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"));
+
+    let out = project_dir.path().join("out.jsonl");
+    rolodex()?
+        .args(["export", "-t", "f", "-d"])
+        .arg(&out)
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&out)?.lines().count(), 3);
+
+    rolodex()?
+        .args(["import", "-f", "f", "--dry-run", "-s"])
+        .arg(&out)
+        .assert()
+        .success();
+    Ok(())
+}
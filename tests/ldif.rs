@@ -0,0 +1,68 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn moves_contacts_to_and_from_ldif() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    // A directory export, with the ou entry people sit under
+    let directory = project_dir.path().join("directory.ldif");
+    fs::write(
+        &directory,
+        "version: 1\n\
+         \n\
+         dn: ou=people,dc=acme,dc=org\n\
+         objectClass: organizationalUnit\n\
+         ou: people\n\
+         \n\
+         dn: uid=aobi,ou=people,dc=acme,dc=org\n\
+         objectClass: inetOrgPerson\n\
+         cn: Ada Obi\n\
+         sn: Obi\n\
+         telephoneNumber: 08012345678\n\
+         mail: ada@acme.org\n\
+         o: Acme\n\
+         \n\
+         dn: uid=bade,ou=people,dc=acme,dc=org\n\
+         objectClass: inetOrgPerson\n\
+         givenName: Bola\n\
+         sn: Ade\n\
+         mobile: 08087654321\n",
+    )?;
+    rolodex()?
+        .args(["import", "--from", "f", "--src"])
+        .arg(&directory)
+        .assert()
+        .success();
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Acme"))
+        .stdout(contains("Bola Ade"));
+
+    rolodex()?
+        .args(["config", "set", "ldif.base_dn", "ou=staff,dc=acme,dc=org"])
+        .assert()
+        .success();
+    let out = project_dir.path().join("export.ldif");
+    rolodex()?
+        .args(["export", "--to", "f", "--des"])
+        .arg(&out)
+        .assert()
+        .success();
+
+    let exported = fs::read_to_string(&out)?;
+    assert!(exported.starts_with("version: 1\n"));
+    assert!(exported.contains(",ou=staff,dc=acme,dc=org\n"));
+    assert!(exported.contains("objectClass: inetOrgPerson\n"));
+    assert!(exported.contains("givenName: Bola\nsn: Ade\ntelephoneNumber: 08087654321\n"));
+    Ok(())
+}
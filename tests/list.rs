@@ -1,139 +1,69 @@
 use assert_cmd::Command;
-use predicates::prelude::*;
-use std::fs;
+use rusty_rolodex::prelude::*;
+use std::path::Path;
+
+use file::JsonStorage;
+use memory::MemStore;
+
+fn contact(name: &str, phone: &str, email: &str, tag: &str) -> Contact {
+    Contact::new(
+        name.to_string(),
+        phone.to_string(),
+        email.to_string(),
+        tag.to_string(),
+    )
+}
+
+/// Runs rolodex on an in-memory book seeded from `fixture`, leaving ./.instance alone
+fn rolodex(fixture: &Path, books_file: &Path) -> Command {
+    let mut cmd = Command::cargo_bin("rusty-rolodex").unwrap();
+    cmd.args(["--storage-choice", "mem"])
+        .env("MEM_FIXTURE", fixture)
+        .env("BOOKS_FILE", books_file)
+        .env_remove("ROLODEX_BOOK");
+    cmd
+}
 
 #[test]
-fn listing_contacts() {
-    // Add a contacts 1
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
+fn listing_contacts() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let fixture = dir.path().join("contacts.json");
+    let books_file = dir.path().join("books.json");
+
+    let seed = MemStore::with_contacts([
+        contact(
             "Patricia",
-            "--phone",
             "08066809241",
-            "--email",
             "lmartinez@bender-patterson.net",
-            "--tag",
             "others",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
-
-    // Add a contacts 2
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
-            "Diane",
-            "--phone",
-            "08064879199",
-            "--email",
-            "grahammatthew@gmail.com",
-            "--tag",
-            "school",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
-
-    // Add a contacts 3
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
-            "John",
-            "--phone",
-            "08046516806",
-            "--email",
-            "wendy59@turner.com",
-            "--tag",
-            "friends",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
-
-    // Add a contacts 4
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
-            "Wayne",
-            "--phone",
-            "08062866694",
-            "--email",
-            "jackie73@lopez.com",
-            "--tag",
-            "friends",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
-
-    // Add a contacts 5
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
-            "Thomas",
-            "--phone",
-            "08019271836",
-            "--email",
-            "kdelacruz@yahoo.com",
-            "--tag",
-            "school",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
-
-    // Add a contacts 6
-    Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&[
-            "add",
-            "--name",
-            "Alice",
-            "--phone",
-            "08031234567",
-            "--email",
-            "alice@example.com",
-            "--tag",
-            "work",
-        ])
-        .assert()
-        .success()
-        .stdout(predicate::str::contains("Contact added successfully"));
+        ),
+        contact("Diane", "08064879199", "grahammatthew@gmail.com", "school"),
+        contact("John", "08046516806", "wendy59@turner.com", "friends"),
+        contact("Wayne", "08062866694", "jackie73@lopez.com", "friends"),
+        contact("Thomas", "08019271836", "kdelacruz@yahoo.com", "school"),
+        contact("Alice", "08031234567", "alice@example.com", "work"),
+    ]);
+    JsonStorage::at(&fixture.to_string_lossy()).save(&seed.load()?)?;
 
     // LISTING ADDED CONTACT
-    let normal_list_output = Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&["list"])
+    let normal_list_output = rolodex(&fixture, &books_file)
+        .args(["list"])
         .assert()
         .success()
         .get_output()
         .stdout
         .clone();
 
-    let tagged_list_output = Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&["list", "--tag", "FRIENDS"])
+    let tagged_list_output = rolodex(&fixture, &books_file)
+        .args(["list", "--tag", "FRIENDS"])
         .assert()
         .success()
         .get_output()
         .stdout
         .clone();
 
-    let sorted_list_output = Command::cargo_bin("rusty-rolodex")
-        .unwrap()
-        .args(&["list", "--sort", "name"])
+    let sorted_list_output = rolodex(&fixture, &books_file)
+        .args(["list", "--sort", "name"])
         .assert()
         .success()
         .get_output()
@@ -158,5 +88,7 @@ fn listing_contacts() {
     assert!(normal_list.len() == sorted_list.len());
     assert!(sorted_list[1].contains("Alice") && sorted_list[2].contains("Diane"));
 
-    let _ = fs::remove_file("./.instance/contacts.json");
+    // The fixture is only read
+    assert_eq!(JsonStorage::at(&fixture.to_string_lossy()).load()?.len(), 6);
+    Ok(())
 }
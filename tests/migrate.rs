@@ -0,0 +1,128 @@
+use predicates::str::contains;
+use std::fs;
+use tempfile::tempdir;
+
+mod common;
+
+#[test]
+fn migrate_upgrades_schema_and_switches_medium() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let instance = project_dir.path().join(".instance");
+
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    // A book from before schema versions: a plain list of contacts
+    fs::create_dir_all(&instance)?;
+    fs::write(
+        instance.join("contacts.json"),
+        r#"[{"name":"Ada","phone":"08012345678","email":"ada@example.com","tag":"work"}]"#,
+    )?;
+
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada"));
+
+    rolodex()?
+        .args(["migrate"])
+        .assert()
+        .success()
+        .stdout(contains("rewritten in storage schema 5"));
+    let json = fs::read_to_string(instance.join("contacts.json"))?;
+    assert!(json.starts_with(r#"{"schema_version":5,"contacts":{"#));
+    // The clock seeded by the upgrade is kept
+    assert!(!json.contains(r#""clock":"""#));
+
+    rolodex()?
+        .args(["migrate", "--to", "txt"])
+        .assert()
+        .success()
+        .stdout(contains("migrated from json to txt"));
+    assert!(!instance.join("contacts.json").exists());
+    let txt = fs::read_to_string(instance.join("contacts.txt"))?;
+    assert!(txt.starts_with("schema_version: 5\n"));
+
+    rolodex()?
+        .args(["config", "get", "storage.choice"])
+        .assert()
+        .success()
+        .stdout("txt\n");
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada"));
+
+    // The binary medium round-trips the book too
+    rolodex()?
+        .args(["migrate", "--to", "bin"])
+        .assert()
+        .success()
+        .stdout(contains("migrated from txt to bin"));
+    assert!(fs::read(instance.join("contacts.bin"))?.starts_with(b"RLDXBIN1"));
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada"));
+    rolodex()?
+        .args(["migrate", "--to", "txt"])
+        .assert()
+        .success();
+    assert!(!instance.join("contacts.bin").exists());
+
+    // A file from a newer rolodex is left alone
+    fs::write(instance.join("contacts.txt"), "schema_version: 99\n")?;
+    rolodex()?
+        .args(["list"])
+        .assert()
+        .failure()
+        .stderr(contains("Upgrade rolodex"));
+    assert_eq!(
+        fs::read_to_string(instance.join("contacts.txt"))?,
+        "schema_version: 99\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn only_the_books_own_old_file_is_removed() -> Result<(), Box<dyn std::error::Error>> {
+    let xdg_dir = tempdir()?;
+    let project_dir = tempdir()?;
+    let instance = project_dir.path().join(".instance");
+    let rolodex = || common::rolodex(project_dir.path(), xdg_dir.path());
+
+    rolodex()?
+        .args(["add", "--name", "Ada Obi", "--phone", "08012345678"])
+        .assert()
+        .success();
+
+    // An export next to an unrelated file of the other format leaves it alone
+    let out = project_dir.path().join("out");
+    fs::create_dir_all(&out)?;
+    fs::write(out.join("book.txt"), "not a book\n")?;
+    rolodex()?
+        .args(["export", "-t", "f", "-d"])
+        .arg(out.join("book.json"))
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(out.join("book.txt"))?, "not a book\n");
+
+    // Switching the book to txt takes in its json file and removes it on save
+    rolodex()?
+        .env("STORAGE_CHOICE", "txt")
+        .args(["add", "--name", "Bola Ade", "--phone", "08087654321"])
+        .assert()
+        .success();
+    assert!(!instance.join("contacts.json").exists());
+    rolodex()?
+        .env("STORAGE_CHOICE", "txt")
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(contains("Ada Obi"))
+        .stdout(contains("Bola Ade"));
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use chrono::{Duration, Utc};
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 use uuid::Uuid;
 
 // Import the necessary types from rusty_rolodex
@@ -31,15 +31,32 @@ impl ContactStore for MockStorage {
     }
 }
 
+/// Storage that can't be read, e.g. a remote that is unreachable
+struct FailingStorage;
+
+impl ContactStore for FailingStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        Err(AppError::Synchronization("remote unreachable".to_string()))
+    }
+
+    fn save(&self, _contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn get_medium(&self) -> &str {
+        "failing"
+    }
+}
+
 fn make_manager() -> Result<ContactManager, AppError> {
-    Ok(ContactManager {
-        mem: HashMap::new(),
-        storage: Box::new(MockStorage::new(HashMap::new())),
-        index: manager::Index {
-            name: HashMap::new(),
-            domain: HashMap::new(),
-        },
-    })
+    ContactManager::builder(Box::new(MockStorage::new(HashMap::new()))).build()
+}
+
+/// A manager keeping its sync snapshots and conflict queue in `dir`.
+fn make_manager_in(dir: &std::path::Path) -> Result<ContactManager, AppError> {
+    ContactManager::builder(Box::new(MockStorage::new(HashMap::new())))
+        .sync_state_dir(dir)
+        .build()
 }
 
 // SCENARIO 1: Same contact edited in two places
@@ -64,8 +81,11 @@ fn sync_same_contact_different_fields_modified() -> Result<(), AppError> {
         email: "john@example.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Laptop modified phone at t1
@@ -91,11 +111,8 @@ fn sync_same_contact_different_fields_modified() -> Result<(), AppError> {
     // Sync: clone remote.mem into MockStorage
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -130,8 +147,11 @@ fn sync_same_contact_same_field_last_write_wins() -> Result<(), AppError> {
         email: "alice@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Laptop edited phone (earlier)
@@ -157,11 +177,8 @@ fn sync_same_contact_same_field_last_write_wins() -> Result<(), AppError> {
     // Sync: clone remote.mem into MockStorage
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -224,11 +241,8 @@ fn sync_remote_contains_new_contacts() -> Result<(), AppError> {
     assert_eq!(local_manager.mem.len(), 1);
 
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -269,11 +283,8 @@ fn sync_offline_additions_no_duplicates() -> Result<(), AppError> {
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
 
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -304,8 +315,11 @@ fn sync_local_delete_remote_edit_delete_wins() -> Result<(), AppError> {
         email: "frank@example.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Local: deleted
@@ -330,11 +344,8 @@ fn sync_local_delete_remote_edit_delete_wins() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -361,8 +372,11 @@ fn sync_remote_delete_local_edit_remote_delete_wins() -> Result<(), AppError> {
         email: "grace@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Local: edited (not deleted)
@@ -385,11 +399,8 @@ fn sync_remote_delete_local_edit_remote_delete_wins() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -419,38 +430,24 @@ fn sync_state_unchanged_on_error() -> Result<(), AppError> {
         email: "henry@example.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     let mut local_manager = make_manager()?;
     local_manager.add_contact(local_contact.clone());
 
-    // Create remote with contact that has mismatched created_at (will cause sync error)
-    let conflicting_remote = Contact {
-        id: contact_id,
-        name: "Henry Brown".to_string(),
-        phone: "1111111111".to_string(),
-        email: "henry@example.com".to_string(),
-        tag: "work".to_string(),
-        deleted: false,
-        created_at: base_time + Duration::seconds(100), // Different created_at = conflict
-        updated_at: base_time,
-    };
-
-    let mut remote_manager = make_manager()?;
-    remote_manager.add_contact(conflicting_remote);
-
-    let remote_storage = MockStorage::new(remote_manager.mem.clone());
-
     // Sync should fail
     let mut base = local_manager.mem.clone();
-    let result = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
+    let result =
+        local_manager.sync_from_storage(&mut base, Box::new(FailingStorage), &sync::LastWriteWins);
+    assert!(
+        result.is_err(),
+        "Sync should fail when the remote can't be read"
     );
-    assert!(result.is_err(), "Sync should fail on timestamp conflict");
 
     // Verify local state unchanged (rollback semantics)
     let local_contact_after = local_manager.mem.get(&contact_id).unwrap();
@@ -478,8 +475,11 @@ fn sync_same_timestamps_uses_local() -> Result<(), AppError> {
         email: "iris@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     let remote_contact = Contact {
@@ -489,8 +489,11 @@ fn sync_same_timestamps_uses_local() -> Result<(), AppError> {
         email: "iris@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time, // Same updated_at (clock drift)
+        clock: Hlc::default(),
     };
 
     let mut local_manager = make_manager()?;
@@ -501,11 +504,8 @@ fn sync_same_timestamps_uses_local() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -520,7 +520,7 @@ fn sync_same_timestamps_uses_local() -> Result<(), AppError> {
 }
 
 #[test]
-fn sync_created_at_mismatch_detected_as_conflict() -> Result<(), AppError> {
+fn sync_created_at_mismatch_is_not_a_conflict() -> Result<(), AppError> {
     let contact_id = Uuid::new_v4();
     let base_time = Utc::now();
 
@@ -531,13 +531,19 @@ fn sync_created_at_mismatch_detected_as_conflict() -> Result<(), AppError> {
         email: "jack@example.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
-    // Same ID but different created_at - this indicates they're not the same contact
+    // Same ID but different created_at, e.g. a file written before contacts had timestamps.
+    // created_at is informational only, the newer clock still wins.
     let mut remote_contact = local_contact.clone();
-    remote_contact.created_at = base_time + Duration::hours(1); // Different creation time
+    remote_contact.created_at = base_time + Duration::hours(1);
+    remote_contact.phone = "5555555555".to_string();
+    remote_contact.clock = Hlc::seeded(base_time).tick_at(base_time, "phone");
 
     let mut local_manager = make_manager()?;
     local_manager.add_contact(local_contact);
@@ -548,23 +554,52 @@ fn sync_created_at_mismatch_detected_as_conflict() -> Result<(), AppError> {
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
 
     let mut base = local_manager.mem.clone();
-    let result = local_manager.sync_from_storage(
+    let conflicts = local_manager.sync_from_storage(
         &mut base,
         Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+        &sync::LastWriteWins,
+    )?;
 
-    assert!(
-        result.is_err(),
-        "Sync should fail when created_at timestamps differ (indicates different contacts)"
+    assert!(conflicts.is_empty());
+    assert_eq!(base.get(&contact_id).unwrap().phone, "5555555555");
+
+    Ok(())
+}
+
+#[test]
+fn sync_orders_by_clock_not_wall_time() -> Result<(), AppError> {
+    let contact_id = Uuid::new_v4();
+    let now = Utc::now();
+
+    // Phone's clock runs an hour ahead
+    let mut phone_version = Contact::new(
+        "Lena Ortiz".to_string(),
+        "6666666666".to_string(),
+        "".to_string(),
+        "".to_string(),
     );
+    phone_version.id = contact_id;
+    phone_version.updated_at = now + Duration::hours(1);
+    phone_version.clock = Hlc::seeded(phone_version.updated_at);
 
-    match result {
-        Err(AppError::Synchronization(_)) => {
-            // Expected
-        }
-        _ => panic!("Expected Synchronization error"),
-    }
+    // Laptop edits after receiving the phone's version, with its correct clock
+    let mut laptop_version = phone_version.clone();
+    laptop_version.phone = "7777777777".to_string();
+    laptop_version.updated_at = now;
+    laptop_version.clock = phone_version.clock.tick_at(now, "laptop");
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(laptop_version);
+
+    let remote_storage = MockStorage::new(HashMap::from([(contact_id, phone_version)]));
+    let mut base = local_manager.mem.clone();
+    local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins)?;
+
+    assert_eq!(
+        base.get(&contact_id).unwrap().phone,
+        "7777777777",
+        "The later edit wins even though its wall clock reads earlier"
+    );
 
     Ok(())
 }
@@ -602,11 +637,8 @@ fn sync_ignores_duplicate_by_name_and_phone() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -661,11 +693,8 @@ fn sync_duplicate_detection_requires_name_and_phone_match() -> Result<(), AppErr
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -698,11 +727,8 @@ fn sync_empty_remote_no_changes() -> Result<(), AppError> {
     let empty_storage = MockStorage::new(HashMap::new());
 
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(empty_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(empty_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -726,8 +752,11 @@ fn sync_all_remote_contacts_deleted_locally() -> Result<(), AppError> {
         email: "nina@example.com".to_string(),
         tag: "work".to_string(),
         deleted: true, // Already deleted
+        deleted_at: Some(base_time),
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     let mut remote_contact = local_contact.clone();
@@ -742,11 +771,8 @@ fn sync_all_remote_contacts_deleted_locally() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -770,8 +796,11 @@ fn sync_index_updated_after_merge() -> Result<(), AppError> {
         email: "oscar@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     let mut remote_contact = local_contact.clone();
@@ -787,11 +816,8 @@ fn sync_index_updated_after_merge() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -856,8 +882,11 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
         email: "alice@old.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     let mut remote_1 = local_1.clone();
@@ -873,8 +902,11 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
         email: "bob@example.com".to_string(),
         tag: "personal".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Contact 3: Only in remote, should be added
@@ -886,8 +918,11 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
         email: "charlie@example.com".to_string(),
         tag: "work".to_string(),
         deleted: false,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time,
+        clock: Hlc::default(),
     };
 
     // Contact 4: Deleted locally, older remote version
@@ -899,8 +934,11 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
         email: "david@example.com".to_string(),
         tag: "work".to_string(),
         deleted: true,
+        deleted_at: None,
+        deleted_by: None,
         created_at: base_time,
         updated_at: base_time + Duration::seconds(20),
+        clock: Hlc::default(),
     };
 
     let mut remote_4 = local_4.clone();
@@ -919,11 +957,8 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
 
     let remote_storage = MockStorage::new(remote_manager.mem.clone());
     let mut base = local_manager.mem.clone();
-    let sync_status = local_manager.sync_from_storage(
-        &mut base,
-        Box::new(remote_storage),
-        manager::SyncPolicy::LastWriteWinsPolicy(manager::LastWriteWinsPolicy),
-    );
+    let sync_status =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LastWriteWins);
     assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
     local_manager.mem = base;
     local_manager.index = manager::Index::new(&local_manager)?;
@@ -945,3 +980,932 @@ fn sync_multiple_contacts_mixed_operations() -> Result<(), AppError> {
 
     Ok(())
 }
+
+// SCENARIO 7: Three-way merge against the last-synced snapshot
+//
+// Fields changed on only one side are both kept;
+// only a field changed on both sides is a conflict.
+
+#[test]
+fn sync_three_way_keeps_changes_to_different_fields() -> Result<(), AppError> {
+    let contact_id = Uuid::new_v4();
+    let base_time = Utc::now();
+
+    let original = Contact {
+        id: contact_id,
+        name: "John Doe".to_string(),
+        phone: "1234567890".to_string(),
+        email: "john@example.com".to_string(),
+        tag: "work".to_string(),
+        deleted: false,
+        deleted_at: None,
+        deleted_by: None,
+        created_at: base_time,
+        updated_at: base_time,
+        clock: Hlc::default(),
+    };
+
+    // Laptop changed phone
+    let mut laptop_version = original.clone();
+    laptop_version.phone = "9876543210".to_string();
+    laptop_version.updated_at = base_time + Duration::seconds(10);
+
+    // Phone changed email later
+    let phone_time = base_time + Duration::seconds(20);
+    let mut phone_version = original.clone();
+    phone_version.email = "john.doe@example.com".to_string();
+    phone_version.updated_at = phone_time;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(laptop_version);
+
+    let mut snapshot = HashMap::new();
+    snapshot.insert(contact_id, original);
+
+    let remote_storage = MockStorage::new(HashMap::from([(contact_id, phone_version)]));
+    let mut base = local_manager.mem.clone();
+    let sync_status = local_manager.sync_from_storage(
+        &mut base,
+        Box::new(remote_storage),
+        &sync::FieldLevelMerge::new(snapshot),
+    );
+    assert!(sync_status.is_ok(), "sync failed: {:?}", sync_status.err());
+
+    let synced = base.get(&contact_id).unwrap();
+    assert_eq!(synced.phone, "9876543210", "Local phone change is kept");
+    assert_eq!(
+        synced.email, "john.doe@example.com",
+        "Remote email change is taken"
+    );
+    assert_eq!(synced.name, "John Doe");
+    assert_eq!(synced.updated_at, phone_time, "Timestamp should be latest");
+
+    Ok(())
+}
+
+#[test]
+fn sync_three_way_field_changed_on_both_sides_is_conflict() -> Result<(), AppError> {
+    let contact_id = Uuid::new_v4();
+    let base_time = Utc::now();
+
+    let original = Contact {
+        id: contact_id,
+        name: "Alice Smith".to_string(),
+        phone: "5555555555".to_string(),
+        email: "alice@example.com".to_string(),
+        tag: "personal".to_string(),
+        deleted: false,
+        deleted_at: None,
+        deleted_by: None,
+        created_at: base_time,
+        updated_at: base_time,
+        clock: Hlc::default(),
+    };
+
+    // Laptop changed phone and tag last
+    let mut laptop_version = original.clone();
+    laptop_version.phone = "1111111111".to_string();
+    laptop_version.tag = "work".to_string();
+    laptop_version.updated_at = base_time + Duration::seconds(15);
+
+    // Phone changed phone earlier
+    let mut phone_version = original.clone();
+    phone_version.phone = "2222222222".to_string();
+    phone_version.updated_at = base_time + Duration::seconds(5);
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(laptop_version);
+
+    let remote_storage = MockStorage::new(HashMap::from([(contact_id, phone_version)]));
+    let mut base = local_manager.mem.clone();
+    let conflicts = local_manager.sync_from_storage(
+        &mut base,
+        Box::new(remote_storage),
+        &sync::FieldLevelMerge::new(HashMap::from([(contact_id, original)])),
+    )?;
+
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(
+        conflicts[0].fields,
+        vec!["phone".to_string(), "tag".to_string()]
+    );
+    assert_eq!(conflicts[0].remote.phone, "2222222222");
+
+    // Local is left as it was until the conflict is resolved
+    let synced = base.get(&contact_id).unwrap();
+    assert_eq!(synced.phone, "1111111111");
+    assert_eq!(synced.tag, "work");
+
+    Ok(())
+}
+
+#[test]
+fn sync_three_way_without_snapshot_reports_a_conflict() -> Result<(), AppError> {
+    let contact_id = Uuid::new_v4();
+    let base_time = Utc::now();
+
+    let local_version = Contact {
+        id: contact_id,
+        name: "Kim Lee".to_string(),
+        phone: "5555555555".to_string(),
+        email: "kim@example.com".to_string(),
+        tag: "personal".to_string(),
+        deleted: false,
+        deleted_at: None,
+        deleted_by: None,
+        created_at: base_time,
+        updated_at: base_time + Duration::seconds(5),
+        clock: Hlc::default(),
+    };
+
+    let mut remote_version = local_version.clone();
+    remote_version.email = "kim.lee@example.com".to_string();
+    remote_version.updated_at = base_time + Duration::seconds(10);
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local_version);
+
+    let remote_storage = MockStorage::new(HashMap::from([(contact_id, remote_version)]));
+    let mut base = local_manager.mem.clone();
+    let conflicts = local_manager.sync_from_storage(
+        &mut base,
+        Box::new(remote_storage),
+        &sync::FieldLevelMerge::new(HashMap::new()),
+    )?;
+
+    // No telling which side changed the email, even though the remote is newer
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].fields, vec!["email".to_string()]);
+    assert_eq!(base.get(&contact_id).unwrap().email, "kim@example.com");
+
+    Ok(())
+}
+
+// SCENARIO 8: Pluggable sync policies
+
+fn diverged_pair(base_time: chrono::DateTime<Utc>) -> (Contact, Contact) {
+    let local = Contact {
+        id: Uuid::new_v4(),
+        name: "Nora Quinn".to_string(),
+        phone: "5551234567".to_string(),
+        email: "nora@example.com".to_string(),
+        tag: "work".to_string(),
+        deleted: false,
+        deleted_at: None,
+        deleted_by: None,
+        created_at: base_time,
+        updated_at: base_time + Duration::seconds(10),
+        clock: Hlc::default(),
+    };
+
+    // Remote is older but differs
+    let mut remote = local.clone();
+    remote.email = "nora.quinn@example.com".to_string();
+    remote.tag = "friends".to_string();
+    remote.updated_at = base_time + Duration::seconds(5);
+
+    (local, remote)
+}
+
+#[test]
+fn sync_local_always_wins_keeps_local_but_adds_new() -> Result<(), AppError> {
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let new_remote = Contact::new(
+        "Owen Park".to_string(),
+        "5559876543".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let new_id = new_remote.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let remote_storage = MockStorage::new(HashMap::from([(id, remote), (new_id, new_remote)]));
+    let mut base = local_manager.mem.clone();
+    local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::LocalAlwaysWins)?;
+
+    assert_eq!(base.get(&id).unwrap().email, "nora@example.com");
+    assert!(base.contains_key(&new_id));
+
+    Ok(())
+}
+
+#[test]
+fn sync_remote_always_wins_overwrites_newer_local() -> Result<(), AppError> {
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let remote_storage = MockStorage::new(HashMap::from([(id, remote)]));
+    let mut base = local_manager.mem.clone();
+    local_manager.sync_from_storage(
+        &mut base,
+        Box::new(remote_storage),
+        &sync::RemoteAlwaysWins,
+    )?;
+
+    let synced = base.get(&id).unwrap();
+    assert_eq!(synced.email, "nora.quinn@example.com");
+    assert_eq!(synced.tag, "friends");
+
+    Ok(())
+}
+
+#[test]
+fn sync_manual_policy_refuses_to_pick_a_side() -> Result<(), AppError> {
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let new_remote = Contact::new(
+        "Owen Park".to_string(),
+        "5559876543".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let new_id = new_remote.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let remote_storage = MockStorage::new(HashMap::from([(id, remote), (new_id, new_remote)]));
+    let mut base = local_manager.mem.clone();
+    let conflicts =
+        local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &sync::Manual)?;
+
+    // The disagreement is queued, the rest of the sync is still applied
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].source, "mock");
+    assert_eq!(
+        conflicts[0].fields,
+        vec!["email".to_string(), "tag".to_string()]
+    );
+    assert_eq!(base.get(&id).unwrap().email, "nora@example.com");
+    assert!(base.contains_key(&new_id));
+
+    Ok(())
+}
+
+/// A library user's policy: take the remote contact but never touch the local tag.
+struct KeepLocalTag;
+
+impl SyncPolicy for KeepLocalTag {
+    fn conflict_resolution(
+        &self,
+        _local: &mut Contact,
+        _remote: &mut Contact,
+    ) -> sync::SyncDecision {
+        sync::SyncDecision::RemoteWins
+    }
+
+    fn merge_changes(&self, local: &mut Contact, remote: &mut Contact) {
+        local.email = remote.email.clone();
+        local.updated_at = remote.updated_at;
+    }
+}
+
+#[test]
+fn sync_accepts_custom_policy() -> Result<(), AppError> {
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let remote_storage = MockStorage::new(HashMap::from([(id, remote)]));
+    let mut base = local_manager.mem.clone();
+    local_manager.sync_from_storage(&mut base, Box::new(remote_storage), &KeepLocalTag)?;
+
+    let synced = base.get(&id).unwrap();
+    assert_eq!(synced.email, "nora.quinn@example.com");
+    assert_eq!(synced.tag, "work");
+
+    Ok(())
+}
+
+// SCENARIO 9: Resolving queued conflicts
+
+#[test]
+fn resolve_conflict_with_merged_contact() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(local);
+
+    let conflicts = local_manager.import_contacts_from_storage(
+        Box::new(MockStorage::new(HashMap::from([(id, remote)]))),
+        &sync::Manual,
+    )?;
+    assert_eq!(conflicts.len(), 1);
+
+    let queue = local_manager.sync_state.conflicts();
+    assert_eq!(queue.load(local_manager.storage.as_ref())?.len(), 1);
+
+    let mut merged = conflicts[0].local.clone();
+    merged.email = conflicts[0].remote.email.clone();
+    local_manager.resolve_conflict(
+        &conflicts[0],
+        sync::ConflictResolution::Merged(Box::new(merged)),
+    )?;
+
+    let resolved = local_manager.mem.get(&id).unwrap();
+    assert_eq!(resolved.email, "nora.quinn@example.com");
+    assert_eq!(resolved.tag, "work");
+    assert!(queue.load(local_manager.storage.as_ref())?.is_empty());
+
+    // The remote version becomes the ancestor for the next sync
+    let ancestors = local_manager
+        .sync_state
+        .snapshot("mock")
+        .load(local_manager.storage.as_ref())?;
+    assert_eq!(ancestors.get(&id).unwrap().tag, "friends");
+
+    Ok(())
+}
+
+#[test]
+fn keep_local_keeps_edits_made_since_the_conflict() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let (local, remote) = diverged_pair(Utc::now());
+    let id = local.id;
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(local);
+
+    let conflicts = local_manager.import_contacts_from_storage(
+        Box::new(MockStorage::new(HashMap::from([(id, remote)]))),
+        &sync::Manual,
+    )?;
+    assert_eq!(conflicts.len(), 1);
+
+    local_manager.mem.get_mut(&id).unwrap().phone = "5559998888".to_string();
+    local_manager.resolve_conflict(&conflicts[0], sync::ConflictResolution::KeepLocal)?;
+
+    let resolved = local_manager.mem.get(&id).unwrap();
+    assert_eq!(resolved.phone, "5559998888");
+    assert_eq!(resolved.tag, "work");
+
+    Ok(())
+}
+
+// SCENARIO 10: Dry run
+
+#[test]
+fn preview_import_reports_changes_without_applying_them() -> Result<(), AppError> {
+    let (local, remote) = diverged_pair(Utc::now() - Duration::seconds(60));
+    let id = local.id;
+
+    // Newer remote edit so last-write-wins takes it
+    let mut remote = remote;
+    remote.updated_at = Utc::now();
+
+    let doomed = Contact::new(
+        "Paul Reed".to_string(),
+        "5550001111".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let mut doomed_remote = doomed.clone();
+    doomed_remote.deleted = true;
+    doomed_remote.updated_at = Utc::now() + Duration::seconds(1);
+
+    let new_remote = Contact::new(
+        "Owen Park".to_string(),
+        "5559876543".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let duplicate = Contact::new(
+        "Paul Reed".to_string(),
+        "5550001111".to_string(),
+        "paul@example.com".to_string(),
+        "".to_string(),
+    );
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local.clone());
+    local_manager.add_contact(doomed.clone());
+
+    let remote_storage = MockStorage::new(HashMap::from([
+        (id, remote),
+        (doomed.id, doomed_remote),
+        (new_remote.id, new_remote),
+        (duplicate.id, duplicate),
+    ]));
+
+    let report = local_manager.preview_import(&remote_storage, &sync::LastWriteWins)?;
+
+    assert_eq!(report.added.len(), 1);
+    assert_eq!(report.added[0].name, "Owen Park");
+
+    assert_eq!(report.updated.len(), 1);
+    let changed: Vec<&str> = report.updated[0]
+        .changes
+        .iter()
+        .map(|c| c.field.as_str())
+        .collect();
+    assert_eq!(changed, vec!["email", "tag"]);
+    assert_eq!(report.updated[0].changes[0].before, "nora@example.com");
+    assert_eq!(report.updated[0].changes[0].after, "nora.quinn@example.com");
+
+    assert_eq!(report.deleted.len(), 1);
+    assert_eq!(report.deleted[0].id, doomed.id);
+
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].email, "paul@example.com");
+    assert!(report.conflicts.is_empty());
+
+    // Nothing was applied
+    assert_eq!(local_manager.mem.len(), 2);
+    assert_eq!(local_manager.mem.get(&id).unwrap().email, local.email);
+    assert!(!local_manager.mem.get(&doomed.id).unwrap().deleted);
+
+    Ok(())
+}
+
+// SCENARIO 11: Bidirectional sync
+
+/// Remote that keeps what is pushed to it. `edits_on_load` simulates another
+/// device writing to it while a sync is running.
+/// `fails_on_save` simulates a push that doesn't go through.
+struct SharedStorage {
+    contacts: Rc<RefCell<HashMap<Uuid, Contact>>>,
+    edits_on_load: bool,
+    fails_on_save: bool,
+}
+
+impl SharedStorage {
+    fn new(contacts: HashMap<Uuid, Contact>) -> Self {
+        Self {
+            contacts: Rc::new(RefCell::new(contacts)),
+            edits_on_load: false,
+            fails_on_save: false,
+        }
+    }
+}
+
+impl ContactStore for SharedStorage {
+    fn load(&self) -> Result<HashMap<Uuid, Contact>, AppError> {
+        let loaded = self.contacts.borrow().clone();
+
+        if self.edits_on_load {
+            let other_device = Contact::new(
+                "Rita Moss".to_string(),
+                "5554443333".to_string(),
+                "".to_string(),
+                "".to_string(),
+            );
+            self.contacts
+                .borrow_mut()
+                .insert(other_device.id, other_device);
+        }
+        Ok(loaded)
+    }
+
+    fn save(&self, contacts: &HashMap<Uuid, Contact>) -> Result<(), AppError> {
+        if self.fails_on_save {
+            return Err(AppError::Synchronization("Remote unreachable".to_string()));
+        }
+        *self.contacts.borrow_mut() = contacts.clone();
+        Ok(())
+    }
+
+    fn get_medium(&self) -> &str {
+        "shared"
+    }
+}
+
+#[test]
+fn sync_with_storage_pulls_then_pushes() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let local_only = Contact::new(
+        "Sam Hill".to_string(),
+        "5551112222".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let remote_only = Contact::new(
+        "Tara Fox".to_string(),
+        "5553334444".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(local_only.clone());
+
+    let remote = SharedStorage::new(HashMap::from([(remote_only.id, remote_only.clone())]));
+    let remote_contacts = remote.contacts.clone();
+
+    let outcome = local_manager.sync_with_storage(Box::new(remote), &sync::LastWriteWins)?;
+
+    assert_eq!(outcome.pulled.added.len(), 1);
+    assert_eq!(outcome.pushed.added.len(), 1);
+    assert_eq!(outcome.pushed.added[0].id, local_only.id);
+
+    assert!(local_manager.mem.contains_key(&remote_only.id));
+    let remote_after = remote_contacts.borrow();
+    assert!(remote_after.contains_key(&local_only.id));
+    assert!(remote_after.contains_key(&remote_only.id));
+
+    Ok(())
+}
+
+#[test]
+fn sync_with_storage_refuses_to_push_if_remote_changed() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let local_only = Contact::new(
+        "Sam Hill".to_string(),
+        "5551112222".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(local_only.clone());
+
+    let mut remote = SharedStorage::new(HashMap::new());
+    remote.edits_on_load = true;
+
+    let result = local_manager.sync_with_storage(Box::new(remote), &sync::LastWriteWins);
+    assert!(matches!(result, Err(AppError::Synchronization(_))));
+
+    // Local book untouched
+    assert_eq!(local_manager.mem.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn sync_with_storage_keeps_local_state_if_the_push_fails() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let local = Contact::new(
+        "Sam Hill".to_string(),
+        "5551112222".to_string(),
+        "sam@example.com".to_string(),
+        "".to_string(),
+    );
+    let id = local.id;
+    let mut remote_version = local.clone();
+    remote_version.email = "sam.hill@example.com".to_string();
+    let remote_only = Contact::new(
+        "Tara Fox".to_string(),
+        "5553334444".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(local);
+
+    let mut remote = SharedStorage::new(HashMap::from([
+        (id, remote_version),
+        (remote_only.id, remote_only.clone()),
+    ]));
+    remote.fails_on_save = true;
+
+    let result = local_manager.sync_with_storage(Box::new(remote), &sync::Manual);
+    assert!(result.is_err());
+
+    // Nothing was pulled, queued or recorded
+    assert!(!local_manager.mem.contains_key(&remote_only.id));
+    assert_eq!(local_manager.mem[&id].email, "sam@example.com");
+    let queue = local_manager.sync_state.conflicts();
+    assert!(queue.load(local_manager.storage.as_ref())?.is_empty());
+    assert!(!dir.path().join("shared.json").exists());
+
+    Ok(())
+}
+
+#[test]
+fn local_changes_sort_after_clocks_pulled_from_a_device_that_is_ahead() -> Result<(), AppError> {
+    let local = Contact::new(
+        "Sam Hill".to_string(),
+        "5551112222".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let id = local.id;
+
+    // Written by a device whose clock is an hour ahead
+    let mut remote_only = Contact::new(
+        "Tara Fox".to_string(),
+        "5553334444".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    remote_only.clock = remote_only
+        .clock
+        .tick_at(Utc::now() + Duration::hours(1), "phone");
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+    local_manager.import_contacts_from_storage(
+        Box::new(MockStorage::new(HashMap::from([(
+            remote_only.id,
+            remote_only.clone(),
+        )]))),
+        &sync::LastWriteWins,
+    )?;
+
+    // A later change to another contact still sorts after what was pulled
+    local_manager.delete_contact(&id)?;
+    assert!(local_manager.mem[&id].clock > remote_only.clock);
+
+    Ok(())
+}
+
+// SCENARIO 12: Deletion rules and tombstones
+
+/// Deleted locally, then edited on the remote by a device that hadn't seen the deletion yet.
+fn deleted_then_edited() -> (Contact, Contact) {
+    let now = Utc::now();
+
+    let mut original = Contact::new(
+        "Uma Stone".to_string(),
+        "5552223333".to_string(),
+        "uma@example.com".to_string(),
+        "".to_string(),
+    );
+    original.clock = Hlc::seeded(now - Duration::seconds(30));
+
+    let mut local = original.clone();
+    local.deleted = true;
+    local.deleted_at = Some(now - Duration::seconds(20));
+    local.deleted_by = Some("laptop".to_string());
+    local.clock = original
+        .clock
+        .tick_at(now - Duration::seconds(20), "laptop");
+
+    let mut remote = original;
+    remote.email = "uma.stone@example.com".to_string();
+    remote.clock = remote.clock.tick_at(now - Duration::seconds(10), "phone");
+
+    (local, remote)
+}
+
+fn sync_with_rule(
+    rule: sync::DeletionRule,
+) -> Result<(Contact, Vec<sync::SyncConflict>), AppError> {
+    let (local, remote) = deleted_then_edited();
+    let id = local.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let policy = sync::WithDeletionRule {
+        policy: Box::new(sync::LastWriteWins),
+        rule,
+    };
+    let mut base = local_manager.mem.clone();
+    let conflicts = local_manager.sync_from_storage(
+        &mut base,
+        Box::new(MockStorage::new(HashMap::from([(id, remote)]))),
+        &policy,
+    )?;
+
+    Ok((base.remove(&id).unwrap(), conflicts))
+}
+
+#[test]
+fn sync_later_edit_resurrects_deleted_contact() -> Result<(), AppError> {
+    let (synced, conflicts) = sync_with_rule(sync::DeletionRule::EditResurrects)?;
+
+    assert!(conflicts.is_empty());
+    assert!(!synced.deleted);
+    assert_eq!(synced.deleted_at, None);
+    assert_eq!(synced.email, "uma.stone@example.com");
+
+    Ok(())
+}
+
+#[test]
+fn sync_delete_wins_over_later_edit() -> Result<(), AppError> {
+    let (synced, conflicts) = sync_with_rule(sync::DeletionRule::DeleteWins)?;
+
+    assert!(conflicts.is_empty());
+    assert!(synced.deleted);
+    assert_eq!(synced.deleted_by.as_deref(), Some("laptop"));
+
+    Ok(())
+}
+
+#[test]
+fn sync_ask_queues_delete_edit_conflict() -> Result<(), AppError> {
+    let (synced, conflicts) = sync_with_rule(sync::DeletionRule::Ask)?;
+
+    assert_eq!(conflicts.len(), 1);
+    assert!(conflicts[0].fields.contains(&"deleted".to_string()));
+    assert!(synced.deleted, "Local is left as it was until resolved");
+
+    Ok(())
+}
+
+#[test]
+fn sync_default_deletion_rule_follows_the_policy() -> Result<(), AppError> {
+    // The remote deleted first and local edited later
+    let (remote, local) = deleted_then_edited();
+    let id = local.id;
+
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let mut base = local_manager.mem.clone();
+    let conflicts = local_manager.sync_from_storage(
+        &mut base,
+        Box::new(MockStorage::new(HashMap::from([(id, remote)]))),
+        &sync::LocalAlwaysWins,
+    )?;
+
+    let synced = base.remove(&id).unwrap();
+    assert!(conflicts.is_empty());
+    assert!(!synced.deleted, "Local always wins, deletion included");
+    assert_eq!(synced.email, "uma.stone@example.com");
+
+    Ok(())
+}
+
+#[test]
+fn sync_remote_tombstone_carries_deletion_metadata() -> Result<(), AppError> {
+    let (remote, local) = deleted_then_edited();
+    let id = local.id;
+
+    // Here the remote deleted first and local edited later
+    let mut local_manager = make_manager()?;
+    local_manager.add_contact(local);
+
+    let policy = sync::WithDeletionRule {
+        policy: Box::new(sync::LastWriteWins),
+        rule: sync::DeletionRule::DeleteWins,
+    };
+    let mut base = local_manager.mem.clone();
+    local_manager.sync_from_storage(
+        &mut base,
+        Box::new(MockStorage::new(HashMap::from([(id, remote)]))),
+        &policy,
+    )?;
+
+    let synced = base.get(&id).unwrap();
+    assert!(synced.deleted);
+    assert_eq!(synced.deleted_by.as_deref(), Some("laptop"));
+    assert!(synced.deleted_at.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn purge_keeps_tombstones_a_remote_has_not_seen() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+
+    let mut contact = Contact::new(
+        "Vera Lane".to_string(),
+        "5557778888".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    let id = contact.id;
+
+    let mut local_manager = make_manager_in(dir.path())?;
+
+    // The remote last saw the contact alive
+    let snapshot = local_manager.sync_state.snapshot("mock");
+    snapshot.save(
+        &HashMap::from([(id, contact.clone())]),
+        local_manager.storage.as_ref(),
+    )?;
+
+    contact.mark_deleted("laptop");
+    contact.deleted_at = Some(Utc::now() - Duration::days(10));
+
+    local_manager.add_contact(contact.clone());
+
+    local_manager.purge_soft_deleted_older_than(1)?;
+    assert!(
+        local_manager.mem.contains_key(&id),
+        "Tombstone must survive until the remote has it"
+    );
+
+    // Once the remote has the deletion, the tombstone can go
+    snapshot.save(
+        &HashMap::from([(id, contact)]),
+        local_manager.storage.as_ref(),
+    )?;
+    local_manager.purge_soft_deleted_older_than(1)?;
+    assert!(!local_manager.mem.contains_key(&id));
+
+    Ok(())
+}
+
+#[test]
+fn purge_fails_when_a_snapshot_cannot_be_read() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("mock.json"), "not json")?;
+
+    let mut contact = Contact::new(
+        "Vera Lane".to_string(),
+        "5557778888".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    contact.mark_deleted("laptop");
+    contact.deleted_at = Some(Utc::now() - Duration::days(10));
+    let id = contact.id;
+
+    let mut local_manager = make_manager_in(dir.path())?;
+    local_manager.add_contact(contact);
+
+    assert!(local_manager.purge_soft_deleted_older_than(1).is_err());
+    assert!(local_manager.mem.contains_key(&id));
+
+    Ok(())
+}
+
+#[test]
+fn export_keeps_no_snapshot() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let mut manager = make_manager_in(dir.path())?;
+    manager.add_contact(Contact::new(
+        "Wale Ojo".to_string(),
+        "5551112222".to_string(),
+        "".to_string(),
+        "".to_string(),
+    ));
+
+    manager.export_contacts_to_storage(Box::new(MockStorage::new(HashMap::new())))?;
+
+    let files: Vec<String> = std::fs::read_dir(dir.path())?
+        .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(files, ["device_id"]);
+    Ok(())
+}
+
+fn old_tombstone(name: &str) -> Contact {
+    let mut contact = Contact::new(
+        name.to_string(),
+        "5553334444".to_string(),
+        "".to_string(),
+        "".to_string(),
+    );
+    contact.mark_deleted("laptop");
+    contact.deleted_at = Some(Utc::now() - Duration::days(40));
+    contact
+}
+
+#[test]
+fn purge_drops_contacts_that_were_only_exported() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let contact = old_tombstone("Kemi Ade");
+    let id = contact.id;
+    let mut exported = contact.clone();
+    exported.deleted = false;
+
+    let mut manager = make_manager_in(dir.path())?;
+    manager.add_contact(exported);
+    manager.export_contacts_to_storage(Box::new(MockStorage::new(HashMap::new())))?;
+
+    manager.mem.insert(id, contact);
+    manager.purge_soft_deleted_older_than(30)?;
+    assert!(!manager.mem.contains_key(&id));
+    Ok(())
+}
+
+#[test]
+fn purge_ignores_snapshots_of_remotes_not_synced_for_the_purge_window() -> Result<(), AppError> {
+    let dir = tempfile::tempdir()?;
+    let contact = old_tombstone("Tobi Ola");
+    let id = contact.id;
+    let mut seen = contact.clone();
+    seen.deleted = false;
+
+    let mut manager = make_manager_in(dir.path())?;
+    manager.add_contact(contact);
+    let snapshot = manager.sync_state.snapshot("old-peer");
+    snapshot.save(&HashMap::from([(id, seen)]), manager.storage.as_ref())?;
+
+    // Synced recently, the remote still holds the tombstone back
+    manager.purge_soft_deleted_older_than(30)?;
+    assert!(manager.mem.contains_key(&id));
+
+    let path = snapshot.path.clone().unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&path)?
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(40 * 86400))?;
+    manager.purge_soft_deleted_older_than(30)?;
+    assert!(!manager.mem.contains_key(&id));
+    // It is still the ancestor of the next sync with that remote
+    assert!(path.exists());
+    Ok(())
+}